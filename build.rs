@@ -149,7 +149,10 @@ fn parse_interpretation(mut json: JsonValue) -> String {
             format!("IfdTypeInterpretation::Offsets {{ lengths: &{lengths} }}")
         }
         "LENGTHS" => "IfdTypeInterpretation::Lengths".to_string(),
-        "BLOB" => "IfdTypeInterpretation::Blob".to_string(),
+        // these are opaque byte sequences for us
+        "BLOB" | "JPEGTABLES" | "ICCPROFILE" | "IPTCNAARECORD" | "EXIFMAKERNOTE" => {
+            "IfdTypeInterpretation::Blob".to_string()
+        }
         _ => "IfdTypeInterpretation::Default".to_string(),
     }
 }
@@ -0,0 +1,10 @@
+#![no_main]
+
+use dng::yaml::IfdYamlParser;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = IfdYamlParser::default().parse_from_str(text);
+    }
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use dng::DngReader;
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(dng) = DngReader::read(Cursor::new(data)) {
+        let path = dng.main_image_data_ifd_path();
+        if let Ok(len) = dng.needed_buffer_length_for_image_data(&path) {
+            // dont allocate absurd amounts of memory for lying length fields
+            if len <= data.len() {
+                let mut buffer = vec![0u8; len];
+                let _ = dng.read_image_data_to_buffer(&path, &mut buffer);
+            }
+        }
+    }
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use dng::{DngReader, DngReaderOptions};
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    let options = DngReaderOptions {
+        lenient: true,
+        ..Default::default()
+    };
+    let _ = DngReader::read_with_options(Cursor::new(data), options);
+});
@@ -0,0 +1,321 @@
+use crate::ifd::{Ifd, IfdEntryRef, IfdPath};
+use crate::{DngReader, DngReaderError, DngReaderOptions, DngReaderWarning, ImageInfo};
+use derivative::Derivative;
+use futures_util::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+
+/// The amount of bytes that is fetched from the underlying reader at once while parsing the IFDs
+const FETCH_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// The async counterpart of [DngReader] for readers implementing [AsyncRead] + [AsyncSeek]
+///
+/// The IFD tree is parsed by the same code as in [DngReader]: the needed parts of the file are
+/// fetched on demand into an in-memory cache, which is then parsed synchronously. The image data
+/// itself is not cached but read directly from the underlying reader.
+///
+/// usage example:
+/// ```rust
+/// use dng::AsyncDngReader;
+/// use futures_util::io::Cursor;
+///
+/// # futures_executor::block_on(async {
+/// let data = std::fs::read("src/testdata/test.dng").expect("couldnt find file");
+/// let dng = AsyncDngReader::read(Cursor::new(data)).await.expect("couldnt read file as dng");
+///
+/// let main_ifd = dng.main_image_data_ifd_path();
+/// let buffer_length = dng.needed_buffer_length_for_image_data(&main_ifd).expect("couldnt read buffer lengths");
+/// let mut buffer = vec![0u8; buffer_length];
+/// dng.read_image_data_to_buffer(&main_ifd, &mut buffer).await.expect("couldnt read to buffer");
+/// println!("successfully read {} bytes into buffer", buffer.len())
+/// # })
+/// ```
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct AsyncDngReader<R: AsyncRead + AsyncSeek + Unpin> {
+    parsed: DngReader<SparseBuffer>,
+    #[derivative(Debug = "ignore")]
+    reader: futures_util::lock::Mutex<R>,
+}
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncDngReader<R> {
+    /// reads and parses the DNG file IFD-tree eagerly (see [DngReader::read]).
+    pub async fn read(reader: R) -> Result<Self, DngReaderError> {
+        Self::read_with_options(reader, DngReaderOptions::default()).await
+    }
+
+    /// reads and parses the DNG file IFD-tree eagerly (see [DngReader::read_with_options]).
+    pub async fn read_with_options(
+        mut reader: R,
+        options: DngReaderOptions,
+    ) -> Result<Self, DngReaderError> {
+        let len = reader.seek(SeekFrom::End(0)).await?;
+        let mut buffer = SparseBuffer::new(len);
+        loop {
+            buffer.seek(SeekFrom::Start(0))?;
+            let result = DngReader::read_with_options(&mut buffer, options).map(|_| ());
+            match buffer.missing.take() {
+                Some(missing) => buffer.fetch(&mut reader, missing).await?,
+                None => {
+                    result?;
+                    break;
+                }
+            }
+        }
+
+        buffer.seek(SeekFrom::Start(0))?;
+        Ok(Self {
+            parsed: DngReader::read_with_options(buffer, options)?,
+            reader: futures_util::lock::Mutex::new(reader),
+        })
+    }
+
+    /// see [DngReader::warnings]
+    pub fn warnings(&self) -> &[DngReaderWarning] {
+        self.parsed.warnings()
+    }
+
+    /// returns the first toplevel IFD of the DNG file.
+    pub fn get_ifd0(&self) -> &Ifd {
+        self.parsed.get_ifd0()
+    }
+
+    /// see [DngReader::ifds]
+    pub fn ifds(&self) -> &[Ifd] {
+        self.parsed.ifds()
+    }
+
+    /// see [DngReader::get_ifd]
+    pub fn get_ifd(&self, n: usize) -> Option<&Ifd> {
+        self.parsed.get_ifd(n)
+    }
+
+    pub fn get_entry_by_path<'a>(&'a self, path: &'a IfdPath) -> Option<IfdEntryRef<'a>> {
+        self.parsed.get_entry_by_path(path)
+    }
+
+    /// see [DngReader::get_ifd_by_path]
+    pub fn get_ifd_by_path<'a>(&'a self, path: &'a IfdPath) -> Option<&'a Ifd> {
+        self.parsed.get_ifd_by_path(path)
+    }
+
+    /// see [DngReader::needed_buffer_size_for_offsets]
+    pub fn needed_buffer_size_for_offsets(
+        &self,
+        entry: IfdEntryRef,
+    ) -> Result<usize, DngReaderError> {
+        self.parsed.needed_buffer_size_for_offsets(entry)
+    }
+    /// see [DngReader::read_offsets_to_buffer]
+    pub async fn read_offsets_to_buffer(
+        &self,
+        entry: IfdEntryRef<'_>,
+        buffer: &mut [u8],
+    ) -> Result<(), DngReaderError> {
+        let offset = self.parsed.offsets_position(entry, buffer.len())?;
+        let mut reader = self.reader.lock().await;
+        reader.seek(SeekFrom::Start(offset as u64)).await?;
+        reader.read_exact(buffer).await?;
+        Ok(())
+    }
+
+    /// see [DngReader::images]
+    pub fn images(&self) -> Vec<ImageInfo> {
+        self.parsed.images()
+    }
+
+    /// see [DngReader::main_image_data_ifd_path]
+    pub fn main_image_data_ifd_path(&self) -> IfdPath {
+        self.parsed.main_image_data_ifd_path()
+    }
+
+    /// see [DngReader::needed_buffer_length_for_image_data]
+    pub fn needed_buffer_length_for_image_data(
+        &self,
+        ifd_path: &IfdPath,
+    ) -> Result<usize, DngReaderError> {
+        self.parsed.needed_buffer_length_for_image_data(ifd_path)
+    }
+    /// see [DngReader::read_image_data_to_buffer]
+    pub async fn read_image_data_to_buffer(
+        &self,
+        ifd_path: &IfdPath,
+        buffer: &mut [u8],
+    ) -> Result<(), DngReaderError> {
+        let mut reader = self.reader.lock().await;
+        let mut buffer_offset = 0;
+        for (offset, length) in self.parsed.image_data_segments(ifd_path)? {
+            reader.seek(SeekFrom::Start(offset as u64)).await?;
+            let buffer_slice =
+                &mut buffer[(buffer_offset as usize)..((buffer_offset + length) as usize)];
+            reader.read_exact(buffer_slice).await?;
+
+            buffer_offset += length;
+        }
+        Ok(())
+    }
+}
+
+/// A synchronous reader over the parts of a file that were already fetched.
+/// Reading a part that was not fetched yet fails and remembers the missing range.
+struct SparseBuffer {
+    /// non-overlapping `(start, data)` pairs sorted by their start
+    chunks: Vec<(u64, Vec<u8>)>,
+    len: u64,
+    position: u64,
+    missing: Option<(u64, u64)>,
+}
+impl SparseBuffer {
+    fn new(len: u64) -> Self {
+        Self {
+            chunks: Vec::new(),
+            len,
+            position: 0,
+            missing: None,
+        }
+    }
+
+    async fn fetch(
+        &mut self,
+        reader: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        (start, len): (u64, u64),
+    ) -> io::Result<()> {
+        let start = start - start % FETCH_CHUNK_SIZE;
+        let end = (start + len.max(FETCH_CHUNK_SIZE)).min(self.len);
+        let mut data = vec![0u8; (end - start) as usize];
+        reader.seek(SeekFrom::Start(start)).await?;
+        reader.read_exact(&mut data).await?;
+        self.insert(start, data);
+        Ok(())
+    }
+
+    fn insert(&mut self, start: u64, data: Vec<u8>) {
+        let end = start + data.len() as u64;
+        let mut merged_start = start;
+        let mut merged = Vec::new();
+        let mut rest = Vec::new();
+        for (chunk_start, chunk) in self.chunks.drain(..) {
+            let chunk_end = chunk_start + chunk.len() as u64;
+            if chunk_end < start || chunk_start > end {
+                rest.push((chunk_start, chunk));
+            } else {
+                merged_start = merged_start.min(chunk_start);
+                merged.push((chunk_start, chunk));
+            }
+        }
+        let merged_end = merged
+            .iter()
+            .map(|(chunk_start, chunk)| chunk_start + chunk.len() as u64)
+            .fold(end, u64::max);
+        let mut buffer = vec![0u8; (merged_end - merged_start) as usize];
+        for (chunk_start, chunk) in merged.iter().chain([(start, data)].iter()) {
+            let offset = (chunk_start - merged_start) as usize;
+            buffer[offset..offset + chunk.len()].copy_from_slice(chunk);
+        }
+        rest.push((merged_start, buffer));
+        rest.sort_by_key(|(chunk_start, _)| *chunk_start);
+        self.chunks = rest;
+    }
+}
+impl Read for SparseBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.len {
+            return Ok(0);
+        }
+        let position = self.position;
+        let chunk = self
+            .chunks
+            .iter()
+            .find(|(start, chunk)| *start <= position && position < start + chunk.len() as u64);
+        if let Some((start, chunk)) = chunk {
+            let available = &chunk[(position - start) as usize..];
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            self.position += n as u64;
+            Ok(n)
+        } else {
+            self.missing = Some((position, buf.len() as u64));
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("data at {position} was not fetched yet"),
+            ))
+        }
+    }
+}
+impl Seek for SparseBuffer {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len.checked_add_signed(n),
+            SeekFrom::Current(n) => self.position.checked_add_signed(n),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::{Ifd, IfdValue};
+    use crate::tags::{ifd, IfdType};
+    use crate::{write_dng_async, AsyncDngReader, DngReader, FileType};
+    use futures_executor::block_on;
+    use futures_util::io::Cursor;
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_async_read_matches_sync_read() {
+        let data = fs::read("src/testdata/test.dng").unwrap();
+        let sync = DngReader::read(std::io::Cursor::new(data.clone())).unwrap();
+        let dng = block_on(AsyncDngReader::read(Cursor::new(data))).unwrap();
+        assert_eq!(
+            format!("{:?}", sync.get_ifd0()),
+            format!("{:?}", dng.get_ifd0())
+        );
+
+        let path = dng.main_image_data_ifd_path();
+        let mut sync_buffer = vec![0u8; sync.needed_buffer_length_for_image_data(&path).unwrap()];
+        sync.read_image_data_to_buffer(&path, &mut sync_buffer)
+            .unwrap();
+        let mut buffer = vec![0u8; dng.needed_buffer_length_for_image_data(&path).unwrap()];
+        block_on(dng.read_image_data_to_buffer(&path, &mut buffer)).unwrap();
+        assert_eq!(sync_buffer, buffer);
+    }
+
+    #[test]
+    fn test_async_write_read_roundtrip() {
+        // make the image data larger than one fetch chunk, so that the IFDs end up behind it
+        let image_data = (0..200_000u32).map(|x| x as u8).collect::<Vec<_>>();
+        let mut ifd = Ifd::new(IfdType::Ifd);
+        ifd.insert(ifd::NewSubfileType, 0u32);
+        ifd.insert(ifd::Copyright, "async test");
+        ifd.insert(
+            ifd::StripOffsets,
+            IfdValue::Offsets(Arc::new(image_data.clone())),
+        );
+        ifd.insert(ifd::StripByteCounts, image_data.len() as u32);
+
+        let mut file = Cursor::new(Vec::new());
+        block_on(write_dng_async(&mut file, true, FileType::Dng, vec![ifd])).unwrap();
+
+        file.set_position(0);
+        let dng = block_on(AsyncDngReader::read(file)).unwrap();
+        let copyright = dng
+            .get_ifd0()
+            .entries()
+            .iter()
+            .find(|entry| entry.tag == ifd::Copyright.as_maybe())
+            .map(|entry| format!("{:?}", entry.value));
+        assert_eq!(copyright, Some("Ascii(\"async test\")".to_string()));
+
+        let path = dng.main_image_data_ifd_path();
+        let mut buffer = vec![0u8; dng.needed_buffer_length_for_image_data(&path).unwrap()];
+        block_on(dng.read_image_data_to_buffer(&path, &mut buffer)).unwrap();
+        assert_eq!(buffer, image_data);
+    }
+}
@@ -1,18 +1,27 @@
-use clap::{arg, Parser};
-use dng::yaml::IfdYamlParser;
-use dng::DngWriter;
+use clap::Parser;
+use dng::ifd::{Ifd, IfdPath};
+use dng::tags::IfdType;
+use dng::xml::{DcpXmlError, DcpXmlParser};
+use dng::yaml::{IfdYamlParser, IfdYamlParserError};
 use dng::FileType;
+use dng::{embed_previews, DngReader, DngWriter, PreviewOptions};
+use dng::{raw_dng_ifd, remove_image_data, CfaPattern, RawImageOptions, RawPacking};
 use std::fs::{File, OpenOptions};
-use std::io::Read;
+use std::io::{Cursor, Read};
 use std::path::Path;
+use std::process::exit;
 
 /// Assemble a DNG file from some of other dng files, plain raw files and metadata
 #[derive(clap::Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// input YAML file to get the metadata from
+    #[arg(long, required_unless_present_any = ["xml", "raw"], conflicts_with = "xml")]
+    yaml: Option<String>,
+
+    /// input DCP profile in the XML format of dcpTool (implies --dcp)
     #[arg(long)]
-    yaml: String,
+    xml: Option<String>,
 
     // write the DCP magic bytes (DNG Camera profile) instead of the DNG ones
     #[arg(long, action)]
@@ -21,30 +30,137 @@ struct Args {
     // write a big endian DNG (default: little endian)
     #[arg(short = 'b', long, action)]
     big_endian: bool,
+
+    /// render a preview from the raw image data and embed it as thumbnail and preview SubIFD
+    #[arg(long, action)]
+    preview: bool,
+
+    /// YAML file whose IFDs are merged into the ones of the input, overwriting entries of the same
+    /// tag (can be given multiple times, later files win)
+    #[arg(long)]
+    overlay: Vec<String>,
+
+    /// set the entry at a path after applying the overlays, e.g. `ExifIFD.ExposureTime=1/50` (can be
+    /// given multiple times)
+    #[arg(long = "set", value_name = "PATH=VALUE")]
+    set: Vec<String>,
+
+    /// raw sensor dump to use as the main image of the DNG (the entries describing the image data
+    /// and defaults for a valid DNG are filled in automatically, the YAML can be left out)
+    #[arg(long, requires_all = ["width", "height"], conflicts_with = "xml")]
+    raw: Option<String>,
+
+    /// width of the raw image in pixels
+    #[arg(long, requires = "raw")]
+    width: Option<u32>,
+
+    /// height of the raw image in pixels
+    #[arg(long, requires = "raw")]
+    height: Option<u32>,
+
+    /// bit depth of the samples of the raw image
+    #[arg(long, requires = "raw", default_value_t = 16)]
+    bits: u32,
+
+    /// layout of the samples of the raw image: `unpacked` (one byte / 16 bit word per sample),
+    /// `msb` or `lsb` (packed without padding, most / least significant bit first)
+    #[arg(long, requires = "raw", default_value = "unpacked")]
+    packing: RawPacking,
+
+    /// the unpacked 16 bit samples of the raw image are big endian (default: little endian)
+    #[arg(long, requires = "raw", action)]
+    raw_big_endian: bool,
+
+    /// CFA pattern of the raw image, listed row by row
+    #[arg(long, requires = "raw", default_value = "RGGB")]
+    cfa: CfaPattern,
+
+    /// existing DNG to take the metadata for the raw image from (its image data is dropped, the
+    /// entries of the YAML file are applied on top)
+    #[arg(long, requires = "raw")]
+    base: Option<String>,
 }
 
 fn main() {
     let args = Args::parse();
-    let yaml_path = Path::new(&args.yaml);
-    let file_type = match args.dcp {
+    let input = (args.yaml.as_ref().or(args.xml.as_ref()))
+        .or(args.raw.as_ref())
+        .unwrap();
+    let input_path = Path::new(input);
+    let file_type = match args.dcp || args.xml.is_some() {
         true => FileType::Dcp,
         false => FileType::Dng,
     };
 
-    let mut file = File::open(yaml_path).expect("Cannot find YAML file!");
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-
-    let ifd =
-        IfdYamlParser::new(yaml_path.parent().unwrap().to_path_buf()).parse_from_str(&contents);
-    let ifd = match ifd {
-        Ok(ifd) => ifd,
-        Err(e) => panic!("{e}"),
+    let mut ifds = if args.xml.is_some() {
+        match DcpXmlParser::default().parse_from_str(&read_to_string(input)) {
+            Ok(ifd) => vec![ifd],
+            Err(e @ DcpXmlError::Invalid { .. }) => {
+                eprintln!("error: {input}:{e}");
+                exit(1);
+            }
+            Err(e) => {
+                eprintln!("error: {input}: {e}");
+                exit(1);
+            }
+        }
+    } else if let Some(yaml) = &args.yaml {
+        read_yaml(yaml)
+    } else {
+        vec![]
     };
 
-    let dcp_file_path = yaml_path.parent().unwrap().join(format!(
+    if let Some(raw) = &args.raw {
+        let mut metadata = match &args.base {
+            Some(base) => read_base(base),
+            None => Ifd::new(IfdType::Ifd),
+        };
+        if !ifds.is_empty() {
+            metadata.insert_from_other(ifds.remove(0));
+        }
+        let data = std::fs::read(raw).unwrap_or_else(|e| {
+            eprintln!("error: cant read {raw}: {e}");
+            exit(1);
+        });
+        let options = RawImageOptions {
+            width: args.width.unwrap(),
+            height: args.height.unwrap(),
+            bits_per_sample: args.bits,
+            packing: args.packing,
+            little_endian: !args.raw_big_endian,
+            cfa_pattern: args.cfa,
+        };
+        match raw_dng_ifd(&data, &options, metadata, !args.big_endian) {
+            Ok(ifd0) => ifds.insert(0, ifd0),
+            Err(e) => {
+                eprintln!("error: {raw}: {e}");
+                exit(1);
+            }
+        }
+    }
+
+    for overlay in &args.overlay {
+        for (i, overlay_ifd) in read_yaml(overlay).into_iter().enumerate() {
+            match ifds.get_mut(i) {
+                Some(ifd) => ifd.insert_from_other(overlay_ifd),
+                None => ifds.push(overlay_ifd),
+            }
+        }
+    }
+    for assignment in &args.set {
+        if let Err(e) = set_entry(&mut ifds, assignment) {
+            eprintln!("error: --set {assignment}: {e}");
+            exit(1);
+        }
+    }
+
+    if args.preview {
+        embed_rendered_preview(&mut ifds, file_type, !args.big_endian);
+    }
+
+    let dcp_file_path = input_path.parent().unwrap().join(format!(
         "{}.{}",
-        yaml_path.file_stem().unwrap().to_str().unwrap(),
+        input_path.file_stem().unwrap().to_str().unwrap(),
         file_type.extension(),
     ));
     let dcp_file = OpenOptions::new()
@@ -53,5 +169,152 @@ fn main() {
         .truncate(true)
         .open(dcp_file_path)
         .unwrap();
-    DngWriter::write_dng(dcp_file, !args.big_endian, file_type, vec![ifd]).unwrap();
+    DngWriter::write_dng(dcp_file, !args.big_endian, file_type, ifds).unwrap();
+}
+
+/// Renders a preview of the raw image and embeds it into IFD0. The image data is written in the
+/// byte order of the output file, so `is_little_endian` has to match the one used for writing.
+fn embed_rendered_preview(ifds: &mut [Ifd], file_type: FileType, is_little_endian: bool) {
+    // the raw data is only resolved when writing, so we render from an in-memory DNG
+    let mut buffer = Vec::new();
+    DngWriter::write_dng(
+        Cursor::new(&mut buffer),
+        is_little_endian,
+        file_type,
+        ifds.to_vec(),
+    )
+    .unwrap();
+    let image = DngReader::read(Cursor::new(buffer))
+        .unwrap()
+        .render_preview()
+        .expect("Couldnt render a preview from the raw image data!");
+    embed_previews(&mut ifds[0], &image, &PreviewOptions::default());
+}
+
+fn read_to_string(path: &str) -> String {
+    let mut contents = String::new();
+    if let Err(e) = File::open(path).and_then(|mut file| file.read_to_string(&mut contents)) {
+        eprintln!("error: cant read {path}: {e}");
+        exit(1);
+    }
+    contents
+}
+
+/// Parses the IFD chain of a YAML file (exiting with all errors if that fails)
+fn read_yaml(path: &str) -> Vec<Ifd> {
+    let ifds = IfdYamlParser::new(Path::new(path).parent().unwrap().to_path_buf())
+        .parse_chain_from_str(&read_to_string(path));
+    match ifds {
+        Ok(ifds) => ifds,
+        Err(e) => {
+            for error in e.errors() {
+                match error {
+                    // located errors start with the line and column
+                    IfdYamlParserError::Located { .. } => eprintln!("error: {path}:{error}\n"),
+                    _ => eprintln!("error: {path}: {error}\n"),
+                }
+            }
+            exit(1);
+        }
+    }
+}
+
+/// Reads IFD0 of an existing DNG without its image data to use it as metadata for a raw image
+fn read_base(path: &str) -> Ifd {
+    let dng = File::open(path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| DngReader::read(file).map_err(|e| format!("{e:?}")));
+    match dng {
+        Ok(dng) => {
+            let mut ifd0 = dng.get_ifd0().clone();
+            remove_image_data(&mut ifd0);
+            ifd0
+        }
+        Err(e) => {
+            eprintln!("error: cant read {path}: {e}");
+            exit(1);
+        }
+    }
+}
+
+/// Applies a `PATH=VALUE` assignment. Paths start in IFD0 unless they start with another toplevel
+/// IFD (e.g. `IFD1.Copyright`), files in values are relative to the working directory.
+fn set_entry(ifds: &mut [Ifd], assignment: &str) -> Result<(), String> {
+    let (entry_path, value) = assignment
+        .split_once('=')
+        .ok_or("expected an assignment like PATH=VALUE")?;
+    let entry_path: IfdPath = entry_path.parse()?;
+    let tag = *entry_path
+        .last_tag()
+        .ok_or_else(|| format!("'{entry_path:?}' doesnt contain a tag"))?;
+    let value = IfdYamlParser::default()
+        .parse_value(tag, value)
+        .map_err(|e| format!("cant parse value for {entry_path:?}: {e}"))?;
+    let index = entry_path.get_chain_index().unwrap_or(0);
+    ifds.get_mut(index as usize)
+        .ok_or_else(|| format!("there is no IFD{index}"))?
+        .insert_by_path(&entry_path, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{embed_rendered_preview, set_entry};
+    use dng::ifd::{Ifd, IfdValue};
+    use dng::tags::{ifd, IfdType};
+    use dng::{raw_dng_ifd, DngReader, DngWriter, FileType, RawImageOptions, RawPacking};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_set_entry() {
+        let mut ifds = vec![Ifd::new(IfdType::Ifd), Ifd::new(IfdType::Ifd)];
+        set_entry(&mut ifds, "ImageWidth=5").unwrap();
+        set_entry(&mut ifds, "IFD1.Orientation=3").unwrap();
+        set_entry(&mut ifds, "ExifIFD.ExposureTime=1/50").unwrap();
+        assert_eq!(ifds[0].get(ifd::ImageWidth), Some(&IfdValue::Short(5)));
+        assert_eq!(ifds[1].get(ifd::Orientation), Some(&IfdValue::Short(3)));
+        assert!(ifds[0].get(ifd::ExifIFD).is_some());
+        assert!(set_entry(&mut ifds, "IFD2.ImageWidth=5").is_err());
+        assert!(set_entry(&mut ifds, "ImageWidth").is_err());
+    }
+
+    #[test]
+    fn test_big_endian_preview() {
+        // a 16 bit image, whose samples change a lot when their bytes are swapped
+        let raw: Vec<u8> = (0..8 * 8u16)
+            .flat_map(|i| (i * 1000 % 4096).to_le_bytes())
+            .collect();
+        let options = RawImageOptions {
+            width: 8,
+            height: 8,
+            bits_per_sample: 16,
+            packing: RawPacking::Unpacked,
+            little_endian: true,
+            cfa_pattern: "RGGB".parse().unwrap(),
+        };
+        let previews = [true, false].map(|is_little_endian| {
+            let metadata = Ifd::new(IfdType::Ifd);
+            let ifd0 = raw_dng_ifd(&raw, &options, metadata, is_little_endian).unwrap();
+            let mut ifds = vec![ifd0];
+            embed_rendered_preview(&mut ifds, FileType::Dng, is_little_endian);
+            let mut data = Vec::new();
+            DngWriter::write_dng(
+                Cursor::new(&mut data),
+                is_little_endian,
+                FileType::Dng,
+                ifds,
+            )
+            .unwrap();
+            let dng = DngReader::read(Cursor::new(data)).unwrap();
+            assert_eq!(dng.is_little_endian(), is_little_endian);
+            let preview = dng.previews().into_iter().next().unwrap();
+            // the extracted TIFF has the byte order of the file, so only its pixels are compared
+            let tiff = DngReader::read(Cursor::new(dng.extract_image(&preview).unwrap().data));
+            let tiff = tiff.unwrap();
+            let path = tiff.main_image_data_ifd_path();
+            let mut pixels = vec![0u8; tiff.needed_buffer_length_for_image_data(&path).unwrap()];
+            tiff.read_image_data_to_buffer(&path, &mut pixels).unwrap();
+            pixels
+        });
+        assert_eq!(previews[0], previews[1]);
+    }
 }
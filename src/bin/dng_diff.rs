@@ -0,0 +1,167 @@
+use clap::Parser;
+use dng::ifd::{IfdDiffOptions, IfdPath, IfdValue};
+use dng::tags::IfdTypeInterpretation;
+use dng::yaml::{IfdDiffPrinter, IfdYamlDumper};
+use dng::{DngReader, DngReaderOptions, Md5};
+use std::fs::File;
+use std::io::{IsTerminal, Read, Seek};
+use std::process::exit;
+
+/// Compare the IFD metadata of two TIFF / DNG / DCP files
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// the old file
+    old: String,
+    /// the new file
+    new: String,
+    /// convert Rational and SRational types to float for better readability (this is lossy)
+    #[arg(short = 'f', long, action)]
+    dump_rational_as_float: bool,
+    /// treat floats and rationals as equal if they differ by at most this amount
+    #[arg(short = 't', long, default_value_t = 0.0)]
+    tolerance: f64,
+    /// also compare the MD5 checksums of the image data of every strip / tile
+    #[arg(short = 'c', long, action)]
+    checksums: bool,
+    /// skip broken entries and IFDs instead of failing (the problems are reported on stderr)
+    #[arg(short = 'l', long, action)]
+    lenient: bool,
+    /// never print ANSI color codes (they are omitted automatically if stdout is not a terminal)
+    #[arg(long, action)]
+    no_color: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    let read = |path: &str| {
+        let file = File::open(path).unwrap_or_else(|e| panic!("Cannot open '{path}': {e}"));
+        let options = DngReaderOptions {
+            lenient: args.lenient,
+            ..Default::default()
+        };
+        let dng = DngReader::read_with_options(file, options)
+            .unwrap_or_else(|e| panic!("Couldnt parse '{path}': {e}"));
+        for warning in dng.warnings() {
+            eprintln!("warning: {path}: {warning}");
+        }
+        dng
+    };
+    let old = read(&args.old);
+    let new = read(&args.new);
+
+    let printer = IfdDiffPrinter {
+        dumper: IfdYamlDumper {
+            dump_rational_as_float: args.dump_rational_as_float,
+            ..Default::default()
+        },
+        color: !args.no_color && std::io::stdout().is_terminal(),
+    };
+    let options = IfdDiffOptions {
+        float_tolerance: args.tolerance,
+        rational_tolerance: args.tolerance,
+    };
+
+    let mut output = String::new();
+    for i in 0..old.ifds().len().max(new.ifds().len()) {
+        output += &printer.ifd(i, old.get_ifd(i), new.get_ifd(i), &options);
+
+        if args.checksums {
+            let old_checksums = image_data_checksums(&old, i);
+            let new_checksums = image_data_checksums(&new, i);
+            for (path, old_checksum) in &old_checksums {
+                let new_checksum = new_checksums.iter().find(|(p, _)| p == path);
+                if new_checksum.map(|(_, c)| c) != Some(old_checksum) {
+                    output += &printer.checksum(
+                        i,
+                        path,
+                        Some(old_checksum),
+                        new_checksum.map(|(_, c)| &c[..]),
+                    );
+                }
+            }
+            for (path, new_checksum) in &new_checksums {
+                if !old_checksums.iter().any(|(p, _)| p == path) {
+                    output += &printer.checksum(i, path, None, Some(new_checksum));
+                }
+            }
+        }
+    }
+
+    let has_differences = !output.is_empty();
+    print!("{}{output}", printer.header(&args.old, &args.new));
+    if has_differences {
+        exit(1);
+    }
+}
+
+/// Computes the MD5 digest of the data behind every OFFSETS entry (i.e. every strip / tile) of the
+/// toplevel IFD with the given index. The returned paths are relative to that IFD.
+fn image_data_checksums<R: Read + Seek>(
+    dng: &DngReader<R>,
+    chain_index: usize,
+) -> Vec<(IfdPath, [u8; 16])> {
+    let Some(ifd) = dng.ifds().get(chain_index) else {
+        return vec![];
+    };
+    let offsets_paths = ifd.find_entries(|entry| {
+        matches!(
+            entry.tag.get_type_interpretation(),
+            Some(IfdTypeInterpretation::Offsets { .. })
+        ) && !matches!(entry.value, IfdValue::List(_))
+    });
+    offsets_paths
+        .into_iter()
+        .filter_map(|path| {
+            // the lengths are looked up by the path of the entry, so it must include the IFD
+            let absolute_path = IfdPath::chain_index(chain_index as u16).chain_path(&path);
+            let entry = dng.get_entry_by_path(&absolute_path)?;
+            let size = dng.needed_buffer_size_for_offsets(entry).ok()?;
+            let mut buffer = vec![0u8; size];
+            dng.read_offsets_to_buffer(entry, &mut buffer).ok()?;
+            let mut md5 = Md5::default();
+            md5.update(&buffer);
+            Some((path, md5.finalize()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image_data_checksums;
+    use dng::ifd::{Ifd, IfdValue};
+    use dng::tags::{ifd, IfdType};
+    use dng::{DngReader, DngWriter, FileType};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_checksums_of_ifd_chain() {
+        let read = |ifd1_data: Vec<u8>| {
+            let image_ifd = |data: Vec<u8>| {
+                let mut ifd = Ifd::new(IfdType::Ifd);
+                ifd.insert(ifd::StripByteCounts, data.len() as u32);
+                ifd.insert(ifd::StripOffsets, IfdValue::Offsets(Arc::new(data)));
+                ifd
+            };
+            let ifds = vec![image_ifd(vec![1, 2, 3, 4]), image_ifd(ifd1_data)];
+            let mut data = Vec::new();
+            DngWriter::write_dng(Cursor::new(&mut data), true, FileType::Dng, ifds).unwrap();
+            DngReader::read(Cursor::new(data)).unwrap()
+        };
+        // IFD1 has a different length than IFD0, so it must not use the StripByteCounts of IFD0
+        let old = read(vec![5, 6, 7, 8, 9, 10]);
+        let new = read(vec![5, 6, 7, 8, 9, 11]);
+
+        assert_eq!(image_data_checksums(&old, 0), image_data_checksums(&new, 0));
+        // the checksums are MD5 digests, so they can be compared with other tools (e.g. md5sum)
+        let md5_of_1234 = 0x08d6c05a21512a79a1dfeb9d2a8f262fu128.to_be_bytes();
+        assert_eq!(image_data_checksums(&old, 0)[0].1, md5_of_1234);
+        let old_checksums = image_data_checksums(&old, 1);
+        let new_checksums = image_data_checksums(&new, 1);
+        assert_eq!(old_checksums.len(), 1);
+        assert_eq!(new_checksums.len(), 1);
+        assert_eq!(old_checksums[0].0, new_checksums[0].0);
+        assert_ne!(old_checksums[0].1, new_checksums[0].1);
+    }
+}
@@ -0,0 +1,81 @@
+use clap::Parser;
+use dng::{DngReader, DngReaderOptions};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Extract a preview / thumbnail of a DNG as a standalone JPEG or TIFF file
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// input file to extract the preview from
+    file: String,
+    /// which preview to extract (0 is the largest one)
+    #[arg(short = 'n', long, default_value_t = 0)]
+    index: usize,
+    /// output file (default: next to the input file with a `_preview` suffix)
+    #[arg(short = 'o', long)]
+    output: Option<PathBuf>,
+    /// only list the available previews
+    #[arg(long, action)]
+    list: bool,
+    /// skip broken entries and IFDs instead of failing (the problems are reported on stderr)
+    #[arg(short = 'l', long, action)]
+    lenient: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    let file_path = Path::new(&args.file);
+    let file = File::open(file_path).expect("Cannot find input file!");
+    let options = DngReaderOptions {
+        lenient: args.lenient,
+        ..Default::default()
+    };
+    let dng = DngReader::read_with_options(file, options).expect("Couldnt parse DNG file!");
+    for warning in dng.warnings() {
+        eprintln!("warning: {warning}");
+    }
+
+    let previews = dng.previews();
+    if args.list {
+        for (i, preview) in previews.iter().enumerate() {
+            println!(
+                "{i}: {:?} {}x{} compression {:?} at {:?}",
+                preview.role,
+                preview.width,
+                preview.height,
+                preview.compression.unwrap_or(1),
+                preview.path
+            );
+        }
+        return;
+    }
+
+    let preview = previews.get(args.index).unwrap_or_else(|| {
+        panic!(
+            "The file contains only {} previews / thumbnails",
+            previews.len()
+        )
+    });
+    let extracted = dng
+        .extract_image(preview)
+        .expect("Couldnt extract the preview!");
+
+    let output = args.output.unwrap_or_else(|| {
+        let basename = file_path.file_stem().unwrap().to_str().unwrap();
+        file_path.with_file_name(format!(
+            "{basename}_preview.{}",
+            extracted.format.extension()
+        ))
+    });
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&output)
+        .unwrap()
+        .write_all(&extracted.data)
+        .unwrap();
+    println!("{}", output.display());
+}
@@ -0,0 +1,121 @@
+use clap::Parser;
+use dng::ifd::IfdPath;
+use dng::{ConflictPolicy, DngEditor, DngMerger, DngReader, DngReaderOptions};
+use dng::{MergeSelection, TagCategory};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::process::exit;
+
+/// Copy metadata (e.g. a calibrated profile or the EXIF data) from one DNG into others, keeping their image data
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// the file to copy the metadata from
+    source: String,
+    /// the files to merge the metadata into (they are edited in place unless --output-dir is given)
+    #[arg(required = true)]
+    targets: Vec<String>,
+    /// copy a category of tags: color, exif, gps, lens or profile (can be given multiple times)
+    #[arg(short = 'c', long)]
+    category: Vec<TagCategory>,
+    /// copy the entry or subtree at a path, e.g. `ExifIFD` or `IFD1.Copyright` (can be given
+    /// multiple times)
+    #[arg(short = 'p', long)]
+    path: Vec<IfdPath>,
+    /// what to do if a target already contains a different value: overwrite, keep or fail
+    #[arg(long, default_value = "overwrite")]
+    conflicts: ConflictPolicy,
+    /// write compacted copies of the merged files to this directory instead of editing them in
+    /// place
+    #[arg(short = 'o', long)]
+    output_dir: Option<String>,
+    /// only print the entries that would be changed instead of writing them
+    #[arg(short = 'n', long, action)]
+    dry_run: bool,
+    /// skip broken entries and IFDs instead of failing (the problems are reported on stderr)
+    #[arg(short = 'l', long, action)]
+    lenient: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut selections: Vec<_> = args
+        .category
+        .iter()
+        .map(|category| MergeSelection::Category(*category))
+        .collect();
+    selections.extend(args.path.iter().cloned().map(MergeSelection::Path));
+    if selections.is_empty() {
+        eprintln!("error: nothing to copy was selected (use --category or --path)");
+        exit(2);
+    }
+    let merger = DngMerger {
+        selections,
+        policy: args.conflicts,
+    };
+
+    let options = DngReaderOptions {
+        lenient: args.lenient,
+        ..Default::default()
+    };
+    let source = File::open(&args.source)
+        .map_err(|e| e.to_string())
+        .and_then(|file| DngReader::read_with_options(file, options).map_err(|e| e.to_string()));
+    let source = match source {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("error: {}: {e}", args.source);
+            exit(1);
+        }
+    };
+
+    let mut failed = false;
+    for target in &args.targets {
+        if let Err(e) = merge_into(target, &source, &merger, &args) {
+            eprintln!("error: {target}: {e}");
+            failed = true;
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+fn merge_into(
+    path: &str,
+    source: &DngReader<File>,
+    merger: &DngMerger,
+    args: &Args,
+) -> Result<(), String> {
+    let in_place = !args.dry_run && args.output_dir.is_none();
+    let file = OpenOptions::new()
+        .read(true)
+        .write(in_place)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    let options = DngReaderOptions {
+        lenient: args.lenient,
+        ..Default::default()
+    };
+    let mut editor = DngEditor::open_with_options(file, options).map_err(|e| e.to_string())?;
+    for warning in editor.reader().warnings() {
+        eprintln!("warning: {path}: {warning}");
+    }
+
+    let changed = merger
+        .merge(source, &mut editor.ifds_mut()[0])
+        .map_err(|e| e.to_string())?;
+    if args.dry_run {
+        println!("{path}:");
+        for entry_path in changed {
+            println!("  {entry_path:?}");
+        }
+    } else if let Some(output_dir) = &args.output_dir {
+        let output_path = Path::new(output_dir).join(Path::new(path).file_name().unwrap());
+        let output = File::create(&output_path).map_err(|e| e.to_string())?;
+        editor.compact_to(output).map_err(|e| e.to_string())?;
+    } else if editor.has_changes() {
+        editor.commit().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
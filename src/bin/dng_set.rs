@@ -0,0 +1,115 @@
+use clap::Parser;
+use dng::ifd::{IfdDiffOptions, IfdPath};
+use dng::yaml::{IfdDiffPrinter, IfdYamlParser};
+use dng::{DngEditor, DngReaderOptions};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::process::exit;
+
+/// Edit tags of DNG / TIFF / DCP files in place (without rewriting the image data)
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// the files to edit followed by `PATH=VALUE` assignments (e.g. `ExifIFD.ExposureTime=1/50`).
+    /// paths start in IFD0 unless they start with another toplevel IFD (e.g. `IFD1.Copyright`)
+    #[arg(required = true)]
+    args: Vec<String>,
+    /// remove the entry at the given path (can be given multiple times)
+    #[arg(short = 'd', long)]
+    delete: Vec<String>,
+    /// only print the changes that would be made instead of writing them
+    #[arg(short = 'n', long, action)]
+    dry_run: bool,
+    /// skip broken entries and IFDs instead of failing (the problems are reported on stderr)
+    #[arg(short = 'l', long, action)]
+    lenient: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    // everything that is not an existing file but contains a '=' is an assignment
+    let (assignments, files): (Vec<_>, Vec<_>) = args
+        .args
+        .iter()
+        .partition(|arg| arg.contains('=') && !Path::new(arg).exists());
+    if files.is_empty() {
+        eprintln!("error: no files to edit were given");
+        exit(2);
+    }
+
+    let mut failed = false;
+    for file in files {
+        if let Err(e) = edit_file(file, &assignments, &args) {
+            eprintln!("error: {file}: {e}");
+            failed = true;
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+fn edit_file(path: &str, assignments: &[&String], args: &Args) -> Result<(), String> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(!args.dry_run)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    let options = DngReaderOptions {
+        lenient: args.lenient,
+        ..Default::default()
+    };
+    let mut editor = DngEditor::open_with_options(file, options).map_err(|e| e.to_string())?;
+    for warning in editor.reader().warnings() {
+        eprintln!("warning: {path}: {warning}");
+    }
+
+    let parser = IfdYamlParser::new(Path::new(path).parent().unwrap().to_path_buf());
+    for assignment in assignments {
+        let (entry_path, value) = assignment.split_once('=').unwrap();
+        let entry_path: IfdPath = entry_path.parse()?;
+        let tag = *entry_path
+            .last_tag()
+            .ok_or_else(|| format!("'{entry_path:?}' doesnt contain a tag"))?;
+        let value = parser
+            .parse_value(tag, value)
+            .map_err(|e| format!("cant parse value for {entry_path:?}: {e}"))?;
+        let ifd = toplevel_ifd(&mut editor, &entry_path)?;
+        ifd.insert_by_path(&entry_path, value)?;
+    }
+    for entry_path in &args.delete {
+        let entry_path: IfdPath = entry_path.parse()?;
+        let ifd = toplevel_ifd(&mut editor, &entry_path)?;
+        if ifd.remove_by_path(&entry_path).is_none() {
+            eprintln!("warning: {path}: '{entry_path:?}' doesnt exist");
+        }
+    }
+
+    if args.dry_run {
+        print_diff(path, &editor);
+    } else if editor.has_changes() {
+        editor.commit().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn toplevel_ifd<'a>(
+    editor: &'a mut DngEditor<std::fs::File>,
+    path: &IfdPath,
+) -> Result<&'a mut dng::ifd::Ifd, String> {
+    let index = path.get_chain_index().unwrap_or(0);
+    editor
+        .ifds_mut()
+        .get_mut(index as usize)
+        .ok_or_else(|| format!("the file has no IFD{index}"))
+}
+
+fn print_diff(path: &str, editor: &DngEditor<std::fs::File>) {
+    let printer = IfdDiffPrinter::default();
+    let diff = printer.ifd_chain(
+        editor.reader().ifds(),
+        editor.ifds(),
+        &IfdDiffOptions::default(),
+    );
+    print!("{path}:\n{diff}");
+}
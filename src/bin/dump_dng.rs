@@ -1,19 +1,21 @@
 use clap::Parser;
-use dng::ifd::{IfdEntryRef, IfdValue};
+use dng::ifd::{exiftool_json, IfdEntryRef, IfdValue};
 use dng::tags::IfdTypeInterpretation;
-use dng::yaml::IfdYamlDumper;
-use dng::DngReader;
+use dng::xml::{DcpXmlDumper, DcpXmlParser};
+use dng::yaml::{IfdYamlBlobFormat, IfdYamlDumper};
+use dng::{DngReader, DngReaderOptions};
 use std::fs;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::path::Path;
-use std::sync::Arc;
+use std::process::exit;
+use std::sync::{Arc, Mutex};
 
 /// Dump the IFD metadata of a TIFF / DNG image to a human readable yaml representation
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// input file to get the metadata from
+    /// input file to get the metadata from (`.xml` files are read as DCP profiles in the XML format of dcpTool)
     file: String,
     /// convert Rational and SRational types to float for better readability (this is lossy)
     #[arg(short = 'f', long, action)]
@@ -21,25 +23,48 @@ struct Args {
     /// extract strips, tiles and larger blobs into a directory. also write the ifd chain as a yaml file there
     #[arg(short = 'e', long, action)]
     extract: bool,
+    /// skip broken entries and IFDs instead of failing (the problems are reported on stderr)
+    #[arg(short = 'l', long, action)]
+    lenient: bool,
+    /// dump in a way that compiles back to exactly the same tags (overrides -f)
+    #[arg(long, action)]
+    lossless: bool,
+    /// how to dump blobs like ICC profiles or opcode lists: as a list of numbers, as `!!binary` base64
+    /// or as `!hex` data
+    #[arg(long, value_parser = ["list", "base64", "hex"], default_value = "list")]
+    blobs: String,
+    /// with -e, inline blobs and strips / tiles of up to this many bytes instead of extracting them
+    #[arg(long, default_value_t = 0)]
+    inline_threshold: usize,
+    /// dump a DCP profile in the XML format of dcpTool instead of YAML (tags it cant represent are skipped)
+    #[arg(long, action)]
+    xml: bool,
+    /// dump the tags as JSON with the names and value formatting of `exiftool -j -n -G1` (for cross-checking)
+    #[arg(long, action, conflicts_with = "xml")]
+    exiftool: bool,
 }
 
 fn main() {
     let args = Args::parse();
     let img_file_path = Path::new(&args.file);
-    let img_file = File::open(img_file_path).expect("Cannot find test image!");
-    let dng = Arc::new(DngReader::read(img_file).expect("Couldnt parse DNG file!"));
-
+    let blob_format = match args.blobs.as_str() {
+        "base64" => IfdYamlBlobFormat::Base64,
+        "hex" => IfdYamlBlobFormat::Hex,
+        _ => IfdYamlBlobFormat::List,
+    };
     let matrix_prettify_visitor = move |entry: IfdEntryRef| -> Option<String> {
         if entry
             .tag
             .get_known_name()
-            .map_or(true, |name| !name.to_lowercase().contains("matrix"))
+            .is_none_or(|name| !name.to_lowercase().contains("matrix"))
         {
             return None;
         }
         if let IfdValue::List(list) = entry.value {
             let dumper = IfdYamlDumper {
                 dump_rational_as_float: args.dump_rational_as_float,
+                lossless: args.lossless,
+                blob_format,
                 visitor: None,
             };
             let wrapped_string = list
@@ -67,6 +92,60 @@ fn main() {
         None
     };
 
+    let is_xml = img_file_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("xml"));
+    if is_xml {
+        let contents = fs::read_to_string(img_file_path).expect("Cannot read the XML file!");
+        let ifd = DcpXmlParser::default()
+            .parse_from_str(&contents)
+            .unwrap_or_else(|e| {
+                eprintln!("error: {}: {e}", args.file);
+                exit(1);
+            });
+        let yaml_dumper = IfdYamlDumper {
+            dump_rational_as_float: args.dump_rational_as_float,
+            lossless: args.lossless,
+            blob_format,
+            visitor: Some(Arc::new(matrix_prettify_visitor)),
+        };
+        print!("{}", yaml_dumper.dump_ifd(&ifd));
+        return;
+    }
+
+    let img_file = File::open(img_file_path).expect("Cannot find test image!");
+    let options = DngReaderOptions {
+        lenient: args.lenient,
+        ..Default::default()
+    };
+    let dng = DngReader::read_with_options(img_file, options).expect("Couldnt parse DNG file!");
+    for warning in dng.warnings() {
+        eprintln!("warning: {warning}");
+    }
+
+    if args.exiftool {
+        print!("{}", exiftool_json(dng.ifds(), &args.file));
+        return;
+    }
+
+    if args.xml {
+        let ifd = dng.get_ifd0();
+        for tag in DcpXmlDumper::unsupported_tags(ifd) {
+            eprintln!("warning: skipping {tag}, which the XML format cant represent");
+        }
+        let dumper = DcpXmlDumper {
+            skip_unsupported: true,
+        };
+        match dumper.dump_ifd(ifd) {
+            Ok(xml) => println!("{xml}"),
+            Err(e) => {
+                eprintln!("error: {e}");
+                exit(1);
+            }
+        }
+        return;
+    }
+
     if args.extract {
         let basename = img_file_path
             .file_stem()
@@ -80,26 +159,17 @@ fn main() {
             .join(format!("{basename}_extracted"));
         fs::create_dir_all(&dir).unwrap();
 
+        // the visitor is 'static, so it takes over the reader and the IFDs are dumped from a copy.
+        // The Mutex keeps the visitor Send + Sync, which clippy expects of values put into an Arc.
+        let ifds = dng.ifds().to_vec();
         let extract_visitor = {
             let dir = dir.clone();
-            let dng = dng.clone();
+            let dng = Mutex::new(dng);
+            let inline_threshold = args.inline_threshold;
             move |entry: IfdEntryRef| -> Option<String> {
-                if matches!(
-                    entry.tag.get_type_interpretation(),
-                    Some(IfdTypeInterpretation::Blob)
-                ) {
-                    let bytes_vec: Option<Vec<u8>> = entry
-                        .value
-                        .as_list()
-                        .map(|x| {
-                            if let IfdValue::Byte(x) = x {
-                                Some(*x)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
-                    if let Some(buf) = bytes_vec {
+                if let Some(buf) = IfdYamlDumper::get_blob_bytes(entry) {
+                    // small blobs are left to the dumper
+                    if buf.len() > inline_threshold {
                         let path = dir.join(entry.path.string_with_separator("_"));
                         let mut file = OpenOptions::new()
                             .write(true)
@@ -121,9 +191,17 @@ fn main() {
                 ) && !matches!(entry.value, IfdValue::List(_))
                 {
                     let path = dir.join(entry.path.string_with_separator("_"));
+                    let dng = dng.lock().unwrap();
                     let buffer_size = dng.needed_buffer_size_for_offsets(entry).unwrap();
                     let mut buffer = vec![0u8; buffer_size];
                     dng.read_offsets_to_buffer(entry, &mut buffer).unwrap();
+                    if buffer_size <= inline_threshold {
+                        let inline_dumper = IfdYamlDumper {
+                            blob_format,
+                            ..Default::default()
+                        };
+                        return Some(inline_dumper.dump_blob(&buffer));
+                    }
                     OpenOptions::new()
                         .write(true)
                         .create(true)
@@ -142,10 +220,12 @@ fn main() {
         };
         let yaml_dumper = IfdYamlDumper {
             dump_rational_as_float: args.dump_rational_as_float,
+            lossless: args.lossless,
+            blob_format,
             visitor: Some(Arc::new(extract_visitor)),
         };
 
-        let ifd_yaml = yaml_dumper.dump_ifd(dng.get_ifd0());
+        let ifd_yaml = yaml_dumper.dump_ifd_chain(&ifds);
         OpenOptions::new()
             .write(true)
             .create(true)
@@ -157,9 +237,11 @@ fn main() {
     } else {
         let yaml_dumper = IfdYamlDumper {
             dump_rational_as_float: args.dump_rational_as_float,
+            lossless: args.lossless,
+            blob_format,
             visitor: Some(Arc::new(matrix_prettify_visitor)),
         };
-        let ifd_yaml = yaml_dumper.dump_ifd(dng.get_ifd0());
+        let ifd_yaml = yaml_dumper.dump_ifd_chain(dng.ifds());
         print!("{ifd_yaml}")
     }
 }
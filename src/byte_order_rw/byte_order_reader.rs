@@ -15,6 +15,12 @@ impl<R: Read> ByteOrderReader<R> {
             is_little_endian,
         }
     }
+    pub fn is_little_endian(&self) -> bool {
+        self.is_little_endian
+    }
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
 }
 
 macro_rules! generate_read_function {
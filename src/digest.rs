@@ -0,0 +1,450 @@
+use crate::ifd::{Ifd, IfdPath, IfdPathElement, IfdValue};
+use crate::images::enumerate_images;
+use crate::md5::Md5;
+use crate::tags::{ifd, IfdFieldDescriptor};
+use crate::{DngReader, DngReaderError, ImageRole};
+use std::io::{Read, Seek};
+
+/// The size of the tiles the image is split into for the `NewRawImageDigest` (as in the DNG SDK)
+const NEW_DIGEST_TILE_SIZE: u32 = 256;
+
+/// The result of checking a single digest tag with [DngReader::verify_digests]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DigestStatus {
+    Match,
+    Mismatch {
+        actual: [u8; 16],
+    },
+    /// The digest could not be computed (e.g. because the raw image data is compressed)
+    Unsupported(String),
+}
+
+/// Describes the outcome of verifying one of the digest tags of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestVerification {
+    /// Either `RawImageDigest` or `NewRawImageDigest`
+    pub tag: IfdFieldDescriptor,
+    /// The digest that is stored in the file
+    pub expected: [u8; 16],
+    pub status: DigestStatus,
+}
+
+impl<R: Read + Seek> DngReader<R> {
+    /// Verifies the `RawImageDigest` and `NewRawImageDigest` tags against the main raw image.
+    ///
+    /// Only the digest tags that are present in the file are checked, so the result is empty for
+    /// files without digests. Digests of compressed raw images can not be verified (yet) and are
+    /// reported as [DigestStatus::Unsupported].
+    pub fn verify_digests(&self) -> Result<Vec<DigestVerification>, DngReaderError> {
+        let mut expected_digests = vec![];
+        for tag in [ifd::RawImageDigest, ifd::NewRawImageDigest] {
+            if let Some(entry) = self.get_entry_by_path(&IfdPath::default().chain_tag(tag)) {
+                let expected = digest_from_value(entry.value).ok_or_else(|| {
+                    DngReaderError::FormatError(format!("{} must contain 16 bytes", tag.name))
+                })?;
+                expected_digests.push((tag, expected));
+            }
+        }
+        if expected_digests.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let path = self.main_image_data_ifd_path();
+        let raw_ifd = self.get_ifd_by_path(&path).ok_or_else(|| {
+            DngReaderError::Other("the main image could not be found".to_string())
+        })?;
+        let mut data = vec![0u8; self.needed_buffer_length_for_image_data(&path)?];
+        self.read_image_data_to_buffer(&path, &mut data)?;
+        let digests = RawImageDigests::compute(raw_ifd, &data, self.is_little_endian());
+
+        Ok(expected_digests
+            .into_iter()
+            .map(|(tag, expected)| {
+                let status = match &digests {
+                    Ok(digests) => {
+                        let actual = if tag == ifd::RawImageDigest {
+                            digests.raw_image_digest
+                        } else {
+                            digests.new_raw_image_digest
+                        };
+                        if actual == expected {
+                            DigestStatus::Match
+                        } else {
+                            DigestStatus::Mismatch { actual }
+                        }
+                    }
+                    Err(reason) => DigestStatus::Unsupported(reason.clone()),
+                };
+                DigestVerification {
+                    tag,
+                    expected,
+                    status,
+                }
+            })
+            .collect())
+    }
+}
+
+fn digest_from_value(value: &IfdValue) -> Option<[u8; 16]> {
+    let bytes: Vec<u8> = value
+        .as_list()
+        .map(|x| x.as_u32().and_then(|x| u8::try_from(x).ok()))
+        .collect::<Option<_>>()?;
+    bytes.try_into().ok()
+}
+
+/// The `RawImageDigest` and `NewRawImageDigest` of a raw image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RawImageDigests {
+    pub raw_image_digest: [u8; 16],
+    pub new_raw_image_digest: [u8; 16],
+}
+impl RawImageDigests {
+    /// Computes both digests from the uncompressed image data (strips or tiles concatenated)
+    ///
+    /// Both digests hash the samples pixel by pixel (the planes of a pixel are interleaved, rows are
+    /// not padded) in little endian byte order, like the buffers of the DNG SDK. The legacy digest
+    /// processes the whole image in row-scan order with every sample zero padded to 16 bit (or 32
+    /// bit for deeper data), so 8 bit data is hashed as 16 bit data. The new one keeps 8 bit
+    /// samples as single bytes, hashes 256x256 pixel tiles separately and then hashes the
+    /// concatenated tile digests.
+    pub fn compute(raw_ifd: &Ifd, data: &[u8], is_little_endian: bool) -> Result<Self, String> {
+        let samples = RawSamples::new(raw_ifd, data, is_little_endian)?;
+        let (legacy_sample_bytes, new_sample_bytes) = match samples.bits_per_sample {
+            0..=8 => (2, 1),
+            9..=16 => (2, 2),
+            _ => (4, 4),
+        };
+
+        let mut legacy = Md5::default();
+        let mut row = Vec::new();
+        for y in 0..samples.height {
+            samples.push_row_bytes(&mut row, y, 0..samples.width, legacy_sample_bytes);
+            legacy.update(&row);
+        }
+
+        let mut new = Md5::default();
+        for tile_y in (0..samples.height).step_by(NEW_DIGEST_TILE_SIZE as usize) {
+            for tile_x in (0..samples.width).step_by(NEW_DIGEST_TILE_SIZE as usize) {
+                let mut tile = Md5::default();
+                let x_end = (tile_x + NEW_DIGEST_TILE_SIZE).min(samples.width);
+                for y in tile_y..(tile_y + NEW_DIGEST_TILE_SIZE).min(samples.height) {
+                    samples.push_row_bytes(&mut row, y, tile_x..x_end, new_sample_bytes);
+                    tile.update(&row);
+                }
+                new.update(&tile.finalize());
+            }
+        }
+
+        Ok(Self {
+            raw_image_digest: legacy.finalize(),
+            new_raw_image_digest: new.finalize(),
+        })
+    }
+}
+
+/// Computes the digests of the main raw image of the given IFD chain and stores them in IFD0
+///
+/// The image data is taken from the `Offsets` blobs of the raw IFD. As these are written verbatim,
+/// their samples are interpreted in the byte order of the file that is written.
+pub(crate) fn insert_digests(ifds: &mut [Ifd], is_little_endian: bool) -> Result<(), String> {
+    let main_image = enumerate_images(ifds)
+        .into_iter()
+        .find(|image| image.role == ImageRole::Main)
+        .ok_or("the file contains no main image to compute the digests of")?;
+    let raw_ifd = find_ifd(ifds, &main_image.path).ok_or("the main image could not be found")?;
+
+    let offsets = raw_ifd
+        .get(ifd::StripOffsets)
+        .or_else(|| raw_ifd.get(ifd::TileOffsets))
+        .ok_or("the main image has no image data")?;
+    let mut data = vec![];
+    for value in offsets.as_list() {
+        data.extend(
+            value
+                .offsets_bytes()
+                .ok_or("the image data of the main image must be given as Offsets")?,
+        );
+    }
+
+    let digests = RawImageDigests::compute(raw_ifd, &data, is_little_endian)?;
+    ifds[0].insert(ifd::RawImageDigest, digests.raw_image_digest);
+    ifds[0].insert(ifd::NewRawImageDigest, digests.new_raw_image_digest);
+    Ok(())
+}
+
+fn find_ifd<'a>(ifds: &'a [Ifd], path: &IfdPath) -> Option<&'a Ifd> {
+    let toplevel = ifds.get(path.get_chain_index().unwrap_or(0) as usize)?;
+    let mut current = None;
+    for element in path.as_vec() {
+        current = match (current, element) {
+            (_, IfdPathElement::ChainIndex(_)) => continue,
+            (None, IfdPathElement::Tag(tag)) => toplevel.get(*tag),
+            (Some(value), element) => value.index_with(element.clone()),
+            (None, _) => return None,
+        };
+        current?;
+    }
+    match current {
+        None => Some(toplevel),
+        Some(IfdValue::Ifd(ifd)) => Some(ifd),
+        Some(_) => None,
+    }
+}
+
+/// Provides access to the individual samples of uncompressed, chunky image data
+struct RawSamples<'a> {
+    data: &'a [u8],
+    is_little_endian: bool,
+    width: u32,
+    height: u32,
+    samples_per_pixel: u32,
+    bits_per_sample: u32,
+    /// the tile size (if the image is tiled), strips are handled as one big tile
+    tile_width: u32,
+    tile_length: u32,
+    tile_row_bytes: usize,
+}
+impl<'a> RawSamples<'a> {
+    fn new(ifd: &Ifd, data: &'a [u8], is_little_endian: bool) -> Result<Self, String> {
+        let get = |tag: IfdFieldDescriptor| ifd.get(tag).and_then(|value| value.as_u32());
+        let (width, height) = match (get(ifd::ImageWidth), get(ifd::ImageLength)) {
+            (Some(width), Some(height)) => (width, height),
+            _ => return Err("the raw image has no size".to_string()),
+        };
+        if get(ifd::Compression).unwrap_or(1) != 1 {
+            return Err("digests of compressed raw images are not supported".to_string());
+        }
+        let samples_per_pixel = get(ifd::SamplesPerPixel).unwrap_or(1);
+        if samples_per_pixel > 1 && get(ifd::PlanarConfiguration).unwrap_or(1) != 1 {
+            return Err("digests of planar raw images are not supported".to_string());
+        }
+        // BitsPerSample has one value per plane, which all have to be the same for DNG
+        let bits_per_sample = ifd
+            .get(ifd::BitsPerSample)
+            .and_then(|value| value.as_list().next()?.as_u32())
+            .unwrap_or(1);
+        if !(1..=32).contains(&bits_per_sample) {
+            return Err(format!(
+                "{bits_per_sample} bits per sample are not supported"
+            ));
+        }
+        if get(ifd::SampleFormat) == Some(3) && bits_per_sample != 32 {
+            return Err("digests of 16 / 24 bit floating point images are not supported".into());
+        }
+
+        let (tile_width, tile_length, tile_count) =
+            match (get(ifd::TileWidth), get(ifd::TileLength)) {
+                (Some(tile_width), Some(tile_length)) if tile_width > 0 && tile_length > 0 => {
+                    let tiles_across = width.div_ceil(tile_width) as usize;
+                    let tiles_down = height.div_ceil(tile_length) as usize;
+                    (tile_width, tile_length, tiles_across * tiles_down)
+                }
+                _ => (width, height, 1),
+            };
+        let tile_row_bytes =
+            (tile_width as usize * samples_per_pixel as usize * bits_per_sample as usize)
+                .div_ceil(8);
+        if data.len() < tile_row_bytes * tile_length as usize * tile_count {
+            return Err("the image data is shorter than expected".to_string());
+        }
+
+        Ok(Self {
+            data,
+            is_little_endian,
+            width,
+            height,
+            samples_per_pixel,
+            bits_per_sample,
+            tile_width,
+            tile_length,
+            tile_row_bytes,
+        })
+    }
+
+    fn sample(&self, x: u32, y: u32, plane: u32) -> u32 {
+        let tiles_across = self.width.div_ceil(self.tile_width);
+        let tile = (y / self.tile_length) * tiles_across + x / self.tile_width;
+        let row_start = (tile as usize * self.tile_length as usize
+            + (y % self.tile_length) as usize)
+            * self.tile_row_bytes;
+        let row = &self.data[row_start..row_start + self.tile_row_bytes];
+        let index = ((x % self.tile_width) * self.samples_per_pixel + plane) as usize;
+
+        match self.bits_per_sample {
+            8 => row[index] as u32,
+            16 => {
+                let bytes = [row[index * 2], row[index * 2 + 1]];
+                match self.is_little_endian {
+                    true => u16::from_le_bytes(bytes) as u32,
+                    false => u16::from_be_bytes(bytes) as u32,
+                }
+            }
+            32 => {
+                let bytes = row[index * 4..index * 4 + 4].try_into().unwrap();
+                match self.is_little_endian {
+                    true => u32::from_le_bytes(bytes),
+                    false => u32::from_be_bytes(bytes),
+                }
+            }
+            // packed samples are stored with the most significant bit first
+            bits => {
+                let bit_offset = index * bits as usize;
+                (bit_offset..bit_offset + bits as usize).fold(0, |value, bit| {
+                    (value << 1) | ((row[bit / 8] >> (7 - bit % 8)) & 1) as u32
+                })
+            }
+        }
+    }
+
+    /// Replaces the contents of `buffer` with the little endian samples of a part of a row, each
+    /// zero padded to `sample_bytes` (1, 2 or 4) bytes
+    fn push_row_bytes(
+        &self,
+        buffer: &mut Vec<u8>,
+        y: u32,
+        xs: std::ops::Range<u32>,
+        sample_bytes: usize,
+    ) {
+        buffer.clear();
+        for x in xs {
+            for plane in 0..self.samples_per_pixel {
+                let sample = self.sample(x, y, plane);
+                match sample_bytes {
+                    1 => buffer.push(sample as u8),
+                    2 => buffer.extend_from_slice(&(sample as u16).to_le_bytes()),
+                    _ => buffer.extend_from_slice(&sample.to_le_bytes()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::{Ifd, IfdValue};
+    use crate::md5::Md5;
+    use crate::tags::{ifd, IfdType};
+    use crate::{DigestStatus, DngReader, DngWriter, DngWriterOptions, FileType};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_digests() {
+        // wider than one 256 pixel tile of the NewRawImageDigest
+        let (width, height) = (300u32, 4u32);
+        let samples: Vec<u16> = (0..width * height)
+            .map(|i| (i * 13 % 4096) as u16)
+            .collect();
+        let mut ifd0 = Ifd::new(IfdType::Ifd);
+        ifd0.insert(ifd::ImageWidth, width);
+        ifd0.insert(ifd::ImageLength, height);
+        ifd0.insert(ifd::BitsPerSample, 16u16);
+        ifd0.insert(ifd::Compression, 1u16);
+        ifd0.insert(ifd::PhotometricInterpretation, 32803u16);
+        ifd0.insert(ifd::RowsPerStrip, height);
+        let big_endian_data: Vec<u8> = samples.iter().flat_map(|x| x.to_be_bytes()).collect();
+        ifd0.insert(ifd::StripByteCounts, big_endian_data.len() as u32);
+        ifd0.insert(
+            ifd::StripOffsets,
+            IfdValue::Offsets(Arc::new(big_endian_data)),
+        );
+
+        let mut data = Vec::new();
+        let options = DngWriterOptions {
+            compute_digests: true,
+        };
+        DngWriter::write_dng_with_options(
+            Cursor::new(&mut data),
+            false,
+            FileType::Dng,
+            vec![ifd0],
+            options,
+        )
+        .unwrap();
+        let dng = DngReader::read(Cursor::new(data.clone())).unwrap();
+
+        // the legacy digest is the MD5 of all samples in little endian byte order
+        let mut expected = Md5::default();
+        for sample in &samples {
+            expected.update(&sample.to_le_bytes());
+        }
+        let expected = expected.finalize();
+        let stored = dng.get_ifd0().get(ifd::RawImageDigest).unwrap();
+        assert_eq!(stored, &IfdValue::from(expected));
+
+        let verifications = dng.verify_digests().unwrap();
+        assert_eq!(verifications.len(), 2);
+        assert!(verifications
+            .iter()
+            .all(|v| v.status == DigestStatus::Match));
+
+        // corrupting a single sample is detected by both digests
+        let path = dng.main_image_data_ifd_path();
+        let (offset, _) = dng.image_data_segments(&path).unwrap()[0];
+        data[offset as usize + 1] ^= 1;
+        let dng = DngReader::read(Cursor::new(data)).unwrap();
+        let verifications = dng.verify_digests().unwrap();
+        assert!(verifications
+            .iter()
+            .all(|v| matches!(v.status, DigestStatus::Mismatch { .. })));
+    }
+
+    #[test]
+    fn test_digests_of_8_bit_data() {
+        // a linear raw image with three interleaved planes that spans two tiles of the new digest
+        let (width, height, planes) = (260usize, 2usize, 3usize);
+        let samples: Vec<u8> = (0..width * height * planes)
+            .map(|i| (i * 7) as u8)
+            .collect();
+        let mut ifd0 = Ifd::new(IfdType::Ifd);
+        ifd0.insert(ifd::ImageWidth, width as u32);
+        ifd0.insert(ifd::ImageLength, height as u32);
+        ifd0.insert(ifd::BitsPerSample, [8u16, 8, 8]);
+        ifd0.insert(ifd::SamplesPerPixel, planes as u16);
+        ifd0.insert(ifd::Compression, 1u16);
+        ifd0.insert(ifd::PhotometricInterpretation, 34892u16);
+        ifd0.insert(ifd::PlanarConfiguration, 1u16);
+        ifd0.insert(ifd::RowsPerStrip, height as u32);
+        ifd0.insert(ifd::StripByteCounts, samples.len() as u32);
+        ifd0.insert(
+            ifd::StripOffsets,
+            IfdValue::Offsets(Arc::new(samples.clone())),
+        );
+
+        let mut data = Vec::new();
+        let options = DngWriterOptions {
+            compute_digests: true,
+        };
+        DngWriter::write_dng_with_options(
+            Cursor::new(&mut data),
+            true,
+            FileType::Dng,
+            vec![ifd0],
+            options,
+        )
+        .unwrap();
+        let dng = DngReader::read(Cursor::new(data)).unwrap();
+
+        // the legacy digest widens 8 bit data to 16 bit (like FindImageDigest of the DNG SDK)
+        let mut expected = Md5::default();
+        for sample in &samples {
+            expected.update(&(*sample as u16).to_le_bytes());
+        }
+        let stored = dng.get_ifd0().get(ifd::RawImageDigest).unwrap();
+        assert_eq!(stored, &IfdValue::from(expected.finalize()));
+
+        // the new one hashes the pixel interleaved bytes of each tile without padding
+        let row_bytes = width * planes;
+        let mut expected = Md5::default();
+        for columns in [0..256 * planes, 256 * planes..row_bytes] {
+            let mut tile = Md5::default();
+            for row in samples.chunks_exact(row_bytes) {
+                tile.update(&row[columns.clone()]);
+            }
+            expected.update(&tile.finalize());
+        }
+        let stored = dng.get_ifd0().get(ifd::NewRawImageDigest).unwrap();
+        assert_eq!(stored, &IfdValue::from(expected.finalize()));
+    }
+}
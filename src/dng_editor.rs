@@ -0,0 +1,190 @@
+use crate::byte_order_rw::ByteOrderWriter;
+use crate::ifd::Ifd;
+use crate::{DngReader, DngReaderError, DngReaderOptions, DngWriter};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// Edits the metadata of an existing DNG / DCP file without rewriting its image data
+///
+/// On [commit][Self::commit], the changed toplevel IFDs (and all toplevel IFDs after them) are
+/// appended to the end of the file together with their values and SubIFDs. Then the pointer to the
+/// first changed IFD is updated in place. Strip / tile data is never touched, as unchanged OFFSETS
+/// entries keep pointing to the original data. The old IFDs stay in the file as unreferenced garbage
+/// until it is rewritten with [compact_to][Self::compact_to].
+///
+/// example:
+/// ```rust
+/// use std::fs::OpenOptions;
+/// use dng::{DngEditor, tags};
+///
+/// std::fs::copy("src/testdata/test.dng", "/tmp/edited.dng").unwrap();
+/// let file = OpenOptions::new().read(true).write(true).open("/tmp/edited.dng").unwrap();
+/// let mut editor = DngEditor::open(file).unwrap();
+/// editor.ifds_mut()[0].insert(tags::ifd::Copyright, "Jane Doe");
+/// editor.commit().unwrap();
+/// ```
+pub struct DngEditor<F: Read + Write + Seek> {
+    dng: DngReader<F>,
+    ifds: Vec<Ifd>,
+}
+impl<F: Read + Write + Seek> DngEditor<F> {
+    /// Reads the IFD tree of the given file for editing
+    pub fn open(file: F) -> Result<Self, DngReaderError> {
+        Self::open_with_options(file, DngReaderOptions::default())
+    }
+    /// Same as [open][Self::open] but with the given [DngReaderOptions]
+    pub fn open_with_options(file: F, options: DngReaderOptions) -> Result<Self, DngReaderError> {
+        let dng = DngReader::read_with_options(file, options)?;
+        let ifds = dng.ifds().to_vec();
+        Ok(Self { dng, ifds })
+    }
+
+    /// Gives access to the unmodified file, e.g. for reading image data
+    pub fn reader(&self) -> &DngReader<F> {
+        &self.dng
+    }
+    /// Returns the (possibly modified) toplevel IFDs
+    pub fn ifds(&self) -> &[Ifd] {
+        &self.ifds
+    }
+    /// Returns the toplevel IFDs for modification. IFDs can also be added or removed, but the chain
+    /// must not become empty.
+    pub fn ifds_mut(&mut self) -> &mut Vec<Ifd> {
+        &mut self.ifds
+    }
+    /// Returns true if the IFDs were modified since the file was opened
+    pub fn has_changes(&self) -> bool {
+        self.dng.ifds() != self.ifds.as_slice()
+    }
+
+    /// Writes the changes to the file and returns it. Nothing is written if there are no changes.
+    ///
+    /// The new IFDs are written before the IFD chain is re-pointed to them, so an interrupted
+    /// commit leaves the file in its original state (with some garbage at the end).
+    pub fn commit(self) -> io::Result<F> {
+        if self.ifds.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a file must contain at least one IFD",
+            ));
+        }
+        let original = self.dng.ifds();
+        let first_changed = (0..=self.ifds.len())
+            .find(|&i| i == self.ifds.len() || original.get(i) != Some(&self.ifds[i]))
+            .unwrap();
+        if first_changed == original.len() && first_changed == self.ifds.len() {
+            return Ok(self.dng.into_inner());
+        }
+
+        let pointer_position = *self
+            .dng
+            .ifd_pointer_positions()
+            .get(first_changed)
+            .ok_or_else(|| io::Error::other("the position of the IFD pointer is unknown"))?;
+        let is_little_endian = self.dng.is_little_endian();
+        let mut file = self.dng.into_inner();
+
+        // if IFDs were only removed from the end, the chain just ends earlier
+        let address = match self.ifds.get(first_changed..) {
+            Some(changed) if !changed.is_empty() => {
+                DngWriter::append_ifds(&mut file, is_little_endian, changed.to_vec())?
+            }
+            _ => 0,
+        };
+        file.seek(SeekFrom::Start(pointer_position))?;
+        ByteOrderWriter::new(&mut file, is_little_endian).write_u32(address)?;
+        file.flush()?;
+        Ok(file)
+    }
+
+    /// Writes a complete copy of the (modified) file without any unreferenced data to `writer`
+    ///
+    /// NOTE: the image data of the whole file is loaded into memory for this.
+    pub fn compact_to<W: Write + Seek>(&self, writer: W) -> Result<(), DngReaderError> {
+        let mut ifds = self.ifds.clone();
+        for ifd in &mut ifds {
+            self.dng.load_offsets(ifd)?;
+        }
+        DngWriter::write_dng(
+            writer,
+            self.dng.is_little_endian(),
+            self.dng.file_type(),
+            ifds,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::{Ifd, IfdValue};
+    use crate::tags::{ifd, IfdType};
+    use crate::{DngEditor, DngReader, DngWriter, FileType};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_edit_in_place() {
+        let image_data: Vec<u8> = (0..64).collect();
+        let mut raw = Ifd::new(IfdType::Ifd);
+        raw.insert(ifd::ImageWidth, 8u32);
+        raw.insert(ifd::ImageLength, 8u32);
+        raw.insert(
+            ifd::StripOffsets,
+            IfdValue::Offsets(Arc::new(image_data.clone())),
+        );
+        raw.insert(ifd::StripByteCounts, image_data.len() as u32);
+        let mut ifd0 = Ifd::new(IfdType::Ifd);
+        ifd0.insert(ifd::Copyright, "wrong");
+        ifd0.insert(ifd::SubIFDs, IfdValue::List(vec![IfdValue::Ifd(raw)]));
+        let mut ifd1 = Ifd::new(IfdType::Ifd);
+        ifd1.insert(ifd::Artist, "someone");
+
+        let mut data = Vec::new();
+        DngWriter::write_dng(
+            Cursor::new(&mut data),
+            true,
+            FileType::Dng,
+            vec![ifd0, ifd1],
+        )
+        .unwrap();
+        let original_len = data.len();
+
+        let mut editor = DngEditor::open(Cursor::new(data)).unwrap();
+        assert!(!editor.has_changes());
+        editor.ifds_mut()[0].insert(ifd::Copyright, "right");
+        assert!(editor.has_changes());
+        let data = editor.commit().unwrap().into_inner();
+
+        // the image data was left in place and the new IFDs were only appended
+        assert!(data.len() > original_len);
+        let dng = DngReader::read(Cursor::new(data.clone())).unwrap();
+        assert_eq!(dng.ifds().len(), 2);
+        assert_eq!(
+            dng.get_ifd0().get(ifd::Copyright),
+            Some(&IfdValue::Ascii("right".to_string()))
+        );
+        let read_image_data = |dng: &DngReader<Cursor<Vec<u8>>>| {
+            let path = dng.main_image_data_ifd_path();
+            let mut buffer = vec![0u8; dng.needed_buffer_length_for_image_data(&path).unwrap()];
+            dng.read_image_data_to_buffer(&path, &mut buffer).unwrap();
+            buffer
+        };
+        assert_eq!(read_image_data(&dng), image_data);
+
+        // removing the last IFD only terminates the chain earlier
+        let mut editor = DngEditor::open(Cursor::new(data)).unwrap();
+        editor.ifds_mut().pop();
+        let data = editor.commit().unwrap().into_inner();
+        let len_before_compaction = data.len();
+        let dng = DngReader::read(Cursor::new(data.clone())).unwrap();
+        assert_eq!(dng.ifds().len(), 1);
+
+        let mut compacted = Vec::new();
+        let editor = DngEditor::open(Cursor::new(data)).unwrap();
+        editor.compact_to(Cursor::new(&mut compacted)).unwrap();
+        assert!(compacted.len() < len_before_compaction);
+        let compacted = DngReader::read(Cursor::new(compacted)).unwrap();
+        assert_eq!(compacted.ifds().len(), 1);
+        assert_eq!(read_image_data(&compacted), image_data);
+    }
+}
@@ -1,14 +1,16 @@
 use crate::byte_order_rw::ByteOrderReader;
-use crate::ifd::{Ifd, IfdEntryRef, IfdPath};
-use crate::ifd_reader::IfdReader;
+use crate::ifd::{Ifd, IfdEntryRef, IfdPath, IfdValue};
+use crate::ifd_reader::{IfdReader, IfdReaderContext};
+use crate::images::enumerate_images;
 use crate::tags::{ifd, IfdType, IfdTypeInterpretation};
-use crate::FileType;
+use crate::{FileType, ImageInfo, ImageRole};
 use derivative::Derivative;
 use std::cell::RefCell;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::io;
 use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
 
 /// The error-type produced by [DngReader]
 #[derive(Debug)]
@@ -44,6 +46,49 @@ impl Error for DngReaderError {
     }
 }
 
+/// Limits that are enforced by [DngReader] while parsing a file.
+///
+/// Files violating them are rejected with a [DngReaderError::FormatError] instead of exhausting
+/// memory or time, which matters when parsing untrusted files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DngReaderOptions {
+    /// The maximum number of IFDs in the file (toplevel and nested ones combined)
+    pub max_ifds: usize,
+    /// The maximum number of entries in a single IFD
+    pub max_entries_per_ifd: usize,
+    /// The maximum size in bytes of a single entry value (not including the data pointed to by OFFSETS)
+    pub max_value_bytes: u64,
+    /// The maximum nesting depth of IFDs (toplevel IFDs have a depth of 0)
+    pub max_depth: usize,
+    /// Skip broken entries and IFDs instead of failing the whole file. The problems that were
+    /// encountered can be inspected with [DngReader::warnings].
+    pub lenient: bool,
+}
+impl Default for DngReaderOptions {
+    fn default() -> Self {
+        Self {
+            max_ifds: 1024,
+            max_entries_per_ifd: 4096,
+            max_value_bytes: 64 * 1024 * 1024,
+            max_depth: 16,
+            lenient: false,
+        }
+    }
+}
+
+/// A problem that was encountered (and worked around) while reading a file with [DngReader]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DngReaderWarning {
+    /// The path of the entry or IFD that the problem occurred in
+    pub path: IfdPath,
+    pub message: String,
+}
+impl Display for DngReaderWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}: {}", self.path, self.message))
+    }
+}
+
 #[derive(Derivative)]
 #[derivative(Debug)]
 /// The main entrypoint for reading DNG / DCP files
@@ -67,6 +112,13 @@ pub struct DngReader<R: Read + Seek> {
     #[derivative(Debug = "ignore")]
     reader: RefCell<ByteOrderReader<R>>,
     ifds: Vec<Ifd>,
+    /// The file positions of the pointers to each toplevel IFD (the header for IFD0 and the
+    /// next-IFD field of the previous IFD otherwise). The last one is the next-IFD field of the
+    /// last IFD.
+    ifd_pointer_positions: Vec<u64>,
+    /// The length of the whole file, which bounds the size of any data the file points to
+    file_length: u64,
+    warnings: Vec<DngReaderWarning>,
 }
 impl<R: Read + Seek> DngReader<R> {
     /// reads and parses the DNG file IFD-tree eagerly.
@@ -84,7 +136,16 @@ impl<R: Read + Seek> DngReader<R> {
     /// [needed_buffer_length_for_image_data][Self::needed_buffer_length_for_image_data],
     /// [read_image_data_to_buffer][Self::read_image_data_to_buffer].
     /// (see usage example).
-    pub fn read(mut reader: R) -> Result<Self, DngReaderError> {
+    pub fn read(reader: R) -> Result<Self, DngReaderError> {
+        Self::read_with_options(reader, DngReaderOptions::default())
+    }
+
+    /// reads and parses the DNG file IFD-tree eagerly like [read][Self::read] but enforces the
+    /// given [DngReaderOptions] instead of the default ones.
+    pub fn read_with_options(
+        mut reader: R,
+        options: DngReaderOptions,
+    ) -> Result<Self, DngReaderError> {
         // the first two bytes set the byte order
         let mut header = vec![0u8; 2];
         reader.read_exact(&mut header)?;
@@ -105,32 +166,95 @@ impl<R: Read + Seek> DngReader<R> {
             ))
         })?;
 
+        let mut context = IfdReaderContext::new(options);
+        let mut ifd_pointer_positions = vec![reader.stream_position()?];
         let mut next_ifd_offset = reader.read_u32()?;
         let mut unprocessed_ifds = Vec::new();
 
         while next_ifd_offset != 0 {
-            reader.seek(SeekFrom::Start(next_ifd_offset as u64))?;
-            unprocessed_ifds.push(IfdReader::read(&mut reader)?);
-            next_ifd_offset = reader.read_u32()?;
+            let path = IfdPath::chain_index(unprocessed_ifds.len() as u16);
+            let ifd = IfdReader::read(
+                &mut reader,
+                next_ifd_offset,
+                IfdType::Ifd,
+                path.clone(),
+                0,
+                &mut context,
+            );
+            let ifd = match context.recover(&path, ifd)? {
+                Some(ifd) => ifd,
+                None => break,
+            };
+            unprocessed_ifds.push(ifd);
+            ifd_pointer_positions.push(reader.stream_position()?);
+            let next = reader.read_u32().map_err(DngReaderError::from);
+            next_ifd_offset = context.recover(&path, next)?.unwrap_or(0);
+        }
+        if unprocessed_ifds.is_empty() {
+            return Err(DngReaderError::FormatError(
+                "the file does not contain any IFD".to_string(),
+            ));
         }
         let ifds: Result<Vec<_>, _> = unprocessed_ifds
             .iter()
-            .map(|ifd| ifd.process(IfdType::Ifd, &mut reader))
+            .map(|ifd| ifd.process(&mut reader, &mut context))
             .collect();
+        let file_length = reader.seek(SeekFrom::End(0))?;
 
         Ok(Self {
             reader: RefCell::new(reader),
             ifds: ifds?,
+            ifd_pointer_positions,
+            file_length,
             file_type,
+            warnings: context.warnings,
         })
     }
 
+    pub(crate) fn into_inner(self) -> R {
+        self.reader.into_inner().into_inner()
+    }
+    pub(crate) fn ifd_pointer_positions(&self) -> &[u64] {
+        &self.ifd_pointer_positions
+    }
+    pub(crate) fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// returns the problems that were encountered while reading the file.
+    ///
+    /// In lenient mode (see [DngReaderOptions::lenient]) this contains every skipped entry or IFD.
+    /// In strict mode only entries with an unknown type (which are always skipped) are reported.
+    pub fn warnings(&self) -> &[DngReaderWarning] {
+        &self.warnings
+    }
+
+    /// returns true if the file is stored in little endian byte order ("II")
+    pub fn is_little_endian(&self) -> bool {
+        self.reader.borrow().is_little_endian()
+    }
+
     /// returns the first toplevel IFD of the DNG file.
     pub fn get_ifd0(&self) -> &Ifd {
         &self.ifds[0]
     }
 
+    /// returns all toplevel IFDs of the file in the order of the IFD chain.
+    pub fn ifds(&self) -> &[Ifd] {
+        &self.ifds
+    }
+
+    /// returns the n-th toplevel IFD of the file (if it exists).
+    pub fn get_ifd(&self, n: usize) -> Option<&Ifd> {
+        self.ifds.get(n)
+    }
+
+    /// Returns an entry by path. If the path starts with an [crate::ifd::IfdPathElement::ChainIndex] only that
+    /// toplevel IFD is searched, otherwise the first toplevel IFD containing the path is used.
     pub fn get_entry_by_path<'a>(&'a self, path: &'a IfdPath) -> Option<IfdEntryRef<'a>> {
+        if let Some(n) = path.get_chain_index() {
+            return self.ifds.get(n as usize)?.get_entry_by_path(path);
+        }
         for ifd in &self.ifds {
             let result = ifd.get_entry_by_path(path);
             if result.is_some() {
@@ -140,6 +264,18 @@ impl<R: Read + Seek> DngReader<R> {
         None
     }
 
+    /// Returns the IFD a path points to. A path consisting only of a chain index (as used by
+    /// [crate::ImageInfo] for toplevel images) returns the toplevel IFD itself.
+    pub fn get_ifd_by_path<'a>(&'a self, path: &'a IfdPath) -> Option<&'a Ifd> {
+        if path.as_vec().len() <= 1 {
+            return self.get_ifd(path.get_chain_index().unwrap_or(0) as usize);
+        }
+        match self.get_entry_by_path(path)?.value {
+            IfdValue::Ifd(ifd) => Some(ifd),
+            _ => None,
+        }
+    }
+
     /// This low-level function returns the length of a single OFFSETS field
     /// Lists are not supported (you must query the individual list member)
     pub fn needed_buffer_size_for_offsets(
@@ -152,14 +288,11 @@ impl<R: Read + Seek> DngReader<R> {
             let lengths_paths = entry.path.with_last_tag_replaced(lengths.as_maybe());
             let lengths_value = self.get_entry_by_path(&lengths_paths);
             if let Some(entry) = lengths_value {
-                entry
-                    .value
-                    .as_u32()
-                    .map(|v| v as usize)
-                    .ok_or(DngReaderError::Other(format!(
-                        "length tag {lengths_paths:?} for {:?} does not have integer value",
-                        entry.path
-                    )))
+                let length = entry.value.as_u32().ok_or(DngReaderError::Other(format!(
+                    "length tag {lengths_paths:?} for {:?} does not have integer value",
+                    entry.path
+                )))?;
+                self.check_data_length(length as u64, &lengths_paths)
             } else {
                 Err(DngReaderError::Other(format!(
                     "length tag {lengths_paths:?} for {:?} not found",
@@ -172,6 +305,18 @@ impl<R: Read + Seek> DngReader<R> {
             )))
         }
     }
+    /// Rejects data lengths that can not be satisfied by the file, so that broken or malicious
+    /// LENGTHS entries dont make callers allocate huge buffers
+    fn check_data_length(&self, length: u64, path: &IfdPath) -> Result<usize, DngReaderError> {
+        if length > self.file_length {
+            return Err(DngReaderError::FormatError(format!(
+                "{path:?} claims {length} bytes of data, but the file is only {} bytes long",
+                self.file_length
+            )));
+        }
+        usize::try_from(length)
+            .map_err(|_| DngReaderError::FormatError(format!("{path:?} is too large")))
+    }
     /// This low-level function can read a single entry from an OFFSETS field to a buffer
     /// Lists are not supported (you must query the individual list member)
     pub fn read_offsets_to_buffer(
@@ -179,32 +324,94 @@ impl<R: Read + Seek> DngReader<R> {
         entry: IfdEntryRef,
         buffer: &mut [u8],
     ) -> Result<(), DngReaderError> {
+        let offset = self.offsets_position(entry, buffer.len())?;
+        let mut reader = self.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        reader.read_exact(buffer)?;
+        Ok(())
+    }
+    /// Reads raw bytes from the given position of the file
+    pub(crate) fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<(), DngReaderError> {
+        let mut reader = self.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(buffer)?;
+        Ok(())
+    }
+    /// Replaces the numeric OFFSETS entries of an IFD tree by the data they point to
+    pub(crate) fn load_offsets(&self, ifd: &mut Ifd) -> Result<(), DngReaderError> {
+        let offsets_paths = ifd.find_entries(|entry| {
+            matches!(
+                entry.tag.get_type_interpretation(),
+                Some(IfdTypeInterpretation::Offsets { .. })
+            ) && !matches!(entry.value, IfdValue::List(_) | IfdValue::Offsets(_))
+        });
+        for path in offsets_paths {
+            let entry = ifd.get_entry_by_path(&path).unwrap();
+            let Some(IfdTypeInterpretation::Offsets { lengths }) =
+                entry.tag.get_type_interpretation()
+            else {
+                unreachable!()
+            };
+            let lengths_path = path.with_last_tag_replaced(lengths.as_maybe());
+            let (Some(offset), Some(length)) = (
+                entry.value.as_u32(),
+                ifd.get_entry_by_path(&lengths_path)
+                    .and_then(|entry| entry.value.as_u32()),
+            ) else {
+                return Err(DngReaderError::FormatError(format!(
+                    "OFFSETS entry {path:?} has no integer offset and length"
+                )));
+            };
+
+            let mut buffer = vec![0u8; self.check_data_length(length as u64, &lengths_path)?];
+            self.read_at(offset as u64, &mut buffer)?;
+            ifd.replace_by_path(&path, IfdValue::Offsets(Arc::new(buffer)));
+        }
+        Ok(())
+    }
+    /// Checks that the buffer fits the given OFFSETS entry and returns the position of its data
+    pub(crate) fn offsets_position(
+        &self,
+        entry: IfdEntryRef,
+        buffer_len: usize,
+    ) -> Result<u32, DngReaderError> {
         let buffer_size = self.needed_buffer_size_for_offsets(entry)?;
-        if buffer_size != buffer.len() {
+        if buffer_size != buffer_len {
             Err(DngReaderError::Other(format!(
-                "buffer has wrong size (expected {buffer_size} found {}",
-                buffer.len()
+                "buffer has wrong size (expected {buffer_size} found {buffer_len}"
             )))
         } else {
-            let mut reader = self.reader.borrow_mut();
-            reader.seek(SeekFrom::Start(entry.value.as_u32().ok_or_else(|| {
+            Ok(entry.value.as_u32().ok_or_else(|| {
                 io::Error::new(
                     io::ErrorKind::InvalidData,
                     format!("entry {entry:?} cant be read into buffer. it is not a single OFFSETS"),
                 )
-            })? as u64))?;
-            reader.read_exact(buffer)?;
-            Ok(())
+            })?)
         }
     }
 
+    /// Returns all images (IFDs with image data) of the file together with their role, e.g. to
+    /// find the largest JPEG preview:
+    /// ```rust
+    /// # use dng::{DngReader, ImageRole};
+    /// # let file = std::fs::File::open("src/testdata/test.dng").unwrap();
+    /// # let dng = DngReader::read(file).unwrap();
+    /// let preview = dng
+    ///     .images()
+    ///     .into_iter()
+    ///     .filter(|image| image.role == ImageRole::Preview && image.is_jpeg())
+    ///     .max_by_key(|image| image.pixel_count());
+    /// ```
+    pub fn images(&self) -> Vec<ImageInfo> {
+        enumerate_images(&self.ifds)
+    }
+
     /// Returns the Path to the IFD in which the main image data (not a preview) is stored.
     pub fn main_image_data_ifd_path(&self) -> IfdPath {
-        self.get_ifd0()
-            .find_entry(|entry| {
-                entry.tag == &ifd::NewSubfileType.as_maybe() && entry.value.as_u32() == Some(0)
-            })
-            .map(|entry| entry.parent())
+        self.images()
+            .into_iter()
+            .find(|image| image.role == ImageRole::Main)
+            .map(|image| image.path)
             .unwrap_or_default()
     }
 
@@ -218,18 +425,18 @@ impl<R: Read + Seek> DngReader<R> {
             self.get_entry_by_path(&ifd_path.chain_tag(ifd::StripOffsets)),
             self.get_entry_by_path(&ifd_path.chain_tag(ifd::StripByteCounts)),
         ) {
-            lengths
-                .value
-                .as_list()
-                .try_fold(0, |acc, x| {
-                    x.as_u32()
-                        .map(|v| acc + v)
-                        .ok_or(DngReaderError::Other(format!(
-                            "length tag {:?} for {:?} does not have integer value",
-                            lengths.path, offsets.path
-                        )))
-                })
-                .map(|v| v as usize)
+            let length = lengths.value.as_list().try_fold(0u64, |acc, x| {
+                let v = x.as_u32().ok_or(DngReaderError::Other(format!(
+                    "length tag {:?} for {:?} does not have integer value",
+                    lengths.path, offsets.path
+                )))?;
+                acc.checked_add(v as u64)
+                    .ok_or(DngReaderError::FormatError(format!(
+                        "the lengths in {:?} overflow",
+                        lengths.path
+                    )))
+            })?;
+            self.check_data_length(length, lengths.path)
         } else if let (Some(_offsets), Some(_lengths)) = (
             self.get_entry_by_path(&ifd_path.chain_tag(ifd::TileOffsets)),
             self.get_entry_by_path(&ifd_path.chain_tag(ifd::TileByteCounts)),
@@ -252,37 +459,50 @@ impl<R: Read + Seek> DngReader<R> {
         ifd_path: &IfdPath,
         buffer: &mut [u8],
     ) -> Result<(), DngReaderError> {
+        let mut reader = self.reader.borrow_mut();
+        let mut buffer_offset = 0;
+        for (offset, length) in self.image_data_segments(ifd_path)? {
+            reader.seek(SeekFrom::Start(offset as u64))?;
+            let buffer_slice =
+                &mut buffer[(buffer_offset as usize)..((buffer_offset + length) as usize)];
+            reader.read_exact(buffer_slice)?;
+
+            buffer_offset += length;
+        }
+        Ok(())
+    }
+    /// Returns the `(offset, length)` pairs of the image data segments (strips) in a given IFD
+    pub(crate) fn image_data_segments(
+        &self,
+        ifd_path: &IfdPath,
+    ) -> Result<Vec<(u32, u32)>, DngReaderError> {
         // we try the different options one after another
         if let (Some(offsets), Some(lengths)) = (
             self.get_entry_by_path(&ifd_path.chain_tag(ifd::StripOffsets)),
             self.get_entry_by_path(&ifd_path.chain_tag(ifd::StripByteCounts)),
         ) {
-            let mut reader = self.reader.borrow_mut();
             let count = offsets.value.get_count();
             if count != lengths.value.get_count() {
                 return Err(DngReaderError::FormatError(
                     "the counts of OFFSETS and LENGTHS must be the same".to_string(),
                 ));
             }
-            let mut buffer_offset = 0;
-            for (offset, length) in offsets.value.as_list().zip(lengths.value.as_list()) {
-                let offset = offset.as_u32().ok_or(DngReaderError::Other(format!(
-                    "offset tag {offset:?} for  {:?} does not have integer value",
-                    offsets.path
-                )))?;
-                let length = length.as_u32().ok_or(DngReaderError::Other(format!(
-                    "length tag {length:?} for  {:?} does not have integer value",
-                    offsets.path
-                )))?;
-
-                reader.seek(SeekFrom::Start(offset as u64))?;
-                let buffer_slice =
-                    &mut buffer[(buffer_offset as usize)..((buffer_offset + length) as usize)];
-                reader.read_exact(buffer_slice)?;
-
-                buffer_offset += length;
-            }
-            Ok(())
+            offsets
+                .value
+                .as_list()
+                .zip(lengths.value.as_list())
+                .map(|(offset, length)| {
+                    let offset = offset.as_u32().ok_or(DngReaderError::Other(format!(
+                        "offset tag {offset:?} for  {:?} does not have integer value",
+                        offsets.path
+                    )))?;
+                    let length = length.as_u32().ok_or(DngReaderError::Other(format!(
+                        "length tag {length:?} for  {:?} does not have integer value",
+                        offsets.path
+                    )))?;
+                    Ok((offset, length))
+                })
+                .collect()
         } else if let (Some(_offsets), Some(_lengths)) = (
             self.get_entry_by_path(&ifd_path.chain_tag(ifd::TileOffsets)),
             self.get_entry_by_path(&ifd_path.chain_tag(ifd::TileByteCounts)),
@@ -298,3 +518,167 @@ impl<R: Read + Seek> DngReader<R> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::{Ifd, IfdPath, IfdValue};
+    use crate::tags::{ifd, IfdType};
+    use crate::{DngReader, DngReaderError, DngReaderOptions, DngWriter, FileType};
+    use std::io::Cursor;
+
+    /// builds a little endian TIFF file consisting of a single IFD with the given raw entries
+    /// `(tag, dtype, count, value_or_offset)` that points to `next_ifd` as the next IFD
+    fn tiff_with_entries(entries: &[(u16, u16, u32, u32)], next_ifd: u32) -> Vec<u8> {
+        let mut data = vec![0x49, 0x49, 42, 0, 8, 0, 0, 0];
+        data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (tag, dtype, count, value) in entries {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&dtype.to_le_bytes());
+            data.extend_from_slice(&count.to_le_bytes());
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&next_ifd.to_le_bytes());
+        data
+    }
+
+    fn assert_format_error(data: Vec<u8>) {
+        match DngReader::read(Cursor::new(data)) {
+            Err(DngReaderError::FormatError(_)) => {}
+            other => panic!("expected a FormatError, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_cyclic_ifd_chain() {
+        // the IFD points to itself as the next IFD
+        assert_format_error(tiff_with_entries(&[(0x0100, 4, 1, 2)], 8));
+    }
+
+    #[test]
+    fn test_cyclic_sub_ifd() {
+        // the SubIFDs tag points back to the toplevel IFD
+        assert_format_error(tiff_with_entries(&[(0x014A, 4, 1, 8)], 0));
+    }
+
+    #[test]
+    fn test_huge_count() {
+        assert_format_error(tiff_with_entries(&[(0x0100, 4, 0xFFFFFFFF, 8)], 0));
+    }
+
+    #[test]
+    fn test_sub_ifd_pointer_with_wrong_type() {
+        assert_format_error(tiff_with_entries(&[(0x014A, 3, 1, 8)], 0));
+    }
+
+    #[test]
+    fn test_too_deeply_nested() {
+        let options = DngReaderOptions {
+            max_depth: 0,
+            ..Default::default()
+        };
+        let mut data = tiff_with_entries(&[(0x014A, 4, 1, 26)], 0);
+        data.extend_from_slice(&tiff_with_entries(&[], 0)[8..]);
+        assert!(DngReader::read(Cursor::new(data.clone())).is_ok());
+        assert!(matches!(
+            DngReader::read_with_options(Cursor::new(data), options),
+            Err(DngReaderError::FormatError(_))
+        ));
+    }
+
+    #[test]
+    fn test_lenient_skips_broken_entries() {
+        let mut data = tiff_with_entries(
+            &[
+                (0x0100, 4, 1, 2),      // ImageWidth
+                (0x8298, 2, 100, 1000), // Copyright pointing behind the end of the file
+                (0x0101, 13, 1, 3),     // ImageLength with the (unknown) IFD type
+                (0x014A, 4, 1, 8),      // SubIFDs pointing back to IFD0
+            ],
+            62,
+        );
+        // IFD1 directly follows IFD0 and has a broken Copyright as well
+        let ifd1 = tiff_with_entries(&[(0x0100, 4, 1, 2), (0x8298, 2, 100, 1000)], 0);
+        data.extend_from_slice(&ifd1[8..]);
+        assert!(DngReader::read(Cursor::new(data.clone())).is_err());
+
+        let options = DngReaderOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let dng = DngReader::read_with_options(Cursor::new(data), options).unwrap();
+        assert_eq!(dng.get_ifd0().entries().len(), 1);
+        assert_eq!(dng.get_ifd(1).unwrap().entries().len(), 1);
+        let warned_paths: Vec<_> = dng
+            .warnings()
+            .iter()
+            .map(|warning| format!("{:?}", warning.path))
+            .collect();
+        assert_eq!(
+            warned_paths,
+            [
+                "IFD0.ImageLength",
+                "IFD0.Copyright",
+                "IFD0.SubIFDs",
+                "IFD1.Copyright"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lenient_truncated_file() {
+        let data = std::fs::read("src/yaml/testdata/pentax_k30.dcp").unwrap();
+        assert!(DngReader::read(Cursor::new(data.clone())).is_err());
+
+        let options = DngReaderOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let dng = DngReader::read_with_options(Cursor::new(data), options).unwrap();
+        assert!(!dng.get_ifd0().entries().is_empty());
+        assert!(!dng.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_ifd_chain() {
+        let ifds: Vec<_> = ["first", "second"]
+            .iter()
+            .map(|copyright| {
+                let mut ifd = Ifd::new(IfdType::Ifd);
+                ifd.insert(ifd::Copyright, *copyright);
+                ifd
+            })
+            .collect();
+        let mut data = Vec::new();
+        DngWriter::write_dng(Cursor::new(&mut data), true, FileType::Dng, ifds.clone()).unwrap();
+
+        let dng = DngReader::read(Cursor::new(data)).unwrap();
+        assert_eq!(dng.ifds(), &ifds[..]);
+        assert_eq!(dng.get_ifd(1), Some(&ifds[1]));
+        assert_eq!(dng.get_ifd(2), None);
+
+        let path = IfdPath::chain_index(1).chain_tag(ifd::Copyright);
+        assert_eq!(format!("{path:?}"), "IFD1.Copyright");
+        let entry = dng.get_entry_by_path(&path).unwrap();
+        assert_eq!(entry.value, &IfdValue::from("second"));
+    }
+
+    #[test]
+    fn test_image_data_longer_than_file() {
+        // two strips of 2 GiB each, whose lengths dont even fit into an u32 together
+        let mut data = tiff_with_entries(&[(0x0111, 4, 2, 38), (0x0117, 4, 2, 46)], 0);
+        for value in [8u32, 8, 0x80000000, 0x80000000] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let dng = DngReader::read(Cursor::new(data)).unwrap();
+        assert!(matches!(
+            dng.needed_buffer_length_for_image_data(&IfdPath::default()),
+            Err(DngReaderError::FormatError(_))
+        ));
+    }
+
+    #[test]
+    fn test_empty_ascii() {
+        let dng = DngReader::read(Cursor::new(tiff_with_entries(&[(0x8298, 2, 0, 0)], 0)));
+        assert!(dng.is_ok());
+    }
+}
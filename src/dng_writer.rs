@@ -1,7 +1,10 @@
 use crate::byte_order_rw::ByteOrderWriter;
+use crate::digest::insert_digests;
 use crate::ifd::{Ifd, IfdEntry, IfdValue};
 use crate::FileType;
 use derivative::Derivative;
+#[cfg(feature = "async")]
+use futures_util::io::{AsyncWrite, AsyncWriteExt};
 use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::io;
@@ -67,6 +70,14 @@ impl<W: Write + Seek, T> WritePlan<W, T> {
     }
 }
 
+/// Additional options for [DngWriter::write_dng_with_options]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DngWriterOptions {
+    /// Compute the `RawImageDigest` and `NewRawImageDigest` of the main raw image and store them in
+    /// IFD0 (replacing existing ones). This requires the raw image data to be uncompressed.
+    pub compute_digests: bool,
+}
+
 /// The main entrypoint for writing DNG / DCP files
 ///
 /// example:
@@ -99,6 +110,28 @@ impl<W: Write + Seek> DngWriter<W> {
         file_type: FileType,
         ifds: Vec<Ifd>,
     ) -> io::Result<()> {
+        Self::write_dng_with_options(
+            writer,
+            is_little_endian,
+            file_type,
+            ifds,
+            DngWriterOptions::default(),
+        )
+    }
+
+    /// Same as [write_dng][DngWriter::write_dng] but allows to specify additional [DngWriterOptions]
+    pub fn write_dng_with_options(
+        writer: W,
+        is_little_endian: bool,
+        file_type: FileType,
+        mut ifds: Vec<Ifd>,
+        options: DngWriterOptions,
+    ) -> io::Result<()> {
+        if options.compute_digests {
+            insert_digests(&mut ifds, is_little_endian)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        }
+
         let plan = Arc::new(WritePlan::default());
         let dng_writer = Self {
             is_little_endian,
@@ -120,11 +153,37 @@ impl<W: Write + Seek> DngWriter<W> {
         dng_writer.plan.execute(&mut writer, &dng_writer)
     }
 
+    /// Appends a chain of IFDs (including their values) to the end of an existing file and returns
+    /// the offset of the first one. Nothing that is already in the file is touched.
+    pub(crate) fn append_ifds(
+        mut writer: W,
+        is_little_endian: bool,
+        ifds: Vec<Ifd>,
+    ) -> io::Result<u32> {
+        let end = writer.seek(SeekFrom::End(0))?;
+        let end = u32::try_from(end)
+            .map_err(|_| io::Error::other("cant append to a file larger than 4 GiB"))?;
+        let plan = Arc::new(WritePlan::default());
+        *plan.write_ptr.borrow_mut() = end;
+        let dng_writer = Self {
+            is_little_endian,
+            plan,
+        };
+        let address = dng_writer.write_ifds(ifds);
+
+        let mut writer = ByteOrderWriter::new(writer, is_little_endian);
+        dng_writer.plan.execute(&mut writer, &dng_writer)?;
+        Ok(address)
+    }
+
     fn write_ifds(&self, mut ifds: Vec<Ifd>) -> u32 {
         if ifds.is_empty() {
             return 0; // we write a nullptr to signify that the IFD chain ends
         }
-        let ifd = ifds.remove(0);
+        let mut ifd = ifds.remove(0);
+        // TIFF requires the entries of an IFD to be sorted by their tag. Sub-IFDs are sorted when
+        // they are written through here as well.
+        ifd.entries.sort_by_key(|entry| entry.tag.numeric());
 
         // the IFD size is:
         // * 2 byte count
@@ -235,3 +294,88 @@ impl<W: Write + Seek> DngWriter<W> {
         }
     }
 }
+
+/// The async variant of [DngWriter::write_dng] for writers implementing [AsyncWrite]
+///
+/// NOTE: the [DngWriter] seeks back and forth while it lays out the file, which an [AsyncWrite]
+/// cant do. Therefore the whole file (including the image data) is assembled in an in-memory
+/// buffer first, which is then written out in one go. This needs enough memory to hold the file,
+/// but the writer doesnt have to implement `AsyncSeek`.
+#[cfg(feature = "async")]
+pub async fn write_dng_async(
+    mut writer: impl AsyncWrite + Unpin,
+    is_little_endian: bool,
+    file_type: FileType,
+    ifds: Vec<Ifd>,
+) -> io::Result<()> {
+    let mut buffer = io::Cursor::new(Vec::new());
+    DngWriter::write_dng(&mut buffer, is_little_endian, file_type, ifds)?;
+    writer.write_all(buffer.get_ref()).await?;
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::strategies::{arb_ifd, IfdStrategyConfig};
+    use crate::ifd::{Ifd, IfdValue};
+    use crate::tags::{exif, ifd, IfdType};
+    use crate::{DngEditor, DngReader, DngWriter, FileType};
+    use proptest::prelude::*;
+    use std::io::Cursor;
+
+    proptest! {
+        #[test]
+        fn test_write_read_roundtrip(
+            ifds in proptest::collection::vec(
+                arb_ifd(IfdType::Ifd, IfdStrategyConfig { text_safe: false, max_depth: 2 }),
+                1..3,
+            ),
+            is_little_endian in any::<bool>(),
+        ) {
+            let mut buffer = Cursor::new(Vec::new());
+            DngWriter::write_dng(&mut buffer, is_little_endian, FileType::Dng, ifds.clone()).unwrap();
+            buffer.set_position(0);
+            let dng = DngReader::read(buffer).unwrap();
+            prop_assert_eq!(dng.ifds(), &ifds[..]);
+        }
+    }
+
+    /// Reads the tags of the IFD at the given offset of a little endian file in their order on disk
+    fn tags_on_disk(data: &[u8], offset: usize) -> Vec<u16> {
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        (0..u16_at(offset) as usize)
+            .map(|i| u16_at(offset + 2 + i * 12))
+            .collect()
+    }
+
+    #[test]
+    fn test_entries_are_sorted() {
+        let mut exif = Ifd::new(IfdType::Exif);
+        exif.insert(exif::ISOSpeedRatings, 100u16);
+        exif.insert(exif::ExposureTime, IfdValue::Rational(1, 50));
+        let mut ifd0 = Ifd::new(IfdType::Ifd);
+        ifd0.insert(ifd::Make, "Make");
+        ifd0.insert(ifd::ExifIFD, IfdValue::Ifd(exif));
+        ifd0.insert(ifd::ImageWidth, 8u32);
+
+        let mut data = Vec::new();
+        DngWriter::write_dng(Cursor::new(&mut data), true, FileType::Dng, vec![ifd0]).unwrap();
+        let ifd0_offset = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        assert_eq!(tags_on_disk(&data, ifd0_offset), [0x0100, 0x010F, 0x8769]);
+        let exif_entry = ifd0_offset + 2 + 2 * 12;
+        let exif_offset =
+            u32::from_le_bytes(data[exif_entry + 8..exif_entry + 12].try_into().unwrap());
+        assert_eq!(tags_on_disk(&data, exif_offset as usize), [0x829A, 0x8827]);
+
+        // entries inserted with the editor are appended to the IFD but sorted when written
+        let mut editor = DngEditor::open(Cursor::new(data)).unwrap();
+        editor.ifds_mut()[0].insert(ifd::Copyright, "someone");
+        editor.ifds_mut()[0].insert(ifd::Artist, "someone");
+        let data = editor.commit().unwrap().into_inner();
+        let ifd0_offset = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        assert_eq!(
+            tags_on_disk(&data, ifd0_offset),
+            [0x0100, 0x010F, 0x013B, 0x8298, 0x8769]
+        );
+    }
+}
@@ -1,13 +1,26 @@
-use crate::tags::{IfdType, IfdValueType, MaybeKnownIfdFieldDescriptor};
+use crate::tags::{IfdType, IfdTypeInterpretation, IfdValueType, MaybeKnownIfdFieldDescriptor};
 use derivative::Derivative;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::io::Write;
 use std::iter::once;
 use std::mem;
 use std::ops::Deref;
+use std::str::FromStr;
 use std::sync::Arc;
 
+mod diff;
+mod exiftool;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(test)]
+pub(crate) mod strategies;
+
+pub use diff::{IfdDiffEntry, IfdDiffOptions};
+pub use exiftool::{exiftool_entries, exiftool_json, ExiftoolEntry, ExiftoolValue};
+
 #[derive(Debug, Clone, Default)]
 /// Represents an IFD-Tree that was read / can be written
 pub struct Ifd {
@@ -38,6 +51,17 @@ impl Ifd {
         self.entries.retain(|e| e.tag != tag);
         self.entries.push(IfdEntry::new(tag, value))
     }
+    /// Returns the value of the entry with the given tag (if it exists)
+    pub fn get(&self, tag: impl Into<MaybeKnownIfdFieldDescriptor>) -> Option<&IfdValue> {
+        let tag = tag.into();
+        self.entries.iter().find(|e| e.tag == tag).map(|e| &e.value)
+    }
+    /// Removes the entry with the given tag and returns its value (if it existed)
+    pub fn remove(&mut self, tag: impl Into<MaybeKnownIfdFieldDescriptor>) -> Option<IfdValue> {
+        let tag = tag.into();
+        let index = self.entries.iter().position(|e| e.tag == tag)?;
+        Some(self.entries.remove(index).value)
+    }
     /// Inserts an entry into the IFD at the given path, overwriting a previously existing entry there.
     /// Returns the previous value if it existed, does nothing otherwise.
     pub fn replace_by_path(
@@ -45,7 +69,7 @@ impl Ifd {
         path: &IfdPath,
         value: impl Into<IfdValue>,
     ) -> Option<IfdValue> {
-        let path_vec = path.as_vec();
+        let path_vec = path.without_chain_index();
         let mut current = if let Some(IfdPathElement::Tag(tag)) = path_vec.first() {
             self.entries
                 .iter_mut()
@@ -65,10 +89,92 @@ impl Ifd {
             None
         }
     }
+    /// Inserts a value at the given path, overwriting a previously existing value there. Missing
+    /// IFDs along the path (e.g. a `GPSInfoIFD`) are created, while list elements must exist.
+    pub fn insert_by_path(
+        &mut self,
+        path: &IfdPath,
+        value: impl Into<IfdValue>,
+    ) -> Result<(), String> {
+        self.insert_by_path_elements(path.without_chain_index(), value.into())
+    }
+    fn insert_by_path_elements(
+        &mut self,
+        path: &[IfdPathElement],
+        value: IfdValue,
+    ) -> Result<(), String> {
+        let (tag, rest) = match path {
+            [IfdPathElement::Tag(tag), rest @ ..] => (*tag, rest),
+            _ => return Err(format!("cant insert at '{path:?}' without a leading tag")),
+        };
+        if rest.is_empty() {
+            self.insert(tag, value);
+            return Ok(());
+        }
+        if self.get(tag).is_none() {
+            match (tag.get_type_interpretation(), rest.first()) {
+                (
+                    Some(IfdTypeInterpretation::IfdOffset { ifd_type }),
+                    Some(IfdPathElement::Tag(_)),
+                ) => self.insert(tag, IfdValue::Ifd(Ifd::new(*ifd_type))),
+                _ => return Err(format!("'{tag}' doesnt exist")),
+            }
+        }
+
+        let mut current = self
+            .entries
+            .iter_mut()
+            .find(|e| e.tag == tag)
+            .map(|e| &mut e.value);
+        for (i, element) in rest.iter().enumerate() {
+            let parent = current.ok_or_else(|| format!("'{path:?}' doesnt exist"))?;
+            match (parent, element) {
+                (IfdValue::Ifd(ifd), IfdPathElement::Tag(_)) => {
+                    return ifd.insert_by_path_elements(&rest[i..], value)
+                }
+                (parent, element) => current = parent.index_with_mut(element.clone()),
+            }
+        }
+        *current.ok_or_else(|| format!("'{path:?}' doesnt exist"))? = value;
+        Ok(())
+    }
+    /// Removes the entry (or list element) at the given path and returns its value (if it existed)
+    pub fn remove_by_path(&mut self, path: &IfdPath) -> Option<IfdValue> {
+        let (last, parent_path) = path.without_chain_index().split_last()?;
+        if parent_path.is_empty() {
+            return match last {
+                IfdPathElement::Tag(tag) => self.remove(*tag),
+                _ => None,
+            };
+        }
+        let parent = self.get_mut_by_path_elements(parent_path)?;
+        match (parent, last) {
+            (IfdValue::Ifd(ifd), IfdPathElement::Tag(tag)) => ifd.remove(*tag),
+            (IfdValue::List(list), IfdPathElement::ListIndex(i)) if (*i as usize) < list.len() => {
+                Some(list.remove(*i as usize))
+            }
+            _ => None,
+        }
+    }
+    fn get_mut_by_path_elements(&mut self, path: &[IfdPathElement]) -> Option<&mut IfdValue> {
+        let mut current = match path.first() {
+            Some(IfdPathElement::Tag(tag)) => self
+                .entries
+                .iter_mut()
+                .find(|x| &x.tag == tag)
+                .map(|x| &mut x.value),
+            _ => None,
+        };
+        for element in &path[1..] {
+            current = current.and_then(|x| x.index_with_mut(element.clone()));
+        }
+        current
+    }
     /// Returns an ifd entry by path. It will return None for the empty path because we cant produce
-    /// a ref with an appropriate lifetime for `self`
+    /// a ref with an appropriate lifetime for `self`. A leading [IfdPathElement::ChainIndex] is
+    /// ignored, as the IFD doesnt know its position in the chain.
     pub fn get_entry_by_path<'a>(&'a self, path: &'a IfdPath) -> Option<IfdEntryRef<'a>> {
-        let path_vec = path.as_vec();
+        let path_vec = path.without_chain_index();
         let mut current = if let Some(IfdPathElement::Tag(tag)) = path_vec.first() {
             self.entries
                 .iter()
@@ -151,7 +257,10 @@ impl Ifd {
                         tag: &entry.tag,
                     };
                     if predicate(entry) {
-                        entries.push(path);
+                        entries.push(path.clone());
+                    }
+                    if let IfdValue::Ifd(ifd) = v {
+                        entries.extend(ifd.find_entries_with_start_path(path, predicate.clone()));
                     }
                 }
             } else if let IfdValue::Ifd(ifd) = &entry.value {
@@ -169,8 +278,39 @@ impl Ifd {
         &self.entries
     }
 }
+/// Two IFDs are equal if they have the same type and contain equal entries, regardless of the order
+/// of the entries.
+impl PartialEq for Ifd {
+    fn eq(&self, other: &Self) -> bool {
+        self.ifd_type == other.ifd_type
+            && self.entries.len() == other.entries.len()
+            && self
+                .entries
+                .iter()
+                .all(|entry| other.entries.iter().any(|other| entry == other))
+    }
+}
+impl Eq for Ifd {}
+impl Hash for Ifd {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // the hash must not depend on the order of the entries, so we combine the hashes of the
+        // entries in a sorted order
+        let mut entry_hashes: Vec<u64> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut hasher = DefaultHasher::new();
+                entry.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect();
+        entry_hashes.sort_unstable();
+        self.ifd_type.hash(state);
+        entry_hashes.hash(state);
+    }
+}
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 /// A singular entry in an IFD (that does not know its path)
 pub struct IfdEntry {
     pub value: IfdValue,
@@ -195,13 +335,17 @@ impl IfdEntry {
     }
 }
 
-#[derive(Clone, PartialEq, Default, Eq)]
+#[derive(Clone, PartialEq, Default, Eq, Hash)]
 /// The absolute path at which the entry is found in the IFD-tree
 pub struct IfdPath(Vec<IfdPathElement>);
 impl IfdPath {
     pub fn chain_path_element(&self, element: IfdPathElement) -> Self {
         Self(self.0.iter().cloned().chain(once(element)).collect())
     }
+    /// The path of the n-th toplevel IFD of a file
+    pub fn chain_index(n: u16) -> Self {
+        Self(vec![IfdPathElement::ChainIndex(n)])
+    }
     pub fn chain_list_index(&self, n: u16) -> Self {
         self.chain_path_element(IfdPathElement::ListIndex(n))
     }
@@ -223,6 +367,23 @@ impl IfdPath {
     pub fn as_vec(&self) -> &Vec<IfdPathElement> {
         &self.0
     }
+    /// Returns the index of the toplevel IFD this path starts in (if it is specified)
+    pub fn get_chain_index(&self) -> Option<u16> {
+        match self.0.first() {
+            Some(IfdPathElement::ChainIndex(n)) => Some(*n),
+            _ => None,
+        }
+    }
+    /// Appends a relative path (e.g. one that was produced for a single [Ifd]) to this path
+    pub fn chain_path(&self, relative: &IfdPath) -> Self {
+        Self(self.0.iter().chain(relative.0.iter()).cloned().collect())
+    }
+    fn without_chain_index(&self) -> &[IfdPathElement] {
+        match self.0.first() {
+            Some(IfdPathElement::ChainIndex(_)) => &self.0[1..],
+            _ => &self.0,
+        }
+    }
     pub fn with_last_tag_replaced(&self, replacement: MaybeKnownIfdFieldDescriptor) -> Self {
         let mut new_vec = self.as_vec().clone();
         for elem in new_vec.iter_mut().rev() {
@@ -248,9 +409,72 @@ impl Debug for IfdPath {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Parses paths in the format of the [Debug] output of [IfdPath], e.g. `IFD1.ExifIFD.ExposureTime`,
+/// `SubIFDs[1].StripOffsets[0]` (or `SubIFDs.1.StripOffsets.0`) and `0xC612`.
+///
+/// Tag names are looked up in the namespace of the IFD they are in, i.e. the tags following
+/// `ExifIFD` or `GPSInfoIFD` are EXIF / GPS tags. The `IFD<n>` prefix is optional.
+impl FromStr for IfdPath {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut path = IfdPath::default();
+        if source.is_empty() {
+            return Ok(path);
+        }
+        let mut ifd_type = IfdType::Ifd;
+        for (i, segment) in source.split('.').enumerate() {
+            let (name, indices) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+            if i == 0 && indices.is_empty() {
+                if let Some(Ok(n)) = name.strip_prefix("IFD").map(str::parse) {
+                    path = IfdPath::chain_index(n);
+                    continue;
+                }
+            }
+
+            if let Ok(index) = name.parse() {
+                if path.last_tag().is_none() {
+                    return Err(format!("list index '{name}' doesnt follow a tag"));
+                }
+                path = path.chain_list_index(index);
+            } else {
+                let hex = name.strip_prefix("0x").or_else(|| name.strip_prefix("0X"));
+                let tag = match hex {
+                    Some(hex) => u16::from_str_radix(hex, 16)
+                        .map(|n| MaybeKnownIfdFieldDescriptor::from_number(n, ifd_type))
+                        .map_err(|_| format!("invalid tag number '{name}' in '{source}'"))?,
+                    None => MaybeKnownIfdFieldDescriptor::from_name(name, ifd_type)?,
+                };
+                if let Some(IfdTypeInterpretation::IfdOffset { ifd_type: sub_type }) =
+                    tag.get_type_interpretation()
+                {
+                    ifd_type = *sub_type;
+                }
+                path = path.chain_tag(tag);
+            }
+
+            let mut indices = indices;
+            while !indices.is_empty() {
+                let (index, rest) = indices
+                    .strip_prefix('[')
+                    .and_then(|indices| indices.split_once(']'))
+                    .ok_or_else(|| format!("invalid list index '{indices}' in '{source}'"))?;
+                let index = index
+                    .parse()
+                    .map_err(|_| format!("invalid list index '{index}' in '{source}'"))?;
+                path = path.chain_list_index(index);
+                indices = rest;
+            }
+        }
+        Ok(path)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 /// A segment of an [IfdPath]
 pub enum IfdPathElement {
+    /// Selects one of the toplevel IFDs of a file. Can only appear as the first element of a path
+    ChainIndex(u16),
     Tag(MaybeKnownIfdFieldDescriptor),
     ListIndex(u16),
 }
@@ -258,6 +482,7 @@ impl Display for IfdPathElement {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             IfdPathElement::Tag(tag) => f.write_fmt(format_args!("{tag}")),
+            IfdPathElement::ChainIndex(n) => f.write_fmt(format_args!("IFD{n}")),
             IfdPathElement::ListIndex(n) => f.write_fmt(format_args!("{n}")),
         }
     }
@@ -348,6 +573,17 @@ impl IfdValue {
         }
     }
 
+    /// Returns the bytes an [IfdValue::Offsets] value would write. Returns `None` for other values
+    pub fn offsets_bytes(&self) -> Option<Vec<u8>> {
+        if let IfdValue::Offsets(blob) = self {
+            let mut buffer = Vec::with_capacity(blob.size() as usize);
+            blob.write(&mut buffer).ok()?;
+            Some(buffer)
+        } else {
+            None
+        }
+    }
+
     pub fn get_count(&self) -> u32 {
         match self {
             IfdValue::List(list) => list.len() as u32,
@@ -384,6 +620,60 @@ impl IfdValue {
     }
 }
 
+/// Values are compared structurally with the following policy:
+/// * floats are compared by their bit representation, so `NaN` equals `NaN` (if the payload is the same)
+///   and `0.0` does not equal `-0.0`. This keeps the comparison reflexive and matches what is stored in the file.
+/// * [IfdValue::Offsets] are compared by the bytes they would write
+/// * values of different types are never equal (e.g. `Short(1) != Long(1)`)
+impl PartialEq for IfdValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Byte(a), Self::Byte(b)) => a == b,
+            (Self::Ascii(a), Self::Ascii(b)) => a == b,
+            (Self::Short(a), Self::Short(b)) => a == b,
+            (Self::Long(a), Self::Long(b)) => a == b,
+            (Self::Rational(a, b), Self::Rational(c, d)) => a == c && b == d,
+            (Self::SByte(a), Self::SByte(b)) => a == b,
+            (Self::Undefined(a), Self::Undefined(b)) => a == b,
+            (Self::SShort(a), Self::SShort(b)) => a == b,
+            (Self::SLong(a), Self::SLong(b)) => a == b,
+            (Self::SRational(a, b), Self::SRational(c, d)) => a == c && b == d,
+            (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+            (Self::Double(a), Self::Double(b)) => a.to_bits() == b.to_bits(),
+            (Self::List(a), Self::List(b)) => a == b,
+            (Self::Ifd(a), Self::Ifd(b)) => a == b,
+            (Self::Offsets(a), Self::Offsets(b)) => {
+                Arc::ptr_eq(a, b) || (a.size() == b.size() && self.offsets_bytes() == other.offsets_bytes())
+            }
+            _ => false,
+        }
+    }
+}
+impl Eq for IfdValue {}
+/// Hashing follows the same policy as the comparison (see [PartialEq] implementation)
+impl Hash for IfdValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Self::Byte(x) => x.hash(state),
+            Self::Ascii(x) => x.hash(state),
+            Self::Short(x) => x.hash(state),
+            Self::Long(x) => x.hash(state),
+            Self::Rational(x, y) => (x, y).hash(state),
+            Self::SByte(x) => x.hash(state),
+            Self::Undefined(x) => x.hash(state),
+            Self::SShort(x) => x.hash(state),
+            Self::SLong(x) => x.hash(state),
+            Self::SRational(x, y) => (x, y).hash(state),
+            Self::Float(x) => x.to_bits().hash(state),
+            Self::Double(x) => x.to_bits().hash(state),
+            Self::List(x) => x.hash(state),
+            Self::Ifd(x) => x.hash(state),
+            Self::Offsets(_) => self.offsets_bytes().hash(state),
+        }
+    }
+}
+
 macro_rules! implement_from {
     ($rust_type:ty, $variant:expr) => {
         impl From<$rust_type> for IfdValue {
@@ -435,3 +725,92 @@ impl<T: Deref<Target = [u8]>> Offsets for T {
         writer.write_all(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::{Ifd, IfdPath, IfdValue};
+    use crate::tags::{exif, gps_info, ifd, IfdType, MaybeKnownIfdFieldDescriptor};
+
+    #[test]
+    fn test_insert_remove_by_path() {
+        let mut ifd0 = Ifd::new(IfdType::Ifd);
+        ifd0.insert(ifd::ExifIFD, IfdValue::Ifd(Ifd::new(IfdType::Exif)));
+        ifd0.insert(
+            ifd::SubIFDs,
+            IfdValue::List(vec![IfdValue::Ifd(Ifd::default())]),
+        );
+
+        let exposure_time = IfdPath::default()
+            .chain_tag(ifd::ExifIFD)
+            .chain_tag(exif::ExposureTime);
+        ifd0.insert_by_path(&exposure_time, IfdValue::Rational(1, 50))
+            .unwrap();
+        assert_eq!(
+            ifd0.get_entry_by_path(&exposure_time).unwrap().value,
+            &IfdValue::Rational(1, 50)
+        );
+
+        // missing IFDs are created, missing list elements are not
+        let latitude_ref = IfdPath::default()
+            .chain_tag(ifd::GPSInfoIFD)
+            .chain_tag(gps_info::GPSLatitudeRef);
+        ifd0.insert_by_path(&latitude_ref, "N").unwrap();
+        match ifd0.get(ifd::GPSInfoIFD) {
+            Some(IfdValue::Ifd(gps)) => assert_eq!(gps.get_type(), IfdType::GpsInfo),
+            other => panic!("expected a GPS IFD, got {other:?}"),
+        }
+        let sub_ifd = IfdPath::default().chain_tag(ifd::SubIFDs);
+        let width = sub_ifd.chain_list_index(0).chain_tag(ifd::ImageWidth);
+        ifd0.insert_by_path(&width, 16u32).unwrap();
+        assert!(ifd0
+            .insert_by_path(
+                &sub_ifd.chain_list_index(1).chain_tag(ifd::ImageWidth),
+                16u32
+            )
+            .is_err());
+
+        assert_eq!(ifd0.remove_by_path(&width), Some(IfdValue::Long(16)));
+        assert_eq!(ifd0.remove_by_path(&width), None);
+        assert!(ifd0.remove_by_path(&sub_ifd.chain_list_index(0)).is_some());
+        assert_eq!(ifd0.get(ifd::SubIFDs), Some(&IfdValue::List(vec![])));
+        assert!(ifd0.remove_by_path(&latitude_ref.parent()).is_some());
+    }
+
+    #[test]
+    fn test_parse_path() {
+        let path: IfdPath = "IFD1.ExifIFD.ExposureTime".parse().unwrap();
+        assert_eq!(
+            path,
+            IfdPath::chain_index(1)
+                .chain_tag(ifd::ExifIFD)
+                .chain_tag(exif::ExposureTime)
+        );
+        let strip = IfdPath::default()
+            .chain_tag(ifd::SubIFDs)
+            .chain_list_index(1)
+            .chain_tag(ifd::StripOffsets)
+            .chain_list_index(0);
+        assert_eq!("SubIFDs[1].StripOffsets[0]".parse(), Ok(strip.clone()));
+        assert_eq!(
+            "0xC612".parse(),
+            Ok(IfdPath::default().chain_tag(ifd::DNGVersion))
+        );
+        assert!("ExifIFD.Make".parse::<IfdPath>().is_err());
+        assert!("SubIFDs[x]".parse::<IfdPath>().is_err());
+
+        // the debug output can be parsed again, even for unknown tags
+        let unknown = IfdPath::chain_index(0)
+            .chain_tag(ifd::GPSInfoIFD)
+            .chain_tag(gps_info::GPSLatitude)
+            .chain_list_index(2);
+        for path in [
+            strip,
+            unknown,
+            IfdPath::default()
+                .chain_tag(ifd::ExifIFD)
+                .chain_tag(MaybeKnownIfdFieldDescriptor::Unknown(0xFEED)),
+        ] {
+            assert_eq!(format!("{path:?}").parse(), Ok(path));
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::ifd::{Ifd, IfdPath, IfdValue};
+
+/// Controls how [Ifd::diff] decides whether two values are equal
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IfdDiffOptions {
+    /// The maximum absolute difference up to which two [IfdValue::Float] or [IfdValue::Double]
+    /// values are considered equal. With the default of `0.0` floats are compared exactly.
+    pub float_tolerance: f64,
+    /// The maximum absolute difference up to which two [IfdValue::Rational] or
+    /// [IfdValue::SRational] values are considered equal. With the default of `0.0` numerator and
+    /// denominator have to match exactly (so `1/2` and `2/4` are different).
+    pub rational_tolerance: f64,
+}
+
+/// A single difference between two IFD trees as produced by [Ifd::diff]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfdDiffEntry {
+    /// The entry exists only in the new tree
+    Added { path: IfdPath, value: IfdValue },
+    /// The entry exists only in the old tree
+    Removed { path: IfdPath, value: IfdValue },
+    /// The entry exists in both trees but with different values
+    Changed {
+        path: IfdPath,
+        old: IfdValue,
+        new: IfdValue,
+    },
+}
+impl IfdDiffEntry {
+    pub fn path(&self) -> &IfdPath {
+        match self {
+            IfdDiffEntry::Added { path, .. } => path,
+            IfdDiffEntry::Removed { path, .. } => path,
+            IfdDiffEntry::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl Ifd {
+    /// Walks this (old) and another (new) IFD tree and returns all entries that were added, removed
+    /// or changed. Sub-IFDs and lists of the same length are descended into, so that only the
+    /// innermost differing values are reported.
+    pub fn diff(&self, other: &Ifd, options: &IfdDiffOptions) -> Vec<IfdDiffEntry> {
+        let mut diff = Vec::new();
+        diff_ifd(self, other, &IfdPath::default(), options, &mut diff);
+        diff
+    }
+}
+
+fn diff_ifd(
+    old: &Ifd,
+    new: &Ifd,
+    path: &IfdPath,
+    options: &IfdDiffOptions,
+    diff: &mut Vec<IfdDiffEntry>,
+) {
+    for entry in &old.entries {
+        let path = path.chain_tag(entry.tag);
+        match new.entries.iter().find(|x| x.tag == entry.tag) {
+            Some(new_entry) => diff_value(&entry.value, &new_entry.value, &path, options, diff),
+            None => diff.push(IfdDiffEntry::Removed {
+                path,
+                value: entry.value.clone(),
+            }),
+        }
+    }
+    for entry in &new.entries {
+        if !old.entries.iter().any(|x| x.tag == entry.tag) {
+            diff.push(IfdDiffEntry::Added {
+                path: path.chain_tag(entry.tag),
+                value: entry.value.clone(),
+            })
+        }
+    }
+}
+
+fn diff_value(
+    old: &IfdValue,
+    new: &IfdValue,
+    path: &IfdPath,
+    options: &IfdDiffOptions,
+    diff: &mut Vec<IfdDiffEntry>,
+) {
+    match (old, new) {
+        (IfdValue::Ifd(old), IfdValue::Ifd(new)) => diff_ifd(old, new, path, options, diff),
+        (IfdValue::List(old), IfdValue::List(new)) if old.len() == new.len() => {
+            for (i, (old, new)) in old.iter().zip(new.iter()).enumerate() {
+                diff_value(old, new, &path.chain_list_index(i as u16), options, diff)
+            }
+        }
+        (old, new) if !values_equal(old, new, options) => diff.push(IfdDiffEntry::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn values_equal(a: &IfdValue, b: &IfdValue, options: &IfdDiffOptions) -> bool {
+    let within = |tolerance: f64| match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => (a - b).abs() <= tolerance,
+        _ => false,
+    };
+    match (a, b) {
+        (IfdValue::Float(_), IfdValue::Float(_)) | (IfdValue::Double(_), IfdValue::Double(_))
+            if options.float_tolerance > 0.0 =>
+        {
+            a == b || within(options.float_tolerance)
+        }
+        (IfdValue::Rational(..), IfdValue::Rational(..))
+        | (IfdValue::SRational(..), IfdValue::SRational(..))
+            if options.rational_tolerance > 0.0 =>
+        {
+            a == b || within(options.rational_tolerance)
+        }
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::{Ifd, IfdDiffEntry, IfdDiffOptions, IfdPath, IfdValue};
+    use crate::tags::{exif, ifd, IfdType};
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn example_ifd(exposure_time: (u32, u32), software: &str) -> Ifd {
+        let mut exif_ifd = Ifd::new(IfdType::Exif);
+        exif_ifd.insert(
+            exif::ExposureTime,
+            IfdValue::Rational(exposure_time.0, exposure_time.1),
+        );
+        let mut ifd = Ifd::new(IfdType::Ifd);
+        ifd.insert(ifd::Software, software);
+        ifd.insert(ifd::BaselineExposure, IfdValue::SRational(1, 3));
+        ifd.insert(ifd::ExifIFD, IfdValue::Ifd(exif_ifd));
+        ifd
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = example_ifd((1, 100), "firmware 1.0");
+        let mut new = example_ifd((10, 1000), "firmware 1.1");
+        new.insert(ifd::Copyright, "someone");
+        new.entries
+            .retain(|entry| entry.tag != ifd::BaselineExposure.as_maybe());
+
+        let diff = old.diff(&new, &IfdDiffOptions::default());
+        let exposure_path = IfdPath::default()
+            .chain_tag(ifd::ExifIFD)
+            .chain_tag(exif::ExposureTime);
+        assert_eq!(
+            diff,
+            vec![
+                IfdDiffEntry::Changed {
+                    path: IfdPath::default().chain_tag(ifd::Software),
+                    old: "firmware 1.0".into(),
+                    new: "firmware 1.1".into(),
+                },
+                IfdDiffEntry::Removed {
+                    path: IfdPath::default().chain_tag(ifd::BaselineExposure),
+                    value: IfdValue::SRational(1, 3),
+                },
+                IfdDiffEntry::Changed {
+                    path: exposure_path,
+                    old: IfdValue::Rational(1, 100),
+                    new: IfdValue::Rational(10, 1000),
+                },
+                IfdDiffEntry::Added {
+                    path: IfdPath::default().chain_tag(ifd::Copyright),
+                    value: "someone".into(),
+                },
+            ]
+        );
+
+        let options = IfdDiffOptions {
+            rational_tolerance: 1e-9,
+            ..Default::default()
+        };
+        assert_eq!(old.diff(&new, &options).len(), 3);
+    }
+
+    #[test]
+    fn test_hash_ignores_entry_order() {
+        let a = example_ifd((1, 100), "a");
+        let mut b = a.clone();
+        b.entries.reverse();
+        let hash = |ifd: &Ifd| {
+            let mut hasher = DefaultHasher::new();
+            ifd.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert!(a.diff(&b, &IfdDiffOptions::default()).is_empty());
+    }
+}
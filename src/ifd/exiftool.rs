@@ -0,0 +1,259 @@
+use crate::ifd::{Ifd, IfdValue};
+use crate::tags::{ifd, IfdTypeInterpretation, MaybeKnownIfdFieldDescriptor};
+
+/// A single entry the way `exiftool -j -n -G1` reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExiftoolEntry {
+    /// The (family 1) group, e.g. `IFD0`, `ExifIFD`, `GPS` or `SubIFD1`
+    pub group: String,
+    /// The exiftool name of the tag, e.g. `ExifOffset` instead of `ExifIFD`
+    pub name: String,
+    pub value: ExiftoolValue,
+}
+impl ExiftoolEntry {
+    /// The key of the entry in the JSON output (e.g. `IFD0:Make`)
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.group, self.name)
+    }
+}
+
+/// A value formatted like exiftool does it with `-n` (without converting it to a description)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExiftoolValue {
+    /// A single number. Rationals are rounded to 10, FLOATs to 7 and DOUBLEs to 15 significant
+    /// digits.
+    Number(String),
+    /// Strings, lists (as their values separated by spaces) and placeholders for binary data
+    String(String),
+}
+
+/// Lists the entries of an IFD chain like exiftool does, so that its output can be cross-checked
+/// with `exiftool -j -n -G1`. Entries whose value depends on the file layout (pointers to
+/// sub-IFDs and the offsets of data that is not read yet) are left out.
+pub fn exiftool_entries(ifds: &[Ifd]) -> Vec<ExiftoolEntry> {
+    let mut entries = Vec::new();
+    let mut sub_ifd_count = 0;
+    for (i, ifd) in ifds.iter().enumerate() {
+        collect_entries(ifd, &format!("IFD{i}"), &mut sub_ifd_count, &mut entries);
+    }
+    entries
+}
+
+/// Dumps an IFD chain as JSON in the format of `exiftool -j -n -G1` (see [exiftool_entries])
+pub fn exiftool_json(ifds: &[Ifd], source_file: &str) -> String {
+    let mut json = format!("[{{\n  \"SourceFile\": {}", json_string(source_file));
+    for entry in exiftool_entries(ifds) {
+        let value = match &entry.value {
+            ExiftoolValue::Number(number) => number.clone(),
+            ExiftoolValue::String(string) => json_string(string),
+        };
+        json += &format!(",\n  {}: {value}", json_string(&entry.key()));
+    }
+    json + "\n}]\n"
+}
+
+fn collect_entries(
+    ifd: &Ifd,
+    group: &str,
+    sub_ifd_count: &mut usize,
+    entries: &mut Vec<ExiftoolEntry>,
+) {
+    for entry in &ifd.entries {
+        let sub_ifds: Vec<_> = entry
+            .value
+            .as_list()
+            .filter_map(|value| match value {
+                IfdValue::Ifd(sub_ifd) => Some(sub_ifd),
+                _ => None,
+            })
+            .collect();
+        if !sub_ifds.is_empty() {
+            for sub_ifd in sub_ifds {
+                let group = match entry.tag {
+                    // exiftool numbers all SubIFDs of a file, starting with `SubIFD`, `SubIFD1`
+                    MaybeKnownIfdFieldDescriptor::Known(tag) if tag == ifd::SubIFDs => {
+                        *sub_ifd_count += 1;
+                        match *sub_ifd_count {
+                            1 => tag.exiftool_group().to_string(),
+                            n => format!("{}{}", tag.exiftool_group(), n - 1),
+                        }
+                    }
+                    MaybeKnownIfdFieldDescriptor::Known(tag) => tag.exiftool_group().to_string(),
+                    tag => tag.exiftool_name(),
+                };
+                collect_entries(sub_ifd, &group, sub_ifd_count, entries);
+            }
+        } else if !matches!(entry.value, IfdValue::Offsets(_)) {
+            entries.push(ExiftoolEntry {
+                group: group.to_string(),
+                name: entry.tag.exiftool_name(),
+                value: exiftool_value(&entry.tag, &entry.value),
+            })
+        }
+    }
+}
+
+fn exiftool_value(tag: &MaybeKnownIfdFieldDescriptor, value: &IfdValue) -> ExiftoolValue {
+    if let Some(IfdTypeInterpretation::Blob) = tag.get_type_interpretation() {
+        let bytes = value.get_count() * value.get_ifd_value_type().needed_bytes();
+        return ExiftoolValue::String(format!(
+            "(Binary data {bytes} bytes, use -b option to extract)"
+        ));
+    }
+    match value {
+        IfdValue::Ascii(string) => ExiftoolValue::String(string.trim_end_matches('\0').to_string()),
+        IfdValue::List(list) => {
+            let bytes: Option<Vec<u8>> = list
+                .iter()
+                .map(|value| match value {
+                    IfdValue::Undefined(byte) => Some(*byte),
+                    _ => None,
+                })
+                .collect();
+            match bytes {
+                // e.g. ExifVersion, which exiftool prints as `0230`
+                Some(bytes) if bytes.iter().all(|byte| (0x20..0x7F).contains(byte)) => {
+                    ExiftoolValue::String(String::from_utf8(bytes).unwrap())
+                }
+                _ => {
+                    let values: Vec<_> = list.iter().map(format_number).collect();
+                    ExiftoolValue::String(values.join(" "))
+                }
+            }
+        }
+        value => {
+            let number = format_number(value);
+            match number.parse::<f64>() {
+                Ok(parsed) if parsed.is_finite() => ExiftoolValue::Number(number),
+                _ => ExiftoolValue::String(number),
+            }
+        }
+    }
+}
+
+fn format_number(value: &IfdValue) -> String {
+    match value {
+        IfdValue::Rational(_, 0) | IfdValue::SRational(_, 0) => match value.as_f64() {
+            Some(f) if f.is_nan() => "undef".to_string(),
+            _ => "inf".to_string(),
+        },
+        IfdValue::Rational(..) | IfdValue::SRational(..) => {
+            format_significant(value.as_f64().unwrap(), 10)
+        }
+        IfdValue::Float(f) => format_significant(*f as f64, 7),
+        IfdValue::Double(f) => format_significant(*f, 15),
+        IfdValue::Byte(x) => x.to_string(),
+        IfdValue::Short(x) => x.to_string(),
+        IfdValue::Long(x) => x.to_string(),
+        IfdValue::SByte(x) => x.to_string(),
+        IfdValue::Undefined(x) => x.to_string(),
+        IfdValue::SShort(x) => x.to_string(),
+        IfdValue::SLong(x) => x.to_string(),
+        IfdValue::Ascii(string) => string.trim_end_matches('\0').to_string(),
+        value => format!("{value:?}"),
+    }
+}
+
+/// Formats a number like the `%.*g` of printf does (which exiftool uses for rounding)
+fn format_significant(value: f64, digits: usize) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return match value {
+            f if f.is_nan() => "nan".to_string(),
+            f if f.is_infinite() && f > 0.0 => "inf".to_string(),
+            f if f.is_infinite() => "-inf".to_string(),
+            _ => "0".to_string(),
+        };
+    }
+    let trim = |number: String| match number.contains('.') {
+        true => number
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string(),
+        false => number,
+    };
+    // the exponent after rounding to the given digits
+    let scientific = format!("{:.*e}", digits - 1, value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    if exponent < -4 || exponent >= digits as i32 {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{sign}{:02}", trim(mantissa.to_string()), exponent.abs())
+    } else {
+        let decimals = (digits as i32 - 1 - exponent) as usize;
+        trim(format!("{value:.decimals$}"))
+    }
+}
+
+fn json_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len() + 2);
+    escaped.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::exiftool::format_significant;
+    use crate::ifd::{exiftool_json, Ifd, IfdValue};
+    use crate::tags::{exif, ifd, IfdType};
+
+    #[test]
+    fn test_exiftool_json() {
+        let mut exif = Ifd::new(IfdType::Exif);
+        exif.insert(exif::ExposureTime, IfdValue::Rational(1, 60));
+        exif.insert(exif::ISOSpeedRatings, 800u16);
+        exif.insert(
+            exif::ExifVersion,
+            IfdValue::List(b"0230".map(IfdValue::Undefined).to_vec()),
+        );
+        let mut sub_ifd = Ifd::new(IfdType::Ifd);
+        sub_ifd.insert(ifd::ImageLength, 1080u32);
+        let mut ifd0 = Ifd::new(IfdType::Ifd);
+        ifd0.insert(ifd::Make, "Some \"Make\"");
+        ifd0.insert(ifd::DNGVersion, &[1u8, 4, 0, 0][..]);
+        ifd0.insert(
+            ifd::AsShotNeutral,
+            &[IfdValue::Rational(1, 2), IfdValue::Rational(1, 3)][..],
+        );
+        ifd0.insert(ifd::ExifIFD, IfdValue::Ifd(exif));
+        ifd0.insert(
+            ifd::SubIFDs,
+            IfdValue::List(vec![IfdValue::Ifd(sub_ifd.clone()), IfdValue::Ifd(sub_ifd)]),
+        );
+        ifd0.insert(ifd::DNGPrivateData, &[0u8; 12][..]);
+
+        assert_eq!(
+            exiftool_json(&[ifd0], "test.dng"),
+            r#"[{
+  "SourceFile": "test.dng",
+  "IFD0:Make": "Some \"Make\"",
+  "IFD0:DNGVersion": "1 4 0 0",
+  "IFD0:AsShotNeutral": "0.5 0.3333333333",
+  "ExifIFD:ExposureTime": 0.01666666667,
+  "ExifIFD:ISO": 800,
+  "ExifIFD:ExifVersion": "0230",
+  "SubIFD:ImageHeight": 1080,
+  "SubIFD1:ImageHeight": 1080,
+  "IFD0:DNGPrivateData": "(Binary data 12 bytes, use -b option to extract)"
+}]
+"#
+        );
+
+        assert_eq!(format_significant(1234567.0, 7), "1234567");
+        assert_eq!(format_significant(12345678.0, 7), "1.234568e+07");
+        assert_eq!(format_significant(0.000012345, 3), "1.23e-05");
+        assert_eq!(format_significant(-2.5, 10), "-2.5");
+        assert_eq!(format_significant(9.9999999999, 10), "10");
+    }
+}
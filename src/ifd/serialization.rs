@@ -0,0 +1,584 @@
+//! [serde] support for IFD trees.
+//!
+//! The representation follows the YAML one of [crate::yaml]: IFDs are maps keyed by tag name (or
+//! the hex tag number for unknown tags), enumerated values are given by name and rationals as
+//! `[numerator, denominator]` pairs. Values whose dtype is not the first one their tag is specified
+//! with are wrapped in a single entry map like `{"SRATIONAL": [-1, 3]}`. Non-finite floats are
+//! given as strings (`"NaN"`, `"inf"`, `"-inf"` or the hex bits for NaNs with a payload) and
+//! [IfdValue::Offsets] as `{"OFFSETS": <bytes>}`.
+//!
+//! Standalone [IfdValue]s always carry their dtype as they have no tag to infer it from.
+//! Deserializing requires a self-describing format (e.g. JSON, CBOR, MessagePack or TOML). Floats
+//! only roundtrip exactly if the format reads them exactly (for `serde_json` this needs the
+//! `float_roundtrip` feature).
+
+use crate::ifd::{Ifd, IfdEntry, IfdPath, IfdValue};
+use crate::tags::{IfdType, IfdTypeInterpretation, IfdValueType, MaybeKnownIfdFieldDescriptor};
+use serde::de::{Error as _, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Formatter;
+use std::sync::Arc;
+
+/// The pseudo dtype under which the data of [IfdValue::Offsets] is given
+const OFFSETS_KEY: &str = "OFFSETS";
+
+impl Serialize for Ifd {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.entries.len()))?;
+        for entry in &self.entries {
+            map.serialize_entry(
+                &tag_key(&entry.tag),
+                &ValueRepr::in_tag(&entry.value, &entry.tag),
+            )?;
+        }
+        map.end()
+    }
+}
+impl<'de> Deserialize<'de> for Ifd {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Raw::deserialize(deserializer)? {
+            Raw::Map(entries) => ifd_from_raw(&entries, IfdType::Ifd).map_err(D::Error::custom),
+            raw => Err(D::Error::custom(format!(
+                "expected an IFD map, got {raw:?}"
+            ))),
+        }
+    }
+}
+
+/// An entry is represented as a map with a single entry (e.g. `{"Orientation": "RowTopColumnLeft"}`)
+impl Serialize for IfdEntry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(
+            &tag_key(&self.tag),
+            &ValueRepr::in_tag(&self.value, &self.tag),
+        )?;
+        map.end()
+    }
+}
+/// Tag names are looked up in the namespace of the main IFD
+impl<'de> Deserialize<'de> for IfdEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Raw::deserialize(deserializer)? {
+            Raw::Map(entries) if entries.len() == 1 => {
+                let (key, raw) = &entries[0];
+                let tag = tag_from_key(key, IfdType::Ifd).map_err(D::Error::custom)?;
+                let value = value_from_raw(raw, Some(&tag), None)
+                    .map_err(|e| D::Error::custom(format!("{key}: {e}")))?;
+                Ok(IfdEntry { tag, value })
+            }
+            raw => Err(D::Error::custom(format!(
+                "expected a map with a single entry, got {raw:?}"
+            ))),
+        }
+    }
+}
+
+impl Serialize for IfdValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ValueRepr {
+            value: self,
+            tag: None,
+            implied_dtype: None,
+        }
+        .serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for IfdValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        value_from_raw(&Raw::deserialize(deserializer)?, None, None).map_err(D::Error::custom)
+    }
+}
+
+/// Paths are represented by their [std::fmt::Debug] representation (e.g. `ExifIFD.ExposureTime`)
+impl Serialize for IfdPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{self:?}"))
+    }
+}
+impl<'de> Deserialize<'de> for IfdPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+fn tag_key(tag: &MaybeKnownIfdFieldDescriptor) -> String {
+    match tag {
+        MaybeKnownIfdFieldDescriptor::Known(descriptor) => descriptor.name.to_string(),
+        MaybeKnownIfdFieldDescriptor::Unknown(tag) => format!("{tag:#06X}"),
+    }
+}
+
+fn tag_from_key(key: &str, ifd_type: IfdType) -> Result<MaybeKnownIfdFieldDescriptor, String> {
+    match key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16)
+            .map(|n| MaybeKnownIfdFieldDescriptor::from_number(n, ifd_type))
+            .map_err(|_| format!("invalid tag number '{key}'")),
+        None => MaybeKnownIfdFieldDescriptor::from_name(key, ifd_type),
+    }
+}
+
+/// The dtype that doesnt need to be given explicitly for a tag
+fn default_dtype(tag: &MaybeKnownIfdFieldDescriptor) -> Option<IfdValueType> {
+    tag.get_known_value_type()
+        .and_then(|types| types.first())
+        .copied()
+}
+
+/// The dtype of scalar values (None for lists, IFDs and offsets)
+fn scalar_dtype(value: &IfdValue) -> Option<IfdValueType> {
+    match value {
+        IfdValue::List(_) | IfdValue::Ifd(_) | IfdValue::Offsets(_) => None,
+        value => Some(value.get_ifd_value_type()),
+    }
+}
+
+/// A value together with the context needed to serialize it in the tag-aware representation
+struct ValueRepr<'a> {
+    value: &'a IfdValue,
+    tag: Option<&'a MaybeKnownIfdFieldDescriptor>,
+    /// values of this dtype are not wrapped in a dtype map
+    implied_dtype: Option<IfdValueType>,
+}
+impl<'a> ValueRepr<'a> {
+    fn in_tag(value: &'a IfdValue, tag: &'a MaybeKnownIfdFieldDescriptor) -> Self {
+        Self {
+            value,
+            tag: Some(tag),
+            implied_dtype: default_dtype(tag),
+        }
+    }
+}
+impl Serialize for ValueRepr<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let plain = |value| PlainValue {
+            value,
+            tag: self.tag,
+        };
+        match self.value {
+            IfdValue::Ifd(ifd) => ifd.serialize(serializer),
+            IfdValue::Offsets(_) => {
+                let bytes = Bytes(self.value.offsets_bytes().unwrap());
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(OFFSETS_KEY, &bytes)?;
+                map.end()
+            }
+            IfdValue::List(list) => {
+                let dtype = list.first().and_then(scalar_dtype);
+                let uniform = list.iter().all(|x| scalar_dtype(x) == dtype);
+                if let (Some(dtype), true) = (dtype, uniform) {
+                    let elements: Vec<_> = list.iter().map(plain).collect();
+                    if Some(dtype) == self.implied_dtype {
+                        elements.serialize(serializer)
+                    } else {
+                        let mut map = serializer.serialize_map(Some(1))?;
+                        map.serialize_entry(dtype.name(), &elements)?;
+                        map.end()
+                    }
+                } else {
+                    let mut seq = serializer.serialize_seq(Some(list.len()))?;
+                    for value in list {
+                        seq.serialize_element(&ValueRepr { value, ..*self })?;
+                    }
+                    seq.end()
+                }
+            }
+            value => {
+                let dtype = value.get_ifd_value_type();
+                if Some(dtype) == self.implied_dtype {
+                    plain(value).serialize(serializer)
+                } else {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    map.serialize_entry(dtype.name(), &plain(value))?;
+                    map.end()
+                }
+            }
+        }
+    }
+}
+
+/// A scalar value without its dtype
+struct PlainValue<'a> {
+    value: &'a IfdValue,
+    tag: Option<&'a MaybeKnownIfdFieldDescriptor>,
+}
+impl Serialize for PlainValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            IfdValue::Byte(_) | IfdValue::Short(_) | IfdValue::Long(_) | IfdValue::Undefined(_) => {
+                let x = self.value.as_u32().unwrap();
+                match self.tag.and_then(|tag| enum_name(tag, x)) {
+                    Some(name) => serializer.serialize_str(name),
+                    None => serializer.serialize_u32(x),
+                }
+            }
+            IfdValue::SByte(x) => serializer.serialize_i8(*x),
+            IfdValue::SShort(x) => serializer.serialize_i16(*x),
+            IfdValue::SLong(x) => serializer.serialize_i32(*x),
+            IfdValue::Ascii(x) => serializer.serialize_str(x),
+            IfdValue::Rational(x, y) => (x, y).serialize(serializer),
+            IfdValue::SRational(x, y) => (x, y).serialize(serializer),
+            IfdValue::Float(x) if x.is_finite() => serializer.serialize_f32(*x),
+            IfdValue::Float(x) => serializer.serialize_str(&non_finite_name(
+                x.is_nan() && x.to_bits() != f32::NAN.to_bits(),
+                *x as f64,
+                format!("{:#X}", x.to_bits()),
+            )),
+            IfdValue::Double(x) if x.is_finite() => serializer.serialize_f64(*x),
+            IfdValue::Double(x) => serializer.serialize_str(&non_finite_name(
+                x.is_nan() && x.to_bits() != f64::NAN.to_bits(),
+                *x,
+                format!("{:#X}", x.to_bits()),
+            )),
+            value => ValueRepr {
+                value,
+                tag: self.tag,
+                implied_dtype: None,
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+fn non_finite_name(has_payload: bool, x: f64, bits: String) -> String {
+    if has_payload {
+        bits
+    } else if x.is_nan() {
+        "NaN".to_string()
+    } else if x > 0.0 {
+        "inf".to_string()
+    } else {
+        "-inf".to_string()
+    }
+}
+
+/// Returns the name of an enumerated value if it refers back to that value unambiguously
+fn enum_name(tag: &MaybeKnownIfdFieldDescriptor, x: u32) -> Option<&'static str> {
+    let Some(IfdTypeInterpretation::Enumerated { values }) = tag.get_type_interpretation() else {
+        return None;
+    };
+    let (_, name) = values.iter().find(|(v, _)| *v == x)?;
+    let first_with_name = values.iter().find(|(_, n)| n.eq_ignore_ascii_case(name));
+    (first_with_name.map(|(v, _)| *v) == Some(x)).then_some(*name)
+}
+
+struct Bytes(Vec<u8>);
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+/// The format independent data model our representation uses. Values are first deserialized into
+/// this and then interpreted with the knowledge of their tag.
+#[derive(Debug)]
+enum Raw {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    Seq(Vec<Raw>),
+    Map(Vec<(String, Raw)>),
+}
+impl Raw {
+    fn as_i128(&self) -> Option<i128> {
+        match self {
+            Raw::Unsigned(x) => Some(*x as i128),
+            Raw::Signed(x) => Some(*x as i128),
+            _ => None,
+        }
+    }
+}
+impl<'de> Deserialize<'de> for Raw {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(RawVisitor)
+    }
+}
+struct RawVisitor;
+impl<'de> Visitor<'de> for RawVisitor {
+    type Value = Raw;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("an IFD value")
+    }
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Raw, E> {
+        Ok(Raw::Unsigned(v))
+    }
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Raw, E> {
+        Ok(Raw::Signed(v))
+    }
+    fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Raw, E> {
+        Ok(Raw::Float(v))
+    }
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Raw, E> {
+        Ok(Raw::Str(v.to_string()))
+    }
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Raw, E> {
+        Ok(Raw::Bytes(v.to_vec()))
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Raw, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Raw::Seq(elements))
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Raw, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Raw::Map(entries))
+    }
+}
+
+fn ifd_from_raw(entries: &[(String, Raw)], ifd_type: IfdType) -> Result<Ifd, String> {
+    let mut ifd = Ifd::new(ifd_type);
+    for (key, raw) in entries {
+        let tag = tag_from_key(key, ifd_type)?;
+        let value = value_from_raw(raw, Some(&tag), None).map_err(|e| format!("{key}: {e}"))?;
+        ifd.insert(tag, value);
+    }
+    Ok(ifd)
+}
+
+/// Interprets a raw value. Unless the dtype is given, the dtypes of the tag are tried in order.
+fn value_from_raw(
+    raw: &Raw,
+    tag: Option<&MaybeKnownIfdFieldDescriptor>,
+    dtype: Option<IfdValueType>,
+) -> Result<IfdValue, String> {
+    let converted;
+    let raw = match raw {
+        Raw::Map(entries) if entries.len() == 1 && entries[0].0 == OFFSETS_KEY => {
+            let bytes = match &entries[0].1 {
+                Raw::Bytes(bytes) => bytes.clone(),
+                Raw::Seq(elements) => elements
+                    .iter()
+                    .map(|x| x.as_i128().and_then(|x| u8::try_from(x).ok()))
+                    .collect::<Option<_>>()
+                    .ok_or("offsets data must be a list of bytes")?,
+                raw => return Err(format!("expected offsets data, got {raw:?}")),
+            };
+            return Ok(IfdValue::Offsets(Arc::new(bytes)));
+        }
+        Raw::Map(entries)
+            if entries.len() == 1 && IfdValueType::from_name(&entries[0].0).is_some() =>
+        {
+            let (name, raw) = &entries[0];
+            return value_from_raw(raw, tag, IfdValueType::from_name(name));
+        }
+        Raw::Map(entries) => {
+            let ifd_type = match tag.and_then(|tag| tag.get_type_interpretation()) {
+                Some(IfdTypeInterpretation::IfdOffset { ifd_type }) => *ifd_type,
+                _ => IfdType::Ifd,
+            };
+            return Ok(IfdValue::Ifd(ifd_from_raw(entries, ifd_type)?));
+        }
+        Raw::Bytes(bytes) => {
+            converted = Raw::Seq(bytes.iter().map(|b| Raw::Unsigned(*b as u64)).collect());
+            &converted
+        }
+        raw => raw,
+    };
+
+    let dtypes = match (dtype, tag.and_then(|tag| tag.get_known_value_type())) {
+        (Some(dtype), _) => vec![dtype],
+        (None, Some(dtypes)) => dtypes.to_vec(),
+        // elements can still carry their own dtype
+        (None, None) if matches!(raw, Raw::Seq(_)) => vec![],
+        (None, None) => return Err(format!("the dtype of {raw:?} must be given explicitly")),
+    };
+    let mut errors = Vec::new();
+    for &dtype in &dtypes {
+        match scalar_from_raw(raw, dtype, tag) {
+            Ok(value) => return Ok(value),
+            Err(e) => errors.push(e),
+        }
+        if let Raw::Seq(elements) = &raw {
+            match list_from_raw(elements, tag, Some(dtype)) {
+                Ok(value) => return Ok(value),
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+    if let (Raw::Seq(elements), true) = (raw, dtypes.is_empty()) {
+        return list_from_raw(elements, tag, None);
+    }
+    Err(errors.join(", "))
+}
+
+fn list_from_raw(
+    elements: &[Raw],
+    tag: Option<&MaybeKnownIfdFieldDescriptor>,
+    dtype: Option<IfdValueType>,
+) -> Result<IfdValue, String> {
+    elements
+        .iter()
+        .map(|raw| value_from_raw(raw, tag, dtype))
+        .collect::<Result<_, _>>()
+        .map(IfdValue::List)
+}
+
+fn scalar_from_raw(
+    raw: &Raw,
+    dtype: IfdValueType,
+    tag: Option<&MaybeKnownIfdFieldDescriptor>,
+) -> Result<IfdValue, String> {
+    let error = || format!("cant read {raw:?} as {}", dtype.name());
+    let int = || -> Result<i128, String> {
+        match (raw, tag.and_then(|tag| tag.get_type_interpretation())) {
+            (Raw::Str(name), Some(IfdTypeInterpretation::Enumerated { values })) => values
+                .iter()
+                .find(|(_, v)| v.eq_ignore_ascii_case(name))
+                .map(|(x, _)| *x as i128)
+                .ok_or_else(|| format!("'{name}' is not a variant of {}", tag.unwrap())),
+            _ => raw.as_i128().ok_or_else(error),
+        }
+    };
+    let pair = || -> Result<(i128, i128), String> {
+        match raw {
+            Raw::Seq(elements) if elements.len() == 2 => {
+                match (elements[0].as_i128(), elements[1].as_i128()) {
+                    (Some(x), Some(y)) => Ok((x, y)),
+                    _ => Err(error()),
+                }
+            }
+            _ => Err(error()),
+        }
+    };
+    let float = || -> Result<f64, String> {
+        match raw {
+            Raw::Float(x) => Ok(*x),
+            Raw::Str(name) => match name.as_str() {
+                "NaN" => Ok(f64::NAN),
+                "inf" => Ok(f64::INFINITY),
+                "-inf" => Ok(f64::NEG_INFINITY),
+                _ => Err(error()),
+            },
+            raw => raw.as_i128().map(|x| x as f64).ok_or_else(error),
+        }
+    };
+    // NaNs with a payload are given by their bits
+    let float_bits = || match raw {
+        Raw::Str(bits) => bits.strip_prefix("0x").map(|bits| bits.to_string()),
+        _ => None,
+    };
+    let convert = |e: std::num::TryFromIntError| format!("{}: {e}", error());
+    Ok(match dtype {
+        IfdValueType::Byte => IfdValue::Byte(int()?.try_into().map_err(convert)?),
+        IfdValueType::Short => IfdValue::Short(int()?.try_into().map_err(convert)?),
+        IfdValueType::Long => IfdValue::Long(int()?.try_into().map_err(convert)?),
+        IfdValueType::Undefined => IfdValue::Undefined(int()?.try_into().map_err(convert)?),
+        IfdValueType::SByte => IfdValue::SByte(int()?.try_into().map_err(convert)?),
+        IfdValueType::SShort => IfdValue::SShort(int()?.try_into().map_err(convert)?),
+        IfdValueType::SLong => IfdValue::SLong(int()?.try_into().map_err(convert)?),
+        IfdValueType::Ascii => match raw {
+            Raw::Str(x) => IfdValue::Ascii(x.clone()),
+            _ => return Err(error()),
+        },
+        IfdValueType::Rational => {
+            let (x, y) = pair()?;
+            IfdValue::Rational(
+                x.try_into().map_err(convert)?,
+                y.try_into().map_err(convert)?,
+            )
+        }
+        IfdValueType::SRational => {
+            let (x, y) = pair()?;
+            IfdValue::SRational(
+                x.try_into().map_err(convert)?,
+                y.try_into().map_err(convert)?,
+            )
+        }
+        IfdValueType::Float => match float_bits() {
+            Some(bits) => IfdValue::Float(f32::from_bits(
+                u32::from_str_radix(&bits, 16).map_err(|_| error())?,
+            )),
+            None => IfdValue::Float(float()? as f32),
+        },
+        IfdValueType::Double => match float_bits() {
+            Some(bits) => IfdValue::Double(f64::from_bits(
+                u64::from_str_radix(&bits, 16).map_err(|_| error())?,
+            )),
+            None => IfdValue::Double(float()?),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::strategies::{arb_ifd, IfdStrategyConfig};
+    use crate::ifd::{Ifd, IfdEntry, IfdPath, IfdValue};
+    use crate::tags::{ifd, IfdType, MaybeKnownIfdFieldDescriptor};
+    use crate::DngReader;
+    use proptest::prelude::*;
+    use std::fs::File;
+    use std::sync::Arc;
+
+    proptest! {
+        #[test]
+        fn test_json_roundtrip(
+            ifd in arb_ifd(IfdType::Ifd, IfdStrategyConfig { text_safe: false, max_depth: 2 })
+        ) {
+            let json = serde_json::to_string(&ifd).unwrap();
+            let parsed: Result<Ifd, _> = serde_json::from_str(&json);
+            prop_assert!(parsed.is_ok(), "{:?} for\n{}", parsed.err(), json);
+            prop_assert_eq!(parsed.unwrap(), ifd, "for\n{}", json);
+        }
+    }
+
+    #[test]
+    fn test_json_representation() {
+        let mut exif = Ifd::new(IfdType::Exif);
+        exif.insert(crate::tags::exif::ExposureTime, IfdValue::Rational(1, 50));
+        let mut ifd = Ifd::new(IfdType::Ifd);
+        ifd.insert(ifd::Orientation, IfdValue::Short(1));
+        ifd.insert(ifd::BaselineExposure, IfdValue::SRational(-1, 3));
+        ifd.insert(ifd::ImageWidth, IfdValue::Long(70000));
+        ifd.insert(ifd::AsShotNeutral, [0.5f32, f32::NAN].map(IfdValue::Float));
+        ifd.insert(ifd::StripOffsets, IfdValue::Offsets(Arc::new(vec![1, 2])));
+        ifd.insert(
+            MaybeKnownIfdFieldDescriptor::Unknown(0xFEED),
+            IfdValue::SShort(-2),
+        );
+        ifd.insert(ifd::ExifIFD, IfdValue::Ifd(exif));
+
+        let json = serde_json::to_string(&ifd).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Orientation":"RowTopColumnLeft","BaselineExposure":[-1,3],"ImageWidth":{"LONG":70000},"AsShotNeutral":{"FLOAT":[0.5,"NaN"]},"StripOffsets":{"OFFSETS":[1,2]},"0xFEED":{"SSHORT":-2},"ExifIFD":{"ExposureTime":[1,50]}}"#
+        );
+        assert_eq!(serde_json::from_str::<Ifd>(&json).unwrap(), ifd);
+
+        // handwritten values dont need the dtype if it can be inferred from the tag
+        let parsed: Ifd =
+            serde_json::from_str(r#"{"ImageWidth": 70000, "Orientation": 3}"#).unwrap();
+        assert_eq!(parsed.get(ifd::ImageWidth), Some(&IfdValue::Long(70000)));
+        assert_eq!(parsed.get(ifd::Orientation), Some(&IfdValue::Short(3)));
+        assert!(serde_json::from_str::<Ifd>(r#"{"0xFEED": 1}"#).is_err());
+
+        let entry = IfdEntry::new(ifd::Copyright, "someone");
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(json, r#"{"Copyright":"someone"}"#);
+        assert_eq!(serde_json::from_str::<IfdEntry>(&json).unwrap(), entry);
+
+        let value = IfdValue::List(vec![IfdValue::Byte(1), IfdValue::Short(2)]);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#"[{"BYTE":1},{"SHORT":2}]"#);
+        assert_eq!(serde_json::from_str::<IfdValue>(&json).unwrap(), value);
+
+        let dng = DngReader::read(File::open("src/testdata/test.dng").unwrap()).unwrap();
+        let json = serde_json::to_string(dng.ifds()).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Ifd>>(&json).unwrap(), dng.ifds());
+
+        let path: IfdPath = "IFD1.ExifIFD.ExposureTime".parse().unwrap();
+        let json = serde_json::to_string(&path).unwrap();
+        assert_eq!(json, r#""IFD1.ExifIFD.ExposureTime""#);
+        assert_eq!(serde_json::from_str::<IfdPath>(&json).unwrap(), path);
+    }
+}
@@ -0,0 +1,186 @@
+//! proptest strategies for generating arbitrary IFD trees
+
+use crate::ifd::{Ifd, IfdValue};
+use crate::tags::{
+    IfdCount, IfdFieldDescriptor, IfdType, IfdTypeInterpretation, IfdValueType,
+    MaybeKnownIfdFieldDescriptor,
+};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+
+/// Restricts the generated trees to what a given serialization can represent
+#[derive(Debug, Clone, Copy)]
+pub struct IfdStrategyConfig {
+    /// Generate only finite floats, enum values that have a name, simple strings and known tags
+    /// with their preferred dtype (as the text representation can only represent these unambiguously)
+    pub text_safe: bool,
+    /// How deep IFDs may be nested
+    pub max_depth: u32,
+}
+
+const ALL_VALUE_TYPES: [IfdValueType; 12] = [
+    IfdValueType::Byte,
+    IfdValueType::Ascii,
+    IfdValueType::Short,
+    IfdValueType::Long,
+    IfdValueType::Rational,
+    IfdValueType::SByte,
+    IfdValueType::Undefined,
+    IfdValueType::SShort,
+    IfdValueType::SLong,
+    IfdValueType::SRational,
+    IfdValueType::Float,
+    IfdValueType::Double,
+];
+
+pub fn arb_ifd(ifd_type: IfdType, config: IfdStrategyConfig) -> BoxedStrategy<Ifd> {
+    vec(arb_entry(ifd_type, config), 1..8)
+        .prop_map(move |entries| {
+            let mut ifd = Ifd::new(ifd_type);
+            for (tag, value) in entries {
+                ifd.insert(tag, value);
+            }
+            ifd
+        })
+        .boxed()
+}
+
+fn arb_entry(
+    ifd_type: IfdType,
+    config: IfdStrategyConfig,
+) -> BoxedStrategy<(MaybeKnownIfdFieldDescriptor, IfdValue)> {
+    let known: Vec<IfdFieldDescriptor> = ifd_type
+        .get_namespace()
+        .iter()
+        .filter(|descriptor| match descriptor.interpretation {
+            IfdTypeInterpretation::Offsets { .. } | IfdTypeInterpretation::Lengths => false,
+            IfdTypeInterpretation::IfdOffset { .. } => config.max_depth > 0,
+            // enum values without a name cant be represented as text
+            IfdTypeInterpretation::Enumerated { values } if config.text_safe => {
+                !text_safe_enum_values(descriptor.dtype[0], values).is_empty()
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect();
+    let known = select(known).prop_flat_map(move |descriptor| {
+        arb_known_value(descriptor, config).prop_map(move |value| (descriptor.as_maybe(), value))
+    });
+    let unknown = any::<u16>()
+        .prop_filter("tag must be unknown", move |tag| {
+            !ifd_type.get_namespace().iter().any(|x| x.tag == *tag)
+        })
+        .prop_flat_map(move |tag| {
+            select(&ALL_VALUE_TYPES[..])
+                .prop_flat_map(move |dtype| arb_value(dtype, IfdCount::N, config))
+                .prop_map(move |value| (MaybeKnownIfdFieldDescriptor::Unknown(tag), value))
+        });
+    prop_oneof![3 => known, 1 => unknown].boxed()
+}
+
+fn arb_known_value(
+    descriptor: IfdFieldDescriptor,
+    config: IfdStrategyConfig,
+) -> BoxedStrategy<IfdValue> {
+    let dtypes = if config.text_safe {
+        &descriptor.dtype[..1]
+    } else {
+        descriptor.dtype
+    };
+    match descriptor.interpretation {
+        IfdTypeInterpretation::IfdOffset { ifd_type } => {
+            let config = IfdStrategyConfig {
+                max_depth: config.max_depth - 1,
+                ..config
+            };
+            let single = arb_ifd(ifd_type, config).prop_map(IfdValue::Ifd);
+            match descriptor.count {
+                IfdCount::ConcreteValue(1) => single.boxed(),
+                _ => {
+                    prop_oneof![single.clone(), vec(single, 2..4).prop_map(IfdValue::List)].boxed()
+                }
+            }
+        }
+        IfdTypeInterpretation::Enumerated { values } if config.text_safe => {
+            let dtype = dtypes[0];
+            arb_list(
+                select(text_safe_enum_values(dtype, values)).boxed(),
+                descriptor.count,
+            )
+        }
+        _ => select(dtypes.to_vec())
+            .prop_flat_map(move |dtype| arb_value(dtype, descriptor.count, config))
+            .boxed(),
+    }
+}
+
+/// Enum values that have a name which survives the text representation inside of a list
+fn text_safe_enum_values(dtype: IfdValueType, values: &[(u32, &str)]) -> Vec<IfdValue> {
+    values
+        .iter()
+        .filter(|(_, name)| !name.contains([',', '[', ']', '{', '}', '#', ':']))
+        .filter_map(|(v, _)| numeric_value(dtype, *v))
+        .collect()
+}
+
+fn numeric_value(dtype: IfdValueType, v: u32) -> Option<IfdValue> {
+    match dtype {
+        IfdValueType::Byte => u8::try_from(v).ok().map(IfdValue::Byte),
+        IfdValueType::Undefined => u8::try_from(v).ok().map(IfdValue::Undefined),
+        IfdValueType::Short => u16::try_from(v).ok().map(IfdValue::Short),
+        IfdValueType::Long => Some(IfdValue::Long(v)),
+        _ => None,
+    }
+}
+
+fn arb_value(
+    dtype: IfdValueType,
+    count: IfdCount,
+    config: IfdStrategyConfig,
+) -> BoxedStrategy<IfdValue> {
+    let scalar = match dtype {
+        IfdValueType::Byte => any::<u8>().prop_map(IfdValue::Byte).boxed(),
+        IfdValueType::Ascii => {
+            let string = if config.text_safe {
+                "[a-zA-Z0-9 ]{0,16}"
+            } else {
+                "\\PC{0,16}"
+            };
+            // the count of an ASCII value is the length of the string
+            return string.prop_map(IfdValue::Ascii).boxed();
+        }
+        IfdValueType::Short => any::<u16>().prop_map(IfdValue::Short).boxed(),
+        IfdValueType::Long => any::<u32>().prop_map(IfdValue::Long).boxed(),
+        IfdValueType::Rational => any::<(u32, u32)>()
+            .prop_map(|(a, b)| IfdValue::Rational(a, b))
+            .boxed(),
+        IfdValueType::SByte => any::<i8>().prop_map(IfdValue::SByte).boxed(),
+        IfdValueType::Undefined => any::<u8>().prop_map(IfdValue::Undefined).boxed(),
+        IfdValueType::SShort => any::<i16>().prop_map(IfdValue::SShort).boxed(),
+        IfdValueType::SLong => any::<i32>().prop_map(IfdValue::SLong).boxed(),
+        IfdValueType::SRational => any::<(i32, i32)>()
+            .prop_map(|(a, b)| IfdValue::SRational(a, b))
+            .boxed(),
+        IfdValueType::Float if config.text_safe => {
+            proptest::num::f32::NORMAL.prop_map(IfdValue::Float).boxed()
+        }
+        IfdValueType::Float => any::<f32>().prop_map(IfdValue::Float).boxed(),
+        IfdValueType::Double if config.text_safe => proptest::num::f64::NORMAL
+            .prop_map(IfdValue::Double)
+            .boxed(),
+        IfdValueType::Double => any::<f64>().prop_map(IfdValue::Double).boxed(),
+    };
+    arb_list(scalar, count)
+}
+
+fn arb_list(scalar: BoxedStrategy<IfdValue>, count: IfdCount) -> BoxedStrategy<IfdValue> {
+    match count {
+        IfdCount::ConcreteValue(1) => scalar,
+        IfdCount::ConcreteValue(n) => vec(scalar, n as usize).prop_map(IfdValue::List).boxed(),
+        // lists of length one are read back as a single value, so we dont generate them
+        IfdCount::N => {
+            prop_oneof![scalar.clone(), vec(scalar, 2..6).prop_map(IfdValue::List)].boxed()
+        }
+    }
+}
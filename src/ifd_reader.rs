@@ -1,30 +1,132 @@
 use crate::byte_order_rw::ByteOrderReader;
-use crate::ifd::{Ifd, IfdValue};
+use crate::ifd::{Ifd, IfdPath, IfdValue};
 use crate::tags::{IfdType, IfdTypeInterpretation, IfdValueType, MaybeKnownIfdFieldDescriptor};
+use crate::{DngReaderError, DngReaderOptions, DngReaderWarning};
+use std::collections::HashSet;
 use std::io::{self, Read, Seek, SeekFrom};
 
+/// Keeps the state that is needed to enforce the [DngReaderOptions] across all IFDs of a file
+pub struct IfdReaderContext {
+    options: DngReaderOptions,
+    visited_offsets: HashSet<u32>,
+    pub warnings: Vec<DngReaderWarning>,
+}
+impl IfdReaderContext {
+    pub fn new(options: DngReaderOptions) -> Self {
+        Self {
+            options,
+            visited_offsets: HashSet::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn warn(&mut self, path: &IfdPath, message: String) {
+        self.warnings.push(DngReaderWarning {
+            path: path.clone(),
+            message,
+        })
+    }
+
+    /// In lenient mode this turns an error into a warning (and returns `None`), otherwise the
+    /// error is passed through
+    pub fn recover<T>(
+        &mut self,
+        path: &IfdPath,
+        result: Result<T, DngReaderError>,
+    ) -> Result<Option<T>, DngReaderError> {
+        match result {
+            Ok(v) => Ok(Some(v)),
+            Err(e) if self.options.lenient => {
+                self.warn(path, e.to_string());
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn visit_ifd(&mut self, offset: u32, depth: usize) -> Result<(), DngReaderError> {
+        if !self.visited_offsets.insert(offset) {
+            return Err(DngReaderError::FormatError(format!(
+                "the IFD at offset {offset} is referenced more than once (the IFD structure contains a cycle)"
+            )));
+        }
+        if self.visited_offsets.len() > self.options.max_ifds {
+            return Err(DngReaderError::FormatError(format!(
+                "the file contains more than {} IFDs",
+                self.options.max_ifds
+            )));
+        }
+        if depth > self.options.max_depth {
+            return Err(DngReaderError::FormatError(format!(
+                "the IFD at offset {offset} is nested deeper than {} levels",
+                self.options.max_depth
+            )));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct IfdReader {
     pub entries: Vec<IfdEntryReader>,
+    ifd_type: IfdType,
+    path: IfdPath,
+    depth: usize,
 }
 impl IfdReader {
-    pub fn read(reader: &mut ByteOrderReader<impl Read + Seek>) -> Result<Self, io::Error> {
+    /// Reads the IFD at the given offset. The reader is left at the pointer to the next IFD.
+    pub fn read(
+        reader: &mut ByteOrderReader<impl Read + Seek>,
+        offset: u32,
+        ifd_type: IfdType,
+        path: IfdPath,
+        depth: usize,
+        context: &mut IfdReaderContext,
+    ) -> Result<Self, DngReaderError> {
+        context.visit_ifd(offset, depth)?;
+        reader.seek(SeekFrom::Start(offset as u64))?;
         let count = reader.read_u16()?;
-        let entries: Result<Vec<_>, _> = (0..count)
-            .map(|_| IfdEntryReader::read(reader))
-            .filter(|x| x.is_ok())
-            .collect();
-        Ok(Self { entries: entries? })
+        if count as usize > context.options.max_entries_per_ifd {
+            return Err(DngReaderError::FormatError(format!(
+                "the IFD at offset {offset} has {count} entries (at most {} are allowed)",
+                context.options.max_entries_per_ifd
+            )));
+        }
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let entry = IfdEntryReader::read(reader).map_err(DngReaderError::from);
+            match context.recover(&path, entry)? {
+                Some(Ok(entry)) => entries.push(entry),
+                // entries of unknown type are skipped (also in strict mode), as we cant know how
+                // to interpret them
+                Some(Err((tag, message))) => context.warn(
+                    &path.chain_tag(MaybeKnownIfdFieldDescriptor::from_number(tag, ifd_type)),
+                    message,
+                ),
+                // the entry table is truncated, so we keep what we have so far
+                None => break,
+            }
+        }
+        Ok(Self {
+            entries,
+            ifd_type,
+            path,
+            depth,
+        })
     }
     pub fn process(
         &self,
-        ifd_type: IfdType,
         reader: &mut ByteOrderReader<impl Read + Seek>,
-    ) -> Result<Ifd, io::Error> {
-        let mut ifd = Ifd::new(ifd_type);
+        context: &mut IfdReaderContext,
+    ) -> Result<Ifd, DngReaderError> {
+        let mut ifd = Ifd::new(self.ifd_type);
         for entry in &self.entries {
-            let tag = MaybeKnownIfdFieldDescriptor::from_number(entry.tag, ifd_type);
-            ifd.insert(tag, entry.process(reader, tag)?);
+            let tag = MaybeKnownIfdFieldDescriptor::from_number(entry.tag, self.ifd_type);
+            let path = self.path.chain_tag(tag);
+            let value = entry.process(reader, tag, &path, self.depth, context);
+            if let Some(value) = context.recover(&path, value)? {
+                ifd.insert(tag, value);
+            }
         }
         Ok(ifd)
     }
@@ -39,42 +141,59 @@ pub struct IfdEntryReader {
     own_offset: u32,
 }
 impl IfdEntryReader {
-    pub fn read(reader: &mut ByteOrderReader<impl Read + Seek>) -> Result<Self, io::Error> {
+    /// Reads an IFD entry. Entries with an unknown type are returned as `Err((tag, message))`.
+    pub fn read(
+        reader: &mut ByteOrderReader<impl Read + Seek>,
+    ) -> Result<Result<Self, (u16, String)>, io::Error> {
         let own_offset = reader.seek(SeekFrom::Current(0))? as u32;
         let tag = reader.read_u16()?;
         let dtype = reader.read_u16()?;
-        let dtype = IfdValueType::from_u16(dtype).ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
+        let count = reader.read_u32()?;
+        let value_or_offset = reader.read_u32()?;
+        Ok(match IfdValueType::from_u16(dtype) {
+            Some(dtype) => Ok(Self {
+                tag,
+                dtype,
+                count,
+                value_or_offset,
+                own_offset,
+            }),
+            None => Err((
+                tag,
                 format!(
                     "encountered unknown value '{}' in IFD type field (tag {:#04X})",
                     dtype, tag
                 ),
-            )
-        })?;
-        let count = reader.read_u32()?;
-        let value_or_offset = reader.read_u32()?;
-        Ok(Self {
-            tag,
-            dtype,
-            count,
-            value_or_offset,
-            own_offset,
+            )),
         })
     }
 
+    fn value_bytes(&self) -> u64 {
+        self.count as u64 * self.dtype.needed_bytes() as u64
+    }
+
     // if the value fits into 4 byte, it is stored inline
     fn fits_inline(&self) -> bool {
-        self.count * self.dtype.needed_bytes() <= 4
+        self.value_bytes() <= 4
     }
 
     pub fn process(
         &self,
         reader: &mut ByteOrderReader<impl Read + Seek>,
         tag: MaybeKnownIfdFieldDescriptor,
-    ) -> Result<IfdValue, io::Error> {
+        path: &IfdPath,
+        depth: usize,
+        context: &mut IfdReaderContext,
+    ) -> Result<IfdValue, DngReaderError> {
+        if self.value_bytes() > context.options.max_value_bytes {
+            return Err(DngReaderError::FormatError(format!(
+                "the value of tag {tag} is {} bytes long (at most {} are allowed)",
+                self.value_bytes(),
+                context.options.max_value_bytes
+            )));
+        }
         if self.fits_inline() {
-            reader.seek(SeekFrom::Start((self.own_offset + 8) as u64))?;
+            reader.seek(SeekFrom::Start(self.own_offset as u64 + 8))?;
         } else {
             reader.seek(SeekFrom::Start(self.value_or_offset as u64))?;
         }
@@ -82,25 +201,37 @@ impl IfdEntryReader {
         let value = if let Some(IfdTypeInterpretation::IfdOffset { ifd_type }) =
             tag.get_type_interpretation()
         {
-            assert_eq!(self.dtype, IfdValueType::Long);
-            let mut read_ifd = || -> Result<IfdValue, io::Error> {
+            if self.dtype != IfdValueType::Long {
+                return Err(DngReaderError::FormatError(format!(
+                    "the IFD pointer in tag {tag} has type {:?} (expected Long)",
+                    self.dtype
+                )));
+            }
+            let mut read_ifd = |path: IfdPath| -> Result<IfdValue, DngReaderError> {
                 let offset = reader.read_u32()?;
                 let current = reader.seek(SeekFrom::Current(0))?;
-                reader.seek(SeekFrom::Start(offset as u64))?;
-                let unprocessed_ifd = IfdReader::read(reader)?;
-                let ifd = unprocessed_ifd.process(*ifd_type, reader)?;
+                let unprocessed_ifd =
+                    IfdReader::read(reader, offset, *ifd_type, path, depth + 1, context)?;
+                let ifd = unprocessed_ifd.process(reader, context)?;
                 reader.seek(SeekFrom::Start(current))?;
                 Ok(IfdValue::Ifd(ifd))
             };
             match self.count {
-                1 => read_ifd(),
+                0 => Err(DngReaderError::FormatError(format!(
+                    "the IFD pointer in tag {tag} has a count of 0"
+                ))),
+                1 => read_ifd(path.clone()),
                 n => {
-                    let vec: Result<Vec<_>, _> = (0..n).map(|_| read_ifd()).collect();
+                    let vec: Result<Vec<_>, _> = (0..n)
+                        .map(|i| read_ifd(path.chain_list_index(i as u16)))
+                        .collect();
                     Ok(IfdValue::List(vec?))
                 }
             }
         } else {
-            Self::read_primitive_ifd_value(self.dtype, self.count, reader)
+            Ok(Self::read_primitive_ifd_value(
+                self.dtype, self.count, reader,
+            )?)
         }?;
         Ok(value)
     }
@@ -111,7 +242,7 @@ impl IfdEntryReader {
         reader: &mut ByteOrderReader<impl Read>,
     ) -> io::Result<IfdValue> {
         let value = if let IfdValueType::Ascii = dtype {
-            let mut buf = vec![0u8; (count - 1) as usize];
+            let mut buf = vec![0u8; count.saturating_sub(1) as usize];
             reader.read_exact(&mut buf)?;
             IfdValue::Ascii(String::from_utf8_lossy(&buf).to_string())
         } else if count > 1 {
@@ -129,7 +260,7 @@ impl IfdEntryReader {
                 }
                 IfdValueType::SByte => IfdValue::SByte(reader.read_i8()?),
                 IfdValueType::Undefined => IfdValue::Undefined(reader.read_u8()?),
-                IfdValueType::SShort => IfdValue::SByte(reader.read_i8()?),
+                IfdValueType::SShort => IfdValue::SShort(reader.read_i16()?),
                 IfdValueType::SLong => IfdValue::SLong(reader.read_i32()?),
                 IfdValueType::SRational => {
                     IfdValue::SRational(reader.read_i32()?, reader.read_i32()?)
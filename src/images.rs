@@ -0,0 +1,194 @@
+use crate::ifd::{Ifd, IfdPath, IfdValue};
+use crate::tags::{ifd, IfdFieldDescriptor, IfdType};
+
+// the bits of the NewSubfileType field as defined by the TIFF and DNG specs
+const REDUCED_RESOLUTION: u32 = 1 << 0;
+const PAGE: u32 = 1 << 1;
+const MASK: u32 = 1 << 2;
+const DEPTH_MAP: u32 = 1 << 3;
+const ENHANCED: u32 = 1 << 4;
+const ALTERNATE: u32 = 1 << 16;
+
+const PHOTOMETRIC_DEPTH: u32 = 51177;
+const PHOTOMETRIC_MASK: u32 = 52527;
+
+/// The purpose of an image stored in a DNG / TIFF file (derived from `NewSubfileType` and
+/// `PhotometricInterpretation`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageRole {
+    /// The full resolution main image. For DNGs this is the raw data.
+    Main,
+    /// A reduced resolution version of the main image stored in a toplevel IFD (usually IFD0)
+    Thumbnail,
+    /// A reduced resolution version of the main image stored in a SubIFD
+    Preview,
+    /// An additional preview that is rendered differently than the main preview (e.g. for HDR displays)
+    AlternatePreview,
+    /// A processed (e.g. demosaiced or denoised) version of the main image
+    EnhancedImage,
+    TransparencyMask,
+    DepthMap,
+    SemanticMask,
+    /// A single page of a multi page TIFF
+    Page,
+}
+
+/// Describes a single image found in the IFD tree of a file (see [crate::DngReader::images])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub role: ImageRole,
+    /// Whether the `ReducedResolution` bit is set (e.g. for reduced resolution masks or depth maps)
+    pub reduced_resolution: bool,
+    /// The path of the IFD containing the image
+    pub path: IfdPath,
+    pub width: u32,
+    pub height: u32,
+    /// The value of the `Compression` tag (1 means uncompressed, 7 is JPEG)
+    pub compression: Option<u32>,
+    pub photometric_interpretation: Option<u32>,
+}
+impl ImageInfo {
+    /// Returns true if the image data is JPEG compressed (old or new style)
+    pub fn is_jpeg(&self) -> bool {
+        matches!(self.compression, Some(6) | Some(7))
+    }
+    pub fn pixel_count(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+}
+
+/// Finds every IFD with image data in the given IFD chain, including nested SubIFDs
+pub(crate) fn enumerate_images(ifds: &[Ifd]) -> Vec<ImageInfo> {
+    let mut images = Vec::new();
+    for (i, ifd) in ifds.iter().enumerate() {
+        visit_ifd(ifd, IfdPath::chain_index(i as u16), true, &mut images);
+    }
+    images
+}
+
+fn visit_ifd(ifd: &Ifd, path: IfdPath, toplevel: bool, images: &mut Vec<ImageInfo>) {
+    if let Some(image) = image_info(ifd, &path, toplevel) {
+        images.push(image);
+    }
+    for entry in ifd.entries() {
+        let path = path.chain_tag(entry.tag);
+        match &entry.value {
+            IfdValue::Ifd(sub_ifd) if sub_ifd.get_type() == IfdType::Ifd => {
+                visit_ifd(sub_ifd, path, false, images)
+            }
+            IfdValue::List(list) => {
+                for (i, value) in list.iter().enumerate() {
+                    if let IfdValue::Ifd(sub_ifd) = value {
+                        if sub_ifd.get_type() == IfdType::Ifd {
+                            visit_ifd(sub_ifd, path.chain_list_index(i as u16), false, images)
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn image_info(ifd: &Ifd, path: &IfdPath, toplevel: bool) -> Option<ImageInfo> {
+    let get = |tag: IfdFieldDescriptor| {
+        ifd.entries()
+            .iter()
+            .find(|entry| entry.tag == tag.as_maybe())
+            .and_then(|entry| entry.value.as_u32())
+    };
+    let width = get(ifd::ImageWidth)?;
+    let height = get(ifd::ImageLength)?;
+    let subfile_type = get(ifd::NewSubfileType).unwrap_or(0);
+    let photometric_interpretation = get(ifd::PhotometricInterpretation);
+
+    let role = if subfile_type & MASK != 0 {
+        if photometric_interpretation == Some(PHOTOMETRIC_MASK) || subfile_type & ALTERNATE != 0 {
+            ImageRole::SemanticMask
+        } else {
+            ImageRole::TransparencyMask
+        }
+    } else if subfile_type & DEPTH_MAP != 0 || photometric_interpretation == Some(PHOTOMETRIC_DEPTH)
+    {
+        ImageRole::DepthMap
+    } else if subfile_type & ENHANCED != 0 {
+        ImageRole::EnhancedImage
+    } else if subfile_type & REDUCED_RESOLUTION != 0 {
+        if subfile_type & ALTERNATE != 0 {
+            ImageRole::AlternatePreview
+        } else if toplevel {
+            ImageRole::Thumbnail
+        } else {
+            ImageRole::Preview
+        }
+    } else if subfile_type & PAGE != 0 {
+        ImageRole::Page
+    } else {
+        ImageRole::Main
+    };
+
+    Some(ImageInfo {
+        role,
+        reduced_resolution: subfile_type & REDUCED_RESOLUTION != 0,
+        path: path.clone(),
+        width,
+        height,
+        compression: get(ifd::Compression),
+        photometric_interpretation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::{Ifd, IfdPath, IfdValue};
+    use crate::images::{enumerate_images, ImageRole};
+    use crate::tags::{ifd, IfdType};
+
+    fn image_ifd(subfile_type: u32, width: u32, compression: u16) -> Ifd {
+        let mut ifd = Ifd::new(IfdType::Ifd);
+        ifd.insert(ifd::NewSubfileType, subfile_type);
+        ifd.insert(ifd::ImageWidth, width);
+        ifd.insert(ifd::ImageLength, width / 2);
+        ifd.insert(ifd::Compression, compression);
+        ifd
+    }
+
+    #[test]
+    fn test_enumerate_images() {
+        let mut ifd0 = image_ifd(1, 256, 1);
+        let mut mask = image_ifd(4, 4000, 8);
+        mask.insert(ifd::PhotometricInterpretation, 52527u16);
+        ifd0.insert(
+            ifd::SubIFDs,
+            IfdValue::List(vec![
+                IfdValue::Ifd(image_ifd(0, 4000, 1)),
+                IfdValue::Ifd(image_ifd(1, 1024, 7)),
+                IfdValue::Ifd(mask),
+            ]),
+        );
+        let images = enumerate_images(&[ifd0]);
+
+        let roles: Vec<_> = images.iter().map(|image| image.role).collect();
+        assert_eq!(
+            roles,
+            [
+                ImageRole::Thumbnail,
+                ImageRole::Main,
+                ImageRole::Preview,
+                ImageRole::SemanticMask
+            ]
+        );
+        let largest_jpeg_preview = images
+            .iter()
+            .filter(|image| image.role == ImageRole::Preview && image.is_jpeg())
+            .max_by_key(|image| image.pixel_count())
+            .unwrap();
+        assert_eq!(
+            largest_jpeg_preview.path,
+            IfdPath::chain_index(0)
+                .chain_tag(ifd::SubIFDs)
+                .chain_list_index(1)
+        );
+        assert_eq!(largest_jpeg_preview.height, 512);
+    }
+}
@@ -5,24 +5,52 @@
 //! was unable to find official documentation on that.
 //!
 //! To get started, see the basic examples of [DngReader] or [DngWriter] or the more advanced usage of the library in
-//! the cli tools in `src/bin/`.
+//! the cli tools in `src/bin/`. With the `async` feature enabled, `AsyncDngReader` and `write_dng_async`
+//! offer the same functionality for `futures` [AsyncRead](https://docs.rs/futures/latest/futures/io/trait.AsyncRead.html)
+//! (together with `AsyncSeek`) / `AsyncWrite` IO.
+//! The `serde` feature implements `Serialize` / `Deserialize` for [ifd::Ifd] and the types it consists of
+//! (e.g. to exchange IFDs as JSON).
+//! The `xml` feature reads / writes DCP files in the XML format of [dcpTool](https://dcptool.sourceforge.net/Usage.html).
 
+#[cfg(feature = "async")]
+mod async_dng_reader;
 mod byte_order_rw;
+mod digest;
+mod dng_editor;
 mod dng_reader;
 mod dng_writer;
 mod ifd_reader;
+mod images;
+mod md5;
+mod merge;
+mod preview;
+mod raw;
 
 /// Datastructures for representing an IFD of a read / to write DNG / DCP
 pub mod ifd;
 /// Datastructures and Data describing the interpretation of IFD / EXIF tags
 pub mod tags;
+/// Code for reading / writing DCP files in the XML format of dcpTool
+#[cfg(feature = "xml")]
+pub mod xml;
 /// Code for reading / writing a human readable text representation of IFDs
 #[cfg(feature = "yaml")]
 #[allow(unstable_name_collisions)]
 pub mod yaml;
 
-pub use dng_reader::{DngReader, DngReaderError};
-pub use dng_writer::DngWriter;
+#[cfg(feature = "async")]
+pub use async_dng_reader::AsyncDngReader;
+pub use digest::{DigestStatus, DigestVerification};
+pub use dng_editor::DngEditor;
+pub use dng_reader::{DngReader, DngReaderError, DngReaderOptions, DngReaderWarning};
+#[cfg(feature = "async")]
+pub use dng_writer::write_dng_async;
+pub use dng_writer::{DngWriter, DngWriterOptions};
+pub use images::{ImageInfo, ImageRole};
+pub use md5::Md5;
+pub use merge::{ConflictPolicy, DngMerger, MergeError, MergeSelection, TagCategory};
+pub use preview::{embed_previews, ExtractedImage, ExtractedImageFormat, PreviewOptions, RgbImage};
+pub use raw::{raw_dng_ifd, remove_image_data, CfaPattern, RawImageOptions, RawPacking};
 
 /// An enumeration over DNG / DCP files
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -0,0 +1,355 @@
+use crate::ifd::{Ifd, IfdPath, IfdPathElement, IfdValue};
+use crate::DngReader;
+use crate::DngReaderError;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Seek};
+use std::str::FromStr;
+
+/// A group of related tags that can be copied from one DNG to another with [DngMerger]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagCategory {
+    /// The color calibration of the camera (color matrices, calibration illuminants, ...)
+    Color,
+    /// The whole EXIF IFD
+    Exif,
+    /// The whole GPS IFD
+    Gps,
+    /// The lens description of DNG and EXIF
+    Lens,
+    /// The camera profile (name, hue / sat maps, look table, tone curve, ...)
+    Profile,
+}
+impl TagCategory {
+    /// The paths (relative to IFD0) of the entries that belong to this category
+    pub fn paths(&self) -> Vec<IfdPath> {
+        let names: &[&str] = match self {
+            TagCategory::Color => &[
+                "ColorMatrix1",
+                "ColorMatrix2",
+                "CameraCalibration1",
+                "CameraCalibration2",
+                "ReductionMatrix1",
+                "ReductionMatrix2",
+                "ForwardMatrix1",
+                "ForwardMatrix2",
+                "CalibrationIlluminant1",
+                "CalibrationIlluminant2",
+                "AnalogBalance",
+            ],
+            TagCategory::Exif => &["ExifIFD"],
+            TagCategory::Gps => &["GPSInfoIFD"],
+            TagCategory::Lens => &[
+                "LensInfo",
+                "ExifIFD.LensSpecification",
+                "ExifIFD.LensMake",
+                "ExifIFD.LensModel",
+                "ExifIFD.LensSerialNumber",
+            ],
+            TagCategory::Profile => &[
+                "ProfileName",
+                "ProfileCalibrationSignature",
+                "ProfileEmbedPolicy",
+                "ProfileCopyright",
+                "ProfileHueSatMapDims",
+                "ProfileHueSatMapData1",
+                "ProfileHueSatMapData2",
+                "ProfileHueSatMapEncoding",
+                "ProfileLookTableDims",
+                "ProfileLookTableData",
+                "ProfileLookTableEncoding",
+                "ProfileToneCurve",
+                "BaselineExposureOffset",
+                "DefaultBlackRender",
+            ],
+        };
+        names.iter().map(|name| name.parse().unwrap()).collect()
+    }
+}
+impl FromStr for TagCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "color" => Ok(Self::Color),
+            "exif" => Ok(Self::Exif),
+            "gps" => Ok(Self::Gps),
+            "lens" => Ok(Self::Lens),
+            "profile" => Ok(Self::Profile),
+            _ => Err(format!(
+                "unknown category '{s}', expected one of 'color', 'exif', 'gps', 'lens' or 'profile'"
+            )),
+        }
+    }
+}
+
+/// What [DngMerger] selects from the source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeSelection {
+    /// The entry (or subtree) at a path. The path starts in IFD0 of the source unless it starts with
+    /// another toplevel IFD (e.g. `IFD1.Copyright`), but always ends up in the target IFD. It is an
+    /// error if the source doesnt contain it.
+    Path(IfdPath),
+    /// All entries of a category that the source contains
+    Category(TagCategory),
+}
+
+/// What [DngMerger] does if the target already contains a different value at a merged path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Replace the value of the target with the one of the source
+    #[default]
+    Overwrite,
+    /// Keep the value of the target
+    Keep,
+    /// Fail with [MergeError::Conflict] without changing the target
+    Fail,
+}
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "overwrite" => Ok(Self::Overwrite),
+            "keep" => Ok(Self::Keep),
+            "fail" => Ok(Self::Fail),
+            _ => Err(format!(
+                "unknown conflict policy '{s}', expected one of 'overwrite', 'keep' or 'fail'"
+            )),
+        }
+    }
+}
+
+/// The error-type produced by [DngMerger]
+#[derive(Debug)]
+pub enum MergeError {
+    /// The target contains a different value at this path (with [ConflictPolicy::Fail])
+    Conflict(IfdPath),
+    /// A path that was selected explicitly doesnt exist in the source
+    NotFound(IfdPath),
+    /// Reading data (e.g. strips of a copied SubIFD) from the source failed
+    ReaderError(DngReaderError),
+    Other(String),
+}
+impl From<DngReaderError> for MergeError {
+    fn from(e: DngReaderError) -> Self {
+        Self::ReaderError(e)
+    }
+}
+impl Display for MergeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::Conflict(path) => write!(f, "conflicting values at '{path:?}'"),
+            MergeError::NotFound(path) => write!(f, "'{path:?}' doesnt exist in the source"),
+            MergeError::ReaderError(e) => write!(f, "cant read the source: {e}"),
+            MergeError::Other(e) => f.write_str(e),
+        }
+    }
+}
+impl Error for MergeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MergeError::ReaderError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Copies selected entries and subtrees from the IFDs of a [DngReader] into another IFD, e.g. to
+/// transplant a calibrated profile or the EXIF data of a reference DNG into converted ones.
+///
+/// Subtrees are merged entry by entry, so that e.g. selecting the EXIF IFD keeps the EXIF entries
+/// the target has but the source doesnt. Data the source entries point to (e.g. the strips of
+/// SubIFDs) is read into memory, so that the result can be written with [crate::DngWriter] or
+/// [crate::DngEditor].
+///
+/// example:
+/// ```rust
+/// use std::fs::File;
+/// use dng::{ConflictPolicy, DngMerger, DngReader, MergeSelection};
+///
+/// let source = DngReader::read(File::open("src/testdata/test.dng").unwrap()).unwrap();
+/// let mut target = source.get_ifd0().clone();
+/// target.insert(dng::tags::ifd::Make, "Someone");
+/// let merger = DngMerger {
+///     selections: vec![MergeSelection::Path("Make".parse().unwrap())],
+///     policy: ConflictPolicy::Keep,
+/// };
+/// assert!(merger.merge(&source, &mut target).unwrap().is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DngMerger {
+    pub selections: Vec<MergeSelection>,
+    pub policy: ConflictPolicy,
+}
+impl DngMerger {
+    /// Merges the selected entries of `source` into `target` and returns the paths (relative to
+    /// `target`) whose values were changed. If an error occurs, `target` is left unchanged.
+    pub fn merge<R: Read + Seek>(
+        &self,
+        source: &DngReader<R>,
+        target: &mut Ifd,
+    ) -> Result<Vec<IfdPath>, MergeError> {
+        let mut merged = target.clone();
+        let mut changed = Vec::new();
+        for selection in &self.selections {
+            let (paths, required) = match selection {
+                MergeSelection::Path(path) => (vec![path.clone()], true),
+                MergeSelection::Category(category) => (category.paths(), false),
+            };
+            for path in paths {
+                let index = path.get_chain_index().unwrap_or(0);
+                let source_ifd = source.ifds().get(index as usize);
+                let Some(entry) = source_ifd.and_then(|ifd| ifd.get_entry_by_path(&path)) else {
+                    match required {
+                        true => return Err(MergeError::NotFound(path)),
+                        false => continue,
+                    }
+                };
+                // resolve the data offsets point to, as they are only valid in the source file
+                let mut value_ifd = Ifd::new(source_ifd.unwrap().get_type());
+                value_ifd.insert(*entry.tag, entry.value.clone());
+                source.load_offsets(&mut value_ifd)?;
+                let value = value_ifd.remove(*entry.tag).unwrap();
+
+                let relative = match path.get_chain_index() {
+                    Some(_) => relative_path(&path.as_vec()[1..]),
+                    None => path.clone(),
+                };
+                self.merge_value(&mut merged, &relative, value, &mut changed)?;
+            }
+        }
+        *target = merged;
+        Ok(changed)
+    }
+
+    fn merge_value(
+        &self,
+        target: &mut Ifd,
+        path: &IfdPath,
+        value: IfdValue,
+        changed: &mut Vec<IfdPath>,
+    ) -> Result<(), MergeError> {
+        match (
+            target.get_entry_by_path(path).map(|entry| entry.value),
+            value,
+        ) {
+            (Some(existing), value) if existing == &value => {}
+            (Some(IfdValue::Ifd(_)), IfdValue::Ifd(source)) => {
+                for entry in source.entries {
+                    let path = path.chain_tag(entry.tag);
+                    self.merge_value(target, &path, entry.value, changed)?;
+                }
+            }
+            (Some(_), value) => match self.policy {
+                ConflictPolicy::Overwrite => {
+                    target
+                        .insert_by_path(path, value)
+                        .map_err(MergeError::Other)?;
+                    changed.push(path.clone());
+                }
+                ConflictPolicy::Keep => {}
+                ConflictPolicy::Fail => return Err(MergeError::Conflict(path.clone())),
+            },
+            (None, value) => {
+                target
+                    .insert_by_path(path, value)
+                    .map_err(MergeError::Other)?;
+                changed.push(path.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn relative_path(elements: &[IfdPathElement]) -> IfdPath {
+    elements.iter().fold(IfdPath::default(), |path, element| {
+        path.chain_path_element(element.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::{Ifd, IfdPath, IfdValue};
+    use crate::tags::{exif, ifd, IfdType};
+    use crate::{ConflictPolicy, DngMerger, DngReader, DngWriter, FileType};
+    use crate::{MergeError, MergeSelection, TagCategory};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_merge() {
+        let mut source_exif = Ifd::new(IfdType::Exif);
+        source_exif.insert(exif::ExposureTime, IfdValue::Rational(1, 50));
+        source_exif.insert(exif::LensModel, "50mm");
+        let mut preview = Ifd::new(IfdType::Ifd);
+        preview.insert(
+            ifd::StripOffsets,
+            IfdValue::Offsets(Arc::new(vec![1u8, 2, 3])),
+        );
+        preview.insert(ifd::StripByteCounts, 3u32);
+        let mut source_ifd0 = Ifd::new(IfdType::Ifd);
+        source_ifd0.insert(ifd::ColorMatrix1, [1; 9].map(|x| IfdValue::SRational(x, 1)));
+        source_ifd0.insert(ifd::ExifIFD, IfdValue::Ifd(source_exif));
+        source_ifd0.insert(ifd::SubIFDs, IfdValue::Ifd(preview));
+        let mut data = Vec::new();
+        DngWriter::write_dng(
+            Cursor::new(&mut data),
+            true,
+            FileType::Dng,
+            vec![source_ifd0],
+        )
+        .unwrap();
+        let source = DngReader::read(Cursor::new(data)).unwrap();
+
+        let mut target_exif = Ifd::new(IfdType::Exif);
+        target_exif.insert(exif::ISOSpeedRatings, 800u16);
+        target_exif.insert(exif::ExposureTime, IfdValue::Rational(1, 100));
+        let mut target = Ifd::new(IfdType::Ifd);
+        target.insert(ifd::ExifIFD, IfdValue::Ifd(target_exif));
+        target.insert(ifd::Make, "Target");
+
+        let mut merger = DngMerger {
+            selections: vec![
+                MergeSelection::Category(TagCategory::Exif),
+                MergeSelection::Category(TagCategory::Color),
+            ],
+            policy: ConflictPolicy::Fail,
+        };
+        let original = target.clone();
+        assert!(matches!(
+            merger.merge(&source, &mut target),
+            Err(MergeError::Conflict(_))
+        ));
+        assert_eq!(target, original);
+
+        merger.policy = ConflictPolicy::Keep;
+        let changed = merger.merge(&source, &mut target).unwrap();
+        let changed: Vec<_> = changed.iter().map(|path| format!("{path:?}")).collect();
+        assert_eq!(changed, ["ExifIFD.LensModel", "ColorMatrix1"]);
+        let exposure_time: IfdPath = "ExifIFD.ExposureTime".parse().unwrap();
+        assert_eq!(
+            target.get_entry_by_path(&exposure_time).unwrap().value,
+            &IfdValue::Rational(1, 100)
+        );
+
+        merger.policy = ConflictPolicy::Overwrite;
+        merger.selections = vec![MergeSelection::Path("SubIFDs".parse().unwrap())];
+        merger.merge(&source, &mut target).unwrap();
+        let strips: IfdPath = "SubIFDs.StripOffsets".parse().unwrap();
+        assert_eq!(
+            target
+                .get_entry_by_path(&strips)
+                .unwrap()
+                .value
+                .offsets_bytes(),
+            Some(vec![1, 2, 3])
+        );
+
+        merger.selections = vec![MergeSelection::Path("GPSInfoIFD".parse().unwrap())];
+        assert!(matches!(
+            merger.merge(&source, &mut target),
+            Err(MergeError::NotFound(_))
+        ));
+    }
+}
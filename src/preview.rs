@@ -0,0 +1,779 @@
+use crate::ifd::{Ifd, IfdPath, IfdValue};
+use crate::tags::{ifd, IfdFieldDescriptor, IfdType};
+use crate::{DngReader, DngReaderError, DngWriter, FileType, ImageInfo, ImageRole};
+use std::io::{Cursor, Read, Seek};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PHOTOMETRIC_CFA: u32 = 32803;
+const PHOTOMETRIC_LINEAR_RAW: u32 = 34892;
+
+/// The container format of an image extracted with [DngReader::extract_image]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractedImageFormat {
+    Jpeg,
+    Tiff,
+}
+impl ExtractedImageFormat {
+    pub fn extension(&self) -> &str {
+        match self {
+            ExtractedImageFormat::Jpeg => "jpg",
+            ExtractedImageFormat::Tiff => "tif",
+        }
+    }
+}
+
+/// A preview / thumbnail as a standalone image file
+#[derive(Debug, Clone)]
+pub struct ExtractedImage {
+    pub format: ExtractedImageFormat,
+    pub data: Vec<u8>,
+}
+
+impl<R: Read + Seek> DngReader<R> {
+    /// Returns all previews and thumbnails of the file, sorted by their size (largest first)
+    pub fn previews(&self) -> Vec<ImageInfo> {
+        let mut previews: Vec<_> = self
+            .images()
+            .into_iter()
+            .filter(|image| {
+                matches!(
+                    image.role,
+                    ImageRole::Preview | ImageRole::AlternatePreview | ImageRole::Thumbnail
+                )
+            })
+            .collect();
+        previews.sort_by_key(|image| std::cmp::Reverse(image.pixel_count()));
+        previews
+    }
+
+    /// Extracts an image (usually a preview or thumbnail) as a standalone file that can be opened
+    /// by ordinary image viewers.
+    ///
+    /// JPEG compressed images are returned as they are stored in the file. Uncompressed images are
+    /// wrapped in a minimal baseline TIFF. In both cases the `Orientation` of the image (or of the
+    /// toplevel IFD if the image has none) is preserved.
+    pub fn extract_image(&self, image: &ImageInfo) -> Result<ExtractedImage, DngReaderError> {
+        let get = |path: &IfdPath, tag: IfdFieldDescriptor| {
+            self.get_entry_by_path(&path.chain_tag(tag))
+                .and_then(|entry| entry.value.as_u32())
+        };
+        let toplevel_path = IfdPath::chain_index(image.path.get_chain_index().unwrap_or(0));
+        let orientation = get(&image.path, ifd::Orientation)
+            .or_else(|| get(&toplevel_path, ifd::Orientation))
+            .unwrap_or(1) as u16;
+
+        let mut data = vec![0u8; self.needed_buffer_length_for_image_data(&image.path)?];
+        self.read_image_data_to_buffer(&image.path, &mut data)?;
+
+        let is_raw = matches!(
+            image.photometric_interpretation,
+            Some(PHOTOMETRIC_CFA) | Some(PHOTOMETRIC_LINEAR_RAW)
+        );
+        if image.is_jpeg() && !is_raw {
+            if !data.starts_with(&[0xFF, 0xD8]) {
+                return Err(DngReaderError::FormatError(format!(
+                    "the image data of {:?} is not a JPEG stream",
+                    image.path
+                )));
+            }
+            if orientation != 1 {
+                data = insert_orientation_into_jpeg(data, orientation)?;
+            }
+            Ok(ExtractedImage {
+                format: ExtractedImageFormat::Jpeg,
+                data,
+            })
+        } else if image.compression.unwrap_or(1) == 1 {
+            if get(&image.path, ifd::PlanarConfiguration).unwrap_or(1) != 1 {
+                return Err(DngReaderError::Other(
+                    "extracting planar images is not implemented".to_string(),
+                ));
+            }
+
+            let mut tiff = Ifd::new(IfdType::Ifd);
+            tiff.insert(ifd::NewSubfileType, 0u32);
+            tiff.insert(ifd::ImageWidth, image.width);
+            tiff.insert(ifd::ImageLength, image.height);
+            tiff.insert(ifd::Compression, 1u16);
+            tiff.insert(ifd::Orientation, orientation);
+            tiff.insert(ifd::RowsPerStrip, image.height);
+            tiff.insert(ifd::StripByteCounts, data.len() as u32);
+            tiff.insert(ifd::StripOffsets, IfdValue::Offsets(Arc::new(data)));
+            for tag in [
+                ifd::BitsPerSample,
+                ifd::SamplesPerPixel,
+                ifd::PhotometricInterpretation,
+                ifd::SampleFormat,
+                ifd::ExtraSamples,
+            ] {
+                if let Some(entry) = self.get_entry_by_path(&image.path.chain_tag(tag)) {
+                    tiff.insert(tag, entry.value.clone());
+                }
+            }
+
+            let mut data = Vec::new();
+            DngWriter::write_dng(
+                Cursor::new(&mut data),
+                self.is_little_endian(),
+                FileType::Dng,
+                vec![tiff],
+            )?;
+            Ok(ExtractedImage {
+                format: ExtractedImageFormat::Tiff,
+                data,
+            })
+        } else {
+            Err(DngReaderError::Other(format!(
+                "extracting images with compression {:?} is not implemented",
+                image.compression
+            )))
+        }
+    }
+}
+
+/// An 8 bit per channel, interleaved RGB image (e.g. a rendered preview)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+impl RgbImage {
+    /// Creates an image from its interleaved RGB data, which must contain exactly 3 bytes per pixel
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Result<Self, String> {
+        let expected = width as usize * height as usize * 3;
+        if data.len() != expected {
+            return Err(format!(
+                "the data of a {width}x{height} RgbImage must be {expected} bytes long, but it is {} bytes long",
+                data.len()
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Downscales the image (with a box filter) so that its longer side is at most `max_size`
+    /// pixels long. Images that are already small enough are returned unchanged.
+    pub fn downscale(&self, max_size: u32) -> Self {
+        let longer_side = self.width.max(self.height);
+        if longer_side <= max_size {
+            return self.clone();
+        }
+        let scale = |x: u32| ((x as u64 * max_size as u64) / longer_side as u64).max(1) as u32;
+        let (width, height) = (scale(self.width), scale(self.height));
+
+        let mut data = Vec::with_capacity(width as usize * height as usize * 3);
+        for y in 0..height {
+            let (y_start, y_end) = Self::source_range(y, height, self.height);
+            for x in 0..width {
+                let (x_start, x_end) = Self::source_range(x, width, self.width);
+                let mut sum = [0u64; 3];
+                for source_y in y_start..y_end {
+                    let row = source_y as usize * self.width as usize;
+                    for source_x in x_start..x_end {
+                        let pixel = (row + source_x as usize) * 3;
+                        for (c, sum) in sum.iter_mut().enumerate() {
+                            *sum += self.data[pixel + c] as u64;
+                        }
+                    }
+                }
+                let count = ((y_end - y_start) * (x_end - x_start)) as u64;
+                data.extend(sum.iter().map(|sum| ((sum + count / 2) / count) as u8));
+            }
+        }
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+    fn source_range(target: u32, target_len: u32, source_len: u32) -> (u32, u32) {
+        let start = (target as u64 * source_len as u64 / target_len as u64) as u32;
+        let end = ((target as u64 + 1) * source_len as u64 / target_len as u64) as u32;
+        (start, end.max(start + 1))
+    }
+
+    /// Renders an uncompressed bayer raw image with a simple development path: each 2x2 CFA block
+    /// becomes one pixel, which is scaled by the black / white level, white balanced with
+    /// `AsShotNeutral` (if the IFD contains it) and gamma corrected. No color matrices are applied,
+    /// so the result is only good enough for previews.
+    ///
+    /// `image_data` contains the (possibly bit-packed) samples as stored in the file, 16 bit samples
+    /// are interpreted with the given byte order.
+    pub fn develop_raw(
+        raw_ifd: &Ifd,
+        image_data: &[u8],
+        is_little_endian: bool,
+    ) -> Result<Self, DngReaderError> {
+        let get = |tag: IfdFieldDescriptor| raw_ifd.get(tag).and_then(|value| value.as_u32());
+        let unsupported = |what: &str| DngReaderError::Other(format!("{what} are not supported"));
+        let (width, height) = match (get(ifd::ImageWidth), get(ifd::ImageLength)) {
+            (Some(width), Some(height)) if width >= 2 && height >= 2 => (width, height),
+            _ => return Err(unsupported("images without a size or smaller than 2x2")),
+        };
+        if get(ifd::Compression).unwrap_or(1) != 1 {
+            return Err(unsupported("compressed raw images"));
+        }
+        if get(ifd::PhotometricInterpretation) != Some(PHOTOMETRIC_CFA) {
+            return Err(unsupported("non CFA raw images"));
+        }
+        let bits_per_sample = get(ifd::BitsPerSample).unwrap_or(8);
+        if !(1..=16).contains(&bits_per_sample) {
+            return Err(unsupported("samples with more than 16 bits"));
+        }
+        let pattern: Vec<_> = raw_ifd
+            .get(ifd::CFAPattern)
+            .map(|value| value.as_list().filter_map(|x| x.as_u32()).collect())
+            .unwrap_or_default();
+        let repeat = raw_ifd.get(ifd::CFARepeatPatternDim).map(|value| {
+            value
+                .as_list()
+                .filter_map(|x| x.as_u32())
+                .collect::<Vec<_>>()
+        });
+        if pattern.len() != 4 || repeat.is_some_and(|repeat| repeat != [2, 2]) {
+            return Err(unsupported("CFA patterns other than 2x2"));
+        }
+        if pattern.iter().any(|&color| color > 2) {
+            return Err(unsupported("CFA colors other than red, green and blue"));
+        }
+
+        let black_level = raw_ifd
+            .get(ifd::BlackLevel)
+            .and_then(|value| value.as_list().next().and_then(|x| x.as_f64()))
+            .unwrap_or(0.0);
+        let white_level = raw_ifd
+            .get(ifd::WhiteLevel)
+            .and_then(|value| value.as_list().next().and_then(|x| x.as_f64()))
+            .unwrap_or(((1u32 << bits_per_sample) - 1) as f64);
+        let neutral: Vec<_> = raw_ifd
+            .get(ifd::AsShotNeutral)
+            .map(|value| value.as_list().filter_map(|x| x.as_f64()).collect())
+            .unwrap_or_default();
+        let gains: [f64; 3] = match neutral[..] {
+            [r, g, b] if r > 0.0 && g > 0.0 && b > 0.0 => [g / r, 1.0, g / b],
+            _ => [1.0; 3],
+        };
+
+        let row_bytes = (width as usize * bits_per_sample as usize).div_ceil(8);
+        if image_data.len() < row_bytes * height as usize {
+            return Err(DngReaderError::FormatError(
+                "the image data is shorter than expected".to_string(),
+            ));
+        }
+        let sample = |x: u32, y: u32| -> f64 {
+            let row = &image_data[y as usize * row_bytes..];
+            let value = match bits_per_sample {
+                8 => row[x as usize] as u32,
+                16 => {
+                    let bytes = [row[x as usize * 2], row[x as usize * 2 + 1]];
+                    if is_little_endian {
+                        u16::from_le_bytes(bytes) as u32
+                    } else {
+                        u16::from_be_bytes(bytes) as u32
+                    }
+                }
+                // packed samples are stored with the most significant bit first
+                bits => {
+                    let bit_offset = x as usize * bits as usize;
+                    let mut value = 0u32;
+                    for bit in bit_offset..bit_offset + bits as usize {
+                        value = (value << 1) | ((row[bit / 8] >> (7 - bit % 8)) & 1) as u32;
+                    }
+                    value
+                }
+            };
+            ((value as f64 - black_level) / (white_level - black_level)).clamp(0.0, 1.0)
+        };
+
+        let (out_width, out_height) = (width / 2, height / 2);
+        let mut data = Vec::with_capacity(out_width as usize * out_height as usize * 3);
+        for y in 0..out_height {
+            for x in 0..out_width {
+                let mut sum = [0.0; 3];
+                let mut count = [0u32; 3];
+                for (i, &color) in pattern.iter().enumerate() {
+                    let value = sample(x * 2 + i as u32 % 2, y * 2 + i as u32 / 2);
+                    sum[color as usize] += value;
+                    count[color as usize] += 1;
+                }
+                for c in 0..3 {
+                    let value = sum[c] / count[c].max(1) as f64 * gains[c];
+                    data.push((value.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0).round() as u8);
+                }
+            }
+        }
+        Ok(Self {
+            width: out_width,
+            height: out_height,
+            data,
+        })
+    }
+}
+
+impl<R: Read + Seek> DngReader<R> {
+    /// Renders a preview of the main image with [RgbImage::develop_raw]
+    pub fn render_preview(&self) -> Result<RgbImage, DngReaderError> {
+        let path = self.main_image_data_ifd_path();
+        let raw_ifd = self.get_ifd_by_path(&path).ok_or_else(|| {
+            DngReaderError::Other("the main image could not be found".to_string())
+        })?;
+        let mut data = vec![0u8; self.needed_buffer_length_for_image_data(&path)?];
+        self.read_image_data_to_buffer(&path, &mut data)?;
+        RgbImage::develop_raw(raw_ifd, &data, self.is_little_endian())
+    }
+}
+
+/// Controls how [embed_previews] generates the previews
+#[derive(Debug, Clone)]
+pub struct PreviewOptions {
+    /// The maximum size of the longer side of the thumbnail in IFD0
+    pub thumbnail_size: u32,
+    /// The maximum size of the longer side of the preview SubIFD
+    pub preview_size: u32,
+    pub application_name: String,
+    pub application_version: String,
+    /// The `PreviewDateTime` in ISO 8601 format. The current time is used if this is `None`
+    pub date_time: Option<String>,
+}
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {
+            thumbnail_size: 256,
+            preview_size: 1024,
+            application_name: env!("CARGO_PKG_NAME").to_string(),
+            application_version: env!("CARGO_PKG_VERSION").to_string(),
+            date_time: None,
+        }
+    }
+}
+
+/// The tags that describe the raw image data and thus must be moved to a SubIFD when IFD0 is
+/// turned into a thumbnail (`NewSubfileType` must stay the first one)
+pub(crate) const RAW_IFD_TAGS: &[IfdFieldDescriptor] = &[
+    ifd::NewSubfileType,
+    ifd::ImageWidth,
+    ifd::ImageLength,
+    ifd::BitsPerSample,
+    ifd::Compression,
+    ifd::PhotometricInterpretation,
+    ifd::FillOrder,
+    ifd::SamplesPerPixel,
+    ifd::RowsPerStrip,
+    ifd::StripOffsets,
+    ifd::StripByteCounts,
+    ifd::PlanarConfiguration,
+    ifd::TileWidth,
+    ifd::TileLength,
+    ifd::TileOffsets,
+    ifd::TileByteCounts,
+    ifd::SampleFormat,
+    ifd::CFARepeatPatternDim,
+    ifd::CFAPattern,
+    ifd::LinearizationTable,
+    ifd::BlackLevelRepeatDim,
+    ifd::BlackLevel,
+    ifd::WhiteLevel,
+    ifd::BlackLevelDeltaH,
+    ifd::BlackLevelDeltaV,
+    ifd::DefaultScale,
+    ifd::BestQualityScale,
+    ifd::DefaultCropOrigin,
+    ifd::DefaultCropSize,
+    ifd::BayerGreenSplit,
+    ifd::ChromaBlurRadius,
+    ifd::AntiAliasStrength,
+    ifd::ActiveArea,
+    ifd::MaskedAreas,
+    ifd::OpcodeList1,
+    ifd::OpcodeList2,
+    ifd::OpcodeList3,
+];
+
+/// Embeds a rendered image as an uncompressed thumbnail in IFD0 and as a (larger) preview in a
+/// SubIFD with `NewSubfileType = 1`.
+///
+/// If IFD0 contains the raw image data, the raw image is moved to a new SubIFD first, as the DNG
+/// spec expects IFD0 to be the thumbnail in that case.
+pub fn embed_previews(ifd0: &mut Ifd, image: &RgbImage, options: &PreviewOptions) {
+    let mut sub_ifds: Vec<IfdValue> = match ifd0.remove(ifd::SubIFDs) {
+        Some(IfdValue::List(list)) => list,
+        Some(value) => vec![value],
+        None => vec![],
+    };
+
+    let contains_main_image = ifd0.get(ifd::NewSubfileType).and_then(|x| x.as_u32()) != Some(1)
+        && (ifd0.get(ifd::StripOffsets).is_some() || ifd0.get(ifd::TileOffsets).is_some());
+    if contains_main_image {
+        let mut raw_ifd = Ifd::new(IfdType::Ifd);
+        raw_ifd.insert(ifd::NewSubfileType, 0u32);
+        for &tag in &RAW_IFD_TAGS[1..] {
+            if let Some(value) = ifd0.remove(tag) {
+                raw_ifd.insert(tag, value);
+            }
+        }
+        sub_ifds.insert(0, IfdValue::Ifd(raw_ifd));
+    }
+
+    for &tag in RAW_IFD_TAGS {
+        ifd0.remove(tag);
+    }
+    ifd0.insert_from_other(rgb_image_ifd(&image.downscale(options.thumbnail_size)));
+
+    let mut preview_ifd = rgb_image_ifd(&image.downscale(options.preview_size));
+    preview_ifd.insert(ifd::PreviewColorSpace, 2u32);
+    preview_ifd.insert(
+        ifd::PreviewDateTime,
+        options.date_time.clone().unwrap_or_else(iso8601_now),
+    );
+    preview_ifd.insert(
+        ifd::PreviewApplicationName,
+        options.application_name.as_str(),
+    );
+    preview_ifd.insert(
+        ifd::PreviewApplicationVersion,
+        options.application_version.as_str(),
+    );
+    sub_ifds.push(IfdValue::Ifd(preview_ifd));
+
+    ifd0.insert(
+        ifd::SubIFDs,
+        if sub_ifds.len() == 1 {
+            sub_ifds.remove(0)
+        } else {
+            IfdValue::List(sub_ifds)
+        },
+    );
+}
+
+fn rgb_image_ifd(image: &RgbImage) -> Ifd {
+    let mut ifd = Ifd::new(IfdType::Ifd);
+    ifd.insert(ifd::NewSubfileType, 1u32);
+    ifd.insert(ifd::ImageWidth, image.width);
+    ifd.insert(ifd::ImageLength, image.height);
+    ifd.insert(ifd::BitsPerSample, [8u16, 8, 8]);
+    ifd.insert(ifd::Compression, 1u16);
+    ifd.insert(ifd::PhotometricInterpretation, 2u16);
+    ifd.insert(ifd::SamplesPerPixel, 3u16);
+    ifd.insert(ifd::PlanarConfiguration, 1u16);
+    ifd.insert(ifd::RowsPerStrip, image.height);
+    ifd.insert(ifd::StripByteCounts, image.data.len() as u32);
+    ifd.insert(
+        ifd::StripOffsets,
+        IfdValue::Offsets(Arc::new(image.data.clone())),
+    );
+    ifd
+}
+
+/// Formats the current time as ISO 8601 in UTC (e.g. `2023-01-31T12:00:00Z`)
+fn iso8601_now() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // converts days since 1970-01-01 to a civil date (see http://howardhinnant.github.io/date_algorithms.html)
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Sets the `Orientation` tag in the EXIF APP1 segment of a JPEG stream. An existing EXIF segment
+/// is rewritten (keeping all its other entries), otherwise a new one containing only the
+/// `Orientation` tag is inserted.
+fn insert_orientation_into_jpeg(
+    jpeg: Vec<u8>,
+    orientation: u16,
+) -> Result<Vec<u8>, DngReaderError> {
+    const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+    // look for an existing EXIF segment in the markers before the image data
+    let mut position = 2;
+    let mut insert_position = 2;
+    let mut existing = None;
+    while position + 4 <= jpeg.len() && jpeg[position] == 0xFF {
+        let marker = jpeg[position + 1];
+        // the segments end with the start of scan marker
+        if marker == 0xDA {
+            break;
+        }
+        let length = u16::from_be_bytes([jpeg[position + 2], jpeg[position + 3]]) as usize;
+        let end = (position + 2 + length).min(jpeg.len());
+        let payload = &jpeg[(position + 4).min(end)..end];
+        if marker == 0xE1 && payload.starts_with(EXIF_HEADER) {
+            existing = Some((position, end));
+            break;
+        }
+        // a new segment goes directly behind a JFIF APP0 segment
+        if marker == 0xE0 && position == 2 {
+            insert_position = end;
+        }
+        position = end;
+    }
+
+    let (tiff, (start, end)) = match existing {
+        Some((start, end)) => {
+            let exif = DngReader::read(Cursor::new(&jpeg[start + 4 + EXIF_HEADER.len()..end]))?;
+            let mut ifds = exif.ifds().to_vec();
+            for ifd in &mut ifds {
+                exif.load_offsets(ifd)?;
+            }
+            ifds[0].insert(ifd::Orientation, orientation);
+            let mut tiff = Vec::new();
+            DngWriter::write_dng(
+                Cursor::new(&mut tiff),
+                exif.is_little_endian(),
+                FileType::Dng,
+                ifds,
+            )?;
+            (tiff, (start, end))
+        }
+        None => {
+            let mut exif_ifd = Ifd::new(IfdType::Ifd);
+            exif_ifd.insert(ifd::Orientation, orientation);
+            let mut tiff = Vec::new();
+            DngWriter::write_dng(Cursor::new(&mut tiff), true, FileType::Dng, vec![exif_ifd])?;
+            let position = insert_position.min(jpeg.len());
+            (tiff, (position, position))
+        }
+    };
+
+    let length = u16::try_from(2 + EXIF_HEADER.len() + tiff.len()).map_err(|_| {
+        DngReaderError::Other("the EXIF data of the JPEG preview is too large".to_string())
+    })?;
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&length.to_be_bytes());
+    segment.extend_from_slice(EXIF_HEADER);
+    segment.extend_from_slice(&tiff);
+    Ok([&jpeg[..start], &segment, &jpeg[end..]].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::{Ifd, IfdValue};
+    use crate::preview::{
+        embed_previews, insert_orientation_into_jpeg, ExtractedImageFormat, PreviewOptions,
+        RgbImage,
+    };
+    use crate::tags::{ifd, IfdType};
+    use crate::{DngReader, DngWriter, FileType, ImageRole};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_extract_previews() {
+        let jpeg = vec![0xFF, 0xD8, 0xFF, 0xD9];
+        let mut jpeg_preview = Ifd::new(IfdType::Ifd);
+        jpeg_preview.insert(ifd::NewSubfileType, 1u32);
+        jpeg_preview.insert(ifd::ImageWidth, 64u32);
+        jpeg_preview.insert(ifd::ImageLength, 32u32);
+        jpeg_preview.insert(ifd::Compression, 7u16);
+        jpeg_preview.insert(ifd::PhotometricInterpretation, 6u16);
+        jpeg_preview.insert(ifd::StripOffsets, IfdValue::Offsets(Arc::new(jpeg.clone())));
+        jpeg_preview.insert(ifd::StripByteCounts, jpeg.len() as u32);
+
+        let rgb = vec![0x80u8; 4 * 2 * 3];
+        let mut thumbnail = Ifd::new(IfdType::Ifd);
+        thumbnail.insert(ifd::NewSubfileType, 1u32);
+        thumbnail.insert(ifd::ImageWidth, 4u32);
+        thumbnail.insert(ifd::ImageLength, 2u32);
+        thumbnail.insert(ifd::Compression, 1u16);
+        thumbnail.insert(ifd::PhotometricInterpretation, 2u16);
+        thumbnail.insert(ifd::BitsPerSample, [8u16, 8, 8]);
+        thumbnail.insert(ifd::SamplesPerPixel, 3u16);
+        thumbnail.insert(ifd::Orientation, 6u16);
+        thumbnail.insert(ifd::StripOffsets, IfdValue::Offsets(Arc::new(rgb.clone())));
+        thumbnail.insert(ifd::StripByteCounts, rgb.len() as u32);
+        thumbnail.insert(ifd::SubIFDs, IfdValue::Ifd(jpeg_preview));
+
+        let mut data = Vec::new();
+        DngWriter::write_dng(Cursor::new(&mut data), true, FileType::Dng, vec![thumbnail]).unwrap();
+        let dng = DngReader::read(Cursor::new(data)).unwrap();
+
+        let previews = dng.previews();
+        assert_eq!(previews.len(), 2);
+        assert_eq!(previews[0].role, ImageRole::Preview);
+        assert_eq!(previews[1].role, ImageRole::Thumbnail);
+
+        // the JPEG inherits the orientation of the toplevel IFD
+        let extracted = dng.extract_image(&previews[0]).unwrap();
+        assert_eq!(extracted.format, ExtractedImageFormat::Jpeg);
+        assert_eq!(&extracted.data[..4], &[0xFF, 0xD8, 0xFF, 0xE1]);
+        assert_eq!(&extracted.data[6..12], b"Exif\0\0");
+        assert!(extracted.data.ends_with(&jpeg[2..]));
+        let exif = DngReader::read(Cursor::new(extracted.data[12..].to_vec())).unwrap();
+        assert_eq!(exif.get_ifd0().entries()[0].value, IfdValue::Short(6));
+
+        let extracted = dng.extract_image(&previews[1]).unwrap();
+        assert_eq!(extracted.format, ExtractedImageFormat::Tiff);
+        let tiff = DngReader::read(Cursor::new(extracted.data)).unwrap();
+        let path = tiff.main_image_data_ifd_path();
+        let mut buffer = vec![0u8; tiff.needed_buffer_length_for_image_data(&path).unwrap()];
+        tiff.read_image_data_to_buffer(&path, &mut buffer).unwrap();
+        assert_eq!(buffer, rgb);
+    }
+
+    #[test]
+    fn test_orientation_replaces_existing_exif() {
+        let mut exif_ifd = Ifd::new(IfdType::Ifd);
+        exif_ifd.insert(ifd::Orientation, 1u16);
+        exif_ifd.insert(ifd::Copyright, "someone");
+        let mut tiff = Vec::new();
+        DngWriter::write_dng(Cursor::new(&mut tiff), false, FileType::Dng, vec![exif_ifd]).unwrap();
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend_from_slice(&(2 + 6 + tiff.len() as u16).to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xFF, 0xD9]);
+
+        let patched = insert_orientation_into_jpeg(jpeg, 6).unwrap();
+        let app1_count = patched.windows(2).filter(|w| w == &[0xFF, 0xE1]).count();
+        assert_eq!(app1_count, 1);
+        assert!(patched.ends_with(&[0xFF, 0xD9]));
+        let exif = DngReader::read(Cursor::new(patched[12..].to_vec())).unwrap();
+        assert!(!exif.is_little_endian());
+        assert_eq!(
+            exif.get_ifd0().get(ifd::Orientation),
+            Some(&IfdValue::Short(6))
+        );
+        assert_eq!(
+            exif.get_ifd0().get(ifd::Copyright),
+            Some(&IfdValue::from("someone"))
+        );
+    }
+
+    #[test]
+    fn test_embed_previews() {
+        // a 4x4 RGGB bayer image with 16 bit samples
+        let raw: Vec<u8> = (0..16u16).flat_map(|i| (i * 1000).to_le_bytes()).collect();
+        let mut ifd0 = Ifd::new(IfdType::Ifd);
+        ifd0.insert(ifd::ImageWidth, 4u32);
+        ifd0.insert(ifd::ImageLength, 4u32);
+        ifd0.insert(ifd::BitsPerSample, 16u16);
+        ifd0.insert(ifd::Compression, 1u16);
+        ifd0.insert(ifd::PhotometricInterpretation, 32803u16);
+        ifd0.insert(ifd::SamplesPerPixel, 1u16);
+        ifd0.insert(ifd::CFARepeatPatternDim, [2u16, 2]);
+        ifd0.insert(ifd::CFAPattern, [0u8, 1, 1, 2]);
+        ifd0.insert(ifd::Make, "Test");
+        ifd0.insert(ifd::RowsPerStrip, 4u32);
+        ifd0.insert(ifd::ActiveArea, [0u32, 0, 4, 4]);
+        ifd0.insert(ifd::MaskedAreas, [0u32, 0, 1, 4]);
+        ifd0.insert(
+            ifd::BlackLevelDeltaH,
+            [0i32; 4].map(|x| IfdValue::SRational(x, 1)),
+        );
+        ifd0.insert(
+            ifd::BlackLevelDeltaV,
+            [0i32; 4].map(|x| IfdValue::SRational(x, 1)),
+        );
+        ifd0.insert(
+            ifd::DefaultScale,
+            [1u32; 2].map(|x| IfdValue::Rational(x, 1)),
+        );
+        ifd0.insert(ifd::BestQualityScale, IfdValue::Rational(1, 1));
+        ifd0.insert(ifd::ChromaBlurRadius, IfdValue::Rational(0, 1));
+        ifd0.insert(ifd::AntiAliasStrength, IfdValue::Rational(1, 1));
+        for opcode_list in [ifd::OpcodeList1, ifd::OpcodeList2, ifd::OpcodeList3] {
+            ifd0.insert(opcode_list, IfdValue::List(vec![IfdValue::Undefined(0); 4]));
+        }
+        ifd0.insert(ifd::StripOffsets, IfdValue::Offsets(Arc::new(raw.clone())));
+        ifd0.insert(ifd::StripByteCounts, raw.len() as u32);
+
+        let mut data = Vec::new();
+        DngWriter::write_dng(
+            Cursor::new(&mut data),
+            true,
+            FileType::Dng,
+            vec![ifd0.clone()],
+        )
+        .unwrap();
+        let image = DngReader::read(Cursor::new(data))
+            .unwrap()
+            .render_preview()
+            .unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.downscale(1).data.len(), 3);
+        assert!(RgbImage::new(2, 2, vec![0; 11]).is_err());
+        assert_eq!(RgbImage::new(2, 2, image.data.clone()), Ok(image.clone()));
+
+        let options = PreviewOptions {
+            date_time: Some("2023-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        embed_previews(&mut ifd0, &image, &options);
+        let mut data = Vec::new();
+        DngWriter::write_dng(Cursor::new(&mut data), true, FileType::Dng, vec![ifd0]).unwrap();
+        let dng = DngReader::read(Cursor::new(data)).unwrap();
+
+        let roles: Vec<_> = dng.images().iter().map(|image| image.role).collect();
+        assert_eq!(
+            roles,
+            [ImageRole::Thumbnail, ImageRole::Main, ImageRole::Preview]
+        );
+        let ifd0 = dng.get_ifd0();
+        assert_eq!(
+            ifd0.get(ifd::Make),
+            Some(&IfdValue::Ascii("Test".to_string()))
+        );
+        assert_eq!(ifd0.get(ifd::CFAPattern), None);
+        // everything that describes the raw image moved with it
+        let raw_path = dng.main_image_data_ifd_path();
+        let raw_ifd = dng.get_ifd_by_path(&raw_path).unwrap();
+        for tag in [
+            ifd::ActiveArea,
+            ifd::MaskedAreas,
+            ifd::BlackLevelDeltaH,
+            ifd::BlackLevelDeltaV,
+            ifd::DefaultScale,
+            ifd::BestQualityScale,
+            ifd::ChromaBlurRadius,
+            ifd::AntiAliasStrength,
+            ifd::OpcodeList1,
+            ifd::OpcodeList2,
+            ifd::OpcodeList3,
+        ] {
+            assert_eq!(ifd0.get(tag), None, "{} stayed in IFD0", tag.name);
+            assert!(
+                raw_ifd.get(tag).is_some(),
+                "{} is missing in the raw IFD",
+                tag.name
+            );
+        }
+
+        // the raw data survives the move to the SubIFD
+        let path = dng.main_image_data_ifd_path();
+        let mut buffer = vec![0u8; dng.needed_buffer_length_for_image_data(&path).unwrap()];
+        dng.read_image_data_to_buffer(&path, &mut buffer).unwrap();
+        assert_eq!(buffer, raw);
+
+        let preview_path = &dng.images()[2].path;
+        let preview = dng.get_ifd_by_path(preview_path).unwrap();
+        assert_eq!(
+            preview.get(ifd::PreviewColorSpace),
+            Some(&IfdValue::Long(2))
+        );
+        assert_eq!(
+            preview.get(ifd::PreviewDateTime),
+            Some(&IfdValue::Ascii("2023-01-01T00:00:00Z".to_string()))
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use crate::ifd::{Ifd, IfdValue};
+use crate::preview::RAW_IFD_TAGS;
+use crate::tags::{ifd, IfdTypeInterpretation};
+use std::str::FromStr;
+use std::sync::Arc;
+
+const PHOTOMETRIC_CFA: u16 = 32803;
+const ILLUMINANT_D65: u16 = 21;
+
+/// How the samples of a raw sensor dump are laid out in memory. Rows always start at a byte
+/// boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawPacking {
+    /// Every sample is stored in its own byte (up to 8 bits) or 16 bit word
+    Unpacked,
+    /// The samples are packed without padding with the most significant bit first (like DNG does
+    /// it)
+    Msb,
+    /// The samples are packed without padding with the least significant bit first
+    Lsb,
+}
+impl FromStr for RawPacking {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "unpacked" => Ok(Self::Unpacked),
+            "msb" => Ok(Self::Msb),
+            "lsb" => Ok(Self::Lsb),
+            _ => Err(format!(
+                "unknown packing '{s}', expected one of 'unpacked', 'msb' or 'lsb'"
+            )),
+        }
+    }
+}
+
+/// A 2x2 color filter array pattern, e.g. `RGGB` (the colors are listed row by row)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CfaPattern(pub [u8; 4]);
+impl FromStr for CfaPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colors: Option<Vec<u8>> = s
+            .chars()
+            .map(|c| match c.to_ascii_uppercase() {
+                'R' => Some(0),
+                'G' => Some(1),
+                'B' => Some(2),
+                _ => None,
+            })
+            .collect();
+        match colors.as_deref() {
+            Some(&[a, b, c, d]) => Ok(Self([a, b, c, d])),
+            _ => Err(format!(
+                "invalid CFA pattern '{s}', expected four of 'R', 'G' and 'B' (e.g. 'RGGB')"
+            )),
+        }
+    }
+}
+
+/// Describes the layout of a raw sensor dump for [raw_dng_ifd]
+#[derive(Debug, Clone)]
+pub struct RawImageOptions {
+    pub width: u32,
+    pub height: u32,
+    /// The bit depth of the samples (1 to 16)
+    pub bits_per_sample: u32,
+    pub packing: RawPacking,
+    /// The byte order of unpacked samples with more than 8 bits
+    pub little_endian: bool,
+    pub cfa_pattern: CfaPattern,
+}
+impl RawImageOptions {
+    fn row_bytes(&self) -> usize {
+        match self.packing {
+            RawPacking::Unpacked => self.width as usize * self.bits_per_sample.div_ceil(8) as usize,
+            RawPacking::Msb | RawPacking::Lsb => {
+                (self.width as usize * self.bits_per_sample as usize).div_ceil(8)
+            }
+        }
+    }
+
+    fn sample(&self, row: &[u8], x: usize) -> u32 {
+        let bits = self.bits_per_sample as usize;
+        match self.packing {
+            RawPacking::Unpacked if bits <= 8 => row[x] as u32,
+            RawPacking::Unpacked => {
+                let bytes = [row[x * 2], row[x * 2 + 1]];
+                match self.little_endian {
+                    true => u16::from_le_bytes(bytes) as u32,
+                    false => u16::from_be_bytes(bytes) as u32,
+                }
+            }
+            RawPacking::Msb => (x * bits..(x + 1) * bits).fold(0, |value, bit| {
+                (value << 1) | ((row[bit / 8] >> (7 - bit % 8)) & 1) as u32
+            }),
+            RawPacking::Lsb => (x * bits..(x + 1) * bits).rev().fold(0, |value, bit| {
+                (value << 1) | ((row[bit / 8] >> (bit % 8)) & 1) as u32
+            }),
+        }
+    }
+}
+
+/// Builds the IFD0 of a DNG from a raw sensor dump.
+///
+/// The entries describing the image data (size, bit depth, strips, CFA layout) are always derived
+/// from `options`. The samples are repacked the way DNG stores them (16 bit samples in the byte
+/// order of the file given by `is_little_endian`, all others packed with the most significant bit
+/// first). The entries of `metadata` (e.g. from an existing DNG with [remove_image_data] applied)
+/// are added on top of defaults that make the file a valid DNG: `DNGVersion`, `UniqueCameraModel`,
+/// an identity `ColorMatrix1`, `AsShotNeutral`, `BlackLevel` and `WhiteLevel`.
+pub fn raw_dng_ifd(
+    data: &[u8],
+    options: &RawImageOptions,
+    metadata: Ifd,
+    is_little_endian: bool,
+) -> Result<Ifd, String> {
+    let (width, height, bits) = (options.width, options.height, options.bits_per_sample);
+    if !(1..=16).contains(&bits) {
+        return Err(format!("a bit depth of {bits} is not supported (1 to 16)"));
+    }
+    let row_bytes = options.row_bytes();
+    if data.len() < row_bytes * height as usize {
+        return Err(format!(
+            "the raw data is {} bytes long, but a {width}x{height} image needs {} bytes",
+            data.len(),
+            row_bytes * height as usize,
+        ));
+    }
+
+    let out_row_bytes = (width as usize * bits as usize).div_ceil(8);
+    let mut image_data = vec![0u8; out_row_bytes * height as usize];
+    for (y, out_row) in image_data.chunks_exact_mut(out_row_bytes).enumerate() {
+        let row = &data[y * row_bytes..];
+        for x in 0..width as usize {
+            let value = options.sample(row, x);
+            if value >> bits != 0 {
+                return Err(format!(
+                    "the sample at ({x}, {y}) is {value}, which doesnt fit into {bits} bits"
+                ));
+            }
+            if bits == 16 {
+                let bytes = match is_little_endian {
+                    true => (value as u16).to_le_bytes(),
+                    false => (value as u16).to_be_bytes(),
+                };
+                out_row[x * 2..x * 2 + 2].copy_from_slice(&bytes);
+            } else {
+                for i in 0..bits as usize {
+                    let bit = x * bits as usize + i;
+                    out_row[bit / 8] |=
+                        (((value >> (bits as usize - 1 - i)) & 1) as u8) << (7 - bit % 8);
+                }
+            }
+        }
+    }
+
+    let identity = [1, 0, 0, 0, 1, 0, 0, 0, 1].map(|x| IfdValue::SRational(x, 1));
+    let mut ifd = Ifd::new(metadata.get_type());
+    ifd.insert(ifd::Orientation, 1u16);
+    ifd.insert(ifd::DNGVersion, [1u8, 4, 0, 0]);
+    ifd.insert(ifd::DNGBackwardVersion, [1u8, 1, 0, 0]);
+    ifd.insert(ifd::UniqueCameraModel, "Unknown");
+    ifd.insert(ifd::CFAPlaneColor, [0u8, 1, 2]);
+    ifd.insert(ifd::CFALayout, 1u16);
+    ifd.insert(ifd::BlackLevel, 0u32);
+    ifd.insert(ifd::WhiteLevel, (1u32 << bits) - 1);
+    ifd.insert(ifd::ColorMatrix1, identity);
+    ifd.insert(ifd::CalibrationIlluminant1, ILLUMINANT_D65);
+    ifd.insert(
+        ifd::AsShotNeutral,
+        [1, 1, 1].map(|x| IfdValue::Rational(x, 1)),
+    );
+    ifd.insert_from_other(metadata);
+
+    ifd.insert(ifd::NewSubfileType, 0u32);
+    ifd.insert(ifd::ImageWidth, width);
+    ifd.insert(ifd::ImageLength, height);
+    ifd.insert(ifd::BitsPerSample, bits as u16);
+    ifd.insert(ifd::Compression, 1u16);
+    ifd.insert(ifd::PhotometricInterpretation, PHOTOMETRIC_CFA);
+    ifd.insert(ifd::SamplesPerPixel, 1u16);
+    ifd.insert(ifd::RowsPerStrip, height);
+    ifd.insert(ifd::StripByteCounts, image_data.len() as u32);
+    ifd.insert(ifd::StripOffsets, IfdValue::Offsets(Arc::new(image_data)));
+    ifd.insert(ifd::PlanarConfiguration, 1u16);
+    ifd.insert(ifd::CFARepeatPatternDim, [2u16, 2]);
+    ifd.insert(ifd::CFAPattern, options.cfa_pattern.0);
+
+    Ok(ifd)
+}
+
+/// Removes everything that describes or points to image data (the raw image, previews and
+/// thumbnails) from an IFD, so that only the metadata remains. This also removes the digests of the
+/// raw image, as they wouldnt match new image data.
+pub fn remove_image_data(ifd: &mut Ifd) {
+    for &tag in RAW_IFD_TAGS {
+        ifd.remove(tag);
+    }
+    for tag in [ifd::SubIFDs, ifd::RawImageDigest, ifd::NewRawImageDigest] {
+        ifd.remove(tag);
+    }
+    ifd.entries
+        .retain(|entry| match entry.tag.get_type_interpretation() {
+            Some(IfdTypeInterpretation::Offsets { .. } | IfdTypeInterpretation::Lengths) => false,
+            _ => !matches!(entry.value, IfdValue::Offsets(_)),
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::{Ifd, IfdValue};
+    use crate::tags::{ifd, IfdType};
+    use crate::{raw_dng_ifd, remove_image_data, CfaPattern, RawImageOptions, RawPacking};
+
+    #[test]
+    fn test_raw_dng_ifd() {
+        let mut options = RawImageOptions {
+            width: 2,
+            height: 2,
+            bits_per_sample: 12,
+            packing: RawPacking::Lsb,
+            little_endian: true,
+            cfa_pattern: "grbg".parse().unwrap(),
+        };
+        assert_eq!(options.cfa_pattern, CfaPattern([1, 0, 2, 1]));
+        // the samples 0x123, 0x456 / 0x789, 0xABC packed lsb first
+        let lsb = [0x23, 0x61, 0x45, 0x89, 0xC7, 0xAB];
+        let mut metadata = Ifd::new(IfdType::Ifd);
+        metadata.insert(ifd::UniqueCameraModel, "Sensor");
+        metadata.insert(ifd::WhiteLevel, 4000u32);
+        let ifd0 = raw_dng_ifd(&lsb, &options, metadata, true).unwrap();
+
+        let msb = vec![0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC];
+        assert_eq!(
+            ifd0.get(ifd::StripOffsets).unwrap().offsets_bytes(),
+            Some(msb.clone())
+        );
+        assert_eq!(ifd0.get(ifd::StripByteCounts), Some(&IfdValue::Long(6)));
+        assert_eq!(ifd0.get(ifd::BitsPerSample), Some(&IfdValue::Short(12)));
+        assert_eq!(ifd0.get(ifd::WhiteLevel), Some(&IfdValue::Long(4000)));
+        assert_eq!(
+            ifd0.get(ifd::UniqueCameraModel),
+            Some(&IfdValue::Ascii("Sensor".to_string()))
+        );
+
+        options.packing = RawPacking::Msb;
+        let repacked = raw_dng_ifd(&msb, &options, Ifd::new(IfdType::Ifd), true).unwrap();
+        assert_eq!(repacked.get(ifd::StripOffsets), ifd0.get(ifd::StripOffsets));
+
+        options.packing = RawPacking::Unpacked;
+        options.bits_per_sample = 16;
+        options.little_endian = false;
+        let unpacked = raw_dng_ifd(
+            &[0, 1, 0, 2, 0, 3, 0, 4],
+            &options,
+            Ifd::new(IfdType::Ifd),
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            unpacked.get(ifd::StripOffsets).unwrap().offsets_bytes(),
+            Some(vec![1, 0, 2, 0, 3, 0, 4, 0])
+        );
+        assert!(raw_dng_ifd(&[0; 7], &options, Ifd::new(IfdType::Ifd), true).is_err());
+
+        let mut with_image = ifd0.clone();
+        with_image.insert(ifd::SubIFDs, IfdValue::Ifd(Ifd::new(IfdType::Ifd)));
+        remove_image_data(&mut with_image);
+        assert_eq!(with_image.get(ifd::StripOffsets), None);
+        assert_eq!(with_image.get(ifd::SubIFDs), None);
+        assert_eq!(with_image.get(ifd::CFAPattern), None);
+        assert!(with_image.get(ifd::UniqueCameraModel).is_some());
+    }
+}
@@ -0,0 +1,67 @@
+use crate::tags::{IfdFieldDescriptor, MaybeKnownIfdFieldDescriptor};
+
+/// The names exiftool uses for tags whose name differs from the one in our tag tables. The tag
+/// numbers of the GPS namespace dont overlap with these, so they are unambiguous.
+const EXIFTOOL_NAMES: [(u16, &str); 24] = [
+    (0x00FE, "SubfileType"),
+    (0x00FF, "OldSubfileType"),
+    (0x0101, "ImageHeight"),
+    (0x0132, "ModifyDate"),
+    (0x014A, "SubIFD"),
+    (0x0201, "ThumbnailOffset"),
+    (0x0202, "ThumbnailLength"),
+    (0x02BC, "ApplicationNotes"),
+    (0x828E, "CFAPattern2"),
+    (0x83BB, "IPTC-NAA"),
+    (0x8769, "ExifOffset"),
+    (0x8773, "ICC_Profile"),
+    (0x8825, "GPSInfo"),
+    (0x8827, "ISO"),
+    (0x8828, "Opto-ElectricConvFactor"),
+    (0x9004, "CreateDate"),
+    (0x9204, "ExposureCompensation"),
+    (0x9216, "TIFF-EPStandardID"),
+    (0xA000, "FlashpixVersion"),
+    (0xA002, "ExifImageWidth"),
+    (0xA003, "ExifImageHeight"),
+    (0xA005, "InteropOffset"),
+    (0xA405, "FocalLengthIn35mmFormat"),
+    (0xC630, "DNGLensInfo"),
+];
+
+/// The (family 1) groups exiftool reports the entries of the IFDs these tags point to in
+const EXIFTOOL_GROUPS: [(u16, &str); 4] = [
+    (0x014A, "SubIFD"),
+    (0x8769, "ExifIFD"),
+    (0x8825, "GPS"),
+    (0xA005, "InteropIFD"),
+];
+
+impl IfdFieldDescriptor {
+    /// The name exiftool uses for this tag (e.g. `ExifOffset` for `ExifIFD`)
+    pub fn exiftool_name(&self) -> &'static str {
+        EXIFTOOL_NAMES
+            .iter()
+            .find(|(tag, _)| *tag == self.tag)
+            .map_or(self.name, |(_, name)| name)
+    }
+
+    /// The group exiftool reports the entries of the IFD this tag points to in (e.g. `ExifIFD`).
+    /// Falls back to the name of the tag for tags that dont point to an IFD.
+    pub fn exiftool_group(&self) -> &'static str {
+        EXIFTOOL_GROUPS
+            .iter()
+            .find(|(tag, _)| *tag == self.tag)
+            .map_or(self.name, |(_, group)| group)
+    }
+}
+
+impl MaybeKnownIfdFieldDescriptor {
+    /// The name exiftool uses for this tag. Unknown tags are named like `exiftool -u` does it.
+    pub fn exiftool_name(&self) -> String {
+        match self {
+            Self::Known(descriptor) => descriptor.exiftool_name().to_string(),
+            Self::Unknown(tag) => format!("Exif_0x{tag:04x}"),
+        }
+    }
+}
@@ -1,10 +1,14 @@
 use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+
+mod exiftool;
 
 include!(concat!(env!("OUT_DIR"), "/ifd_data.rs"));
 
 /// An enum indicating the context (and thus valid tags) of an IFD (normal / EXIF / GPSInfo)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum IfdType {
+    #[default]
     Ifd,
     Exif,
     GpsInfo,
@@ -24,11 +28,6 @@ impl IfdType {
             .chain(gps_info::ALL.iter())
     }
 }
-impl Default for IfdType {
-    fn default() -> Self {
-        Self::Ifd
-    }
-}
 
 /// A data structure describing one specific Field (2byte key) that can appear in an IFD
 /// Possible keys are defined in various specs, such ass the TIFF, TIFF-EP, DNG, ... spec.
@@ -165,6 +164,11 @@ impl PartialEq for MaybeKnownIfdFieldDescriptor {
         self.numeric() == other.numeric()
     }
 }
+impl Hash for MaybeKnownIfdFieldDescriptor {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.numeric().hash(state)
+    }
+}
 
 /// The data-type of an IFD value
 /// This does not include the fact that it is possible to have a list of every type
@@ -233,4 +237,26 @@ impl IfdValueType {
             IfdValueType::Double => 8,
         }
     }
+    /// The name of the type as used in the TIFF specification (e.g. `SHORT`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            IfdValueType::Byte => "BYTE",
+            IfdValueType::Ascii => "ASCII",
+            IfdValueType::Short => "SHORT",
+            IfdValueType::Long => "LONG",
+            IfdValueType::Rational => "RATIONAL",
+            IfdValueType::SByte => "SBYTE",
+            IfdValueType::Undefined => "UNDEFINED",
+            IfdValueType::SShort => "SSHORT",
+            IfdValueType::SLong => "SLONG",
+            IfdValueType::SRational => "SRATIONAL",
+            IfdValueType::Float => "FLOAT",
+            IfdValueType::Double => "DOUBLE",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        (1..=12)
+            .filter_map(Self::from_u16)
+            .find(|dtype| dtype.name() == name)
+    }
 }
@@ -0,0 +1,780 @@
+//! The XML format dcpTool uses for DCP (DNG Camera Profile) files. The root element is `<dcpData>`,
+//! each of its children stores one profile tag. Matrices and other real numbers are
+//! stored as SRATIONAL with a denominator of 10000 when reading, like the DNG SDK does.
+
+use crate::ifd::{Ifd, IfdValue};
+use crate::tags::{ifd, IfdFieldDescriptor, IfdType, IfdValueType, MaybeKnownIfdFieldDescriptor};
+use ::xml::common::{Position, TextPosition};
+use ::xml::reader::{EventReader, XmlEvent as ReaderEvent};
+use ::xml::writer::{EmitterConfig, EventWriter, XmlEvent as WriterEvent};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The denominator real numbers get when they are stored as SRATIONAL
+const RATIONAL_DENOMINATOR: i32 = 10000;
+/// The maximum number of values of a table (matrix, hue / saturation map or curve). Real profiles
+/// stay far below this, larger dimensions are rejected instead of allocating the table.
+const MAX_TABLE_LEN: usize = 1 << 20;
+
+/// How the content of an element of the XML maps to the value of a tag
+#[derive(Clone, Copy, Debug)]
+enum DcpXmlField {
+    /// The text of the element
+    Text,
+    /// An integer (e.g. the value of an enum)
+    Integer,
+    /// A real number stored as SRATIONAL
+    Real,
+    /// A matrix with 3 columns (mapping XYZ to the camera color space) given by `Element`s with
+    /// `Row` and `Col` attributes. The shape is given by the `Rows` and `Cols` attributes.
+    XyzToCameraMatrix,
+    /// A matrix with 3 rows (mapping the camera color space to XYZ), otherwise like
+    /// [DcpXmlField::XyzToCameraMatrix]
+    CameraToXyzMatrix,
+    /// A hue / saturation / value table given by `Element`s with `HueDiv`, `SatDiv`, `ValDiv`,
+    /// `HueShift`, `SatScale` and `ValScale` attributes. Its dimensions are given by the
+    /// `hueDivisions`, `satDivisions` and `valDivisions` attributes and stored in the given tag.
+    HueSatMap(IfdFieldDescriptor),
+    /// A curve given by `Element`s with `N`, `h` and `v` attributes. The number of points is given by
+    /// the `Size` attribute.
+    ToneCurve,
+}
+
+/// The elements of the XML (in the order dcpTool writes them) and the tags they correspond to
+const FIELDS: [(&str, IfdFieldDescriptor, DcpXmlField); 21] = [
+    ("ProfileName", ifd::ProfileName, DcpXmlField::Text),
+    (
+        "CalibrationIlluminant1",
+        ifd::CalibrationIlluminant1,
+        DcpXmlField::Integer,
+    ),
+    (
+        "CalibrationIlluminant2",
+        ifd::CalibrationIlluminant2,
+        DcpXmlField::Integer,
+    ),
+    (
+        "ColorMatrix1",
+        ifd::ColorMatrix1,
+        DcpXmlField::XyzToCameraMatrix,
+    ),
+    (
+        "ColorMatrix2",
+        ifd::ColorMatrix2,
+        DcpXmlField::XyzToCameraMatrix,
+    ),
+    (
+        "ForwardMatrix1",
+        ifd::ForwardMatrix1,
+        DcpXmlField::CameraToXyzMatrix,
+    ),
+    (
+        "ForwardMatrix2",
+        ifd::ForwardMatrix2,
+        DcpXmlField::CameraToXyzMatrix,
+    ),
+    (
+        "ReductionMatrix1",
+        ifd::ReductionMatrix1,
+        DcpXmlField::CameraToXyzMatrix,
+    ),
+    (
+        "ReductionMatrix2",
+        ifd::ReductionMatrix2,
+        DcpXmlField::CameraToXyzMatrix,
+    ),
+    ("Copyright", ifd::ProfileCopyright, DcpXmlField::Text),
+    ("EmbedPolicy", ifd::ProfileEmbedPolicy, DcpXmlField::Integer),
+    (
+        "HueSatDeltas1",
+        ifd::ProfileHueSatMapData1,
+        DcpXmlField::HueSatMap(ifd::ProfileHueSatMapDims),
+    ),
+    (
+        "HueSatDeltas2",
+        ifd::ProfileHueSatMapData2,
+        DcpXmlField::HueSatMap(ifd::ProfileHueSatMapDims),
+    ),
+    (
+        "ProfileHueSatMapEncoding",
+        ifd::ProfileHueSatMapEncoding,
+        DcpXmlField::Integer,
+    ),
+    (
+        "LookTable",
+        ifd::ProfileLookTableData,
+        DcpXmlField::HueSatMap(ifd::ProfileLookTableDims),
+    ),
+    (
+        "ProfileLookTableEncoding",
+        ifd::ProfileLookTableEncoding,
+        DcpXmlField::Integer,
+    ),
+    ("ToneCurve", ifd::ProfileToneCurve, DcpXmlField::ToneCurve),
+    (
+        "ProfileCalibrationSignature",
+        ifd::ProfileCalibrationSignature,
+        DcpXmlField::Text,
+    ),
+    (
+        "UniqueCameraModelRestriction",
+        ifd::UniqueCameraModel,
+        DcpXmlField::Text,
+    ),
+    (
+        "BaselineExposureOffset",
+        ifd::BaselineExposureOffset,
+        DcpXmlField::Real,
+    ),
+    (
+        "DefaultBlackRender",
+        ifd::DefaultBlackRender,
+        DcpXmlField::Integer,
+    ),
+];
+
+/// The error-type produced by the [DcpXmlParser] and the [DcpXmlDumper]
+#[derive(Debug)]
+pub enum DcpXmlError {
+    /// The source is not well-formed XML
+    XmlError(::xml::reader::Error),
+    WriterError(::xml::writer::Error),
+    /// The XML is well-formed but not a valid profile. Line and column are 1-based.
+    Invalid {
+        line: u64,
+        column: u64,
+        message: String,
+    },
+    /// The value of a tag cant be represented in the XML format (e.g. a matrix of the wrong size)
+    Unrepresentable(String),
+    /// The IFD contains tags that have no element in the XML format
+    UnsupportedTags(Vec<MaybeKnownIfdFieldDescriptor>),
+}
+impl Display for DcpXmlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DcpXmlError::XmlError(e) => f.write_fmt(format_args!("XmlError '{e}'")),
+            DcpXmlError::WriterError(e) => f.write_fmt(format_args!("WriterError '{e}'")),
+            DcpXmlError::Invalid {
+                line,
+                column,
+                message,
+            } => f.write_fmt(format_args!("{line}:{column}: {message}")),
+            DcpXmlError::Unrepresentable(message) => f.write_str(message),
+            DcpXmlError::UnsupportedTags(tags) => {
+                let names: Vec<_> = tags.iter().map(|tag| tag.to_string()).collect();
+                f.write_fmt(format_args!(
+                    "the XML format has no representation for {}",
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+impl Error for DcpXmlError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DcpXmlError::XmlError(e) => Some(e),
+            DcpXmlError::WriterError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<::xml::reader::Error> for DcpXmlError {
+    fn from(e: ::xml::reader::Error) -> Self {
+        Self::XmlError(e)
+    }
+}
+impl From<::xml::writer::Error> for DcpXmlError {
+    fn from(e: ::xml::writer::Error) -> Self {
+        Self::WriterError(e)
+    }
+}
+
+/// An element of the XML source together with its position
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<Element>,
+    position: TextPosition,
+}
+impl Element {
+    fn error(&self, message: String) -> DcpXmlError {
+        DcpXmlError::Invalid {
+            line: self.position.row + 1,
+            column: self.position.column + 1,
+            message,
+        }
+    }
+    fn attribute<T: FromStr>(&self, name: &str) -> Result<T, DcpXmlError> {
+        let value = self
+            .attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| self.error(format!("<{}> lacks the attribute '{name}'", self.name)))?;
+        value.trim().parse().map_err(|_| {
+            self.error(format!(
+                "couldn't parse '{value}' (attribute '{name}' of <{}>)",
+                self.name
+            ))
+        })
+    }
+    fn parse_text<T: FromStr>(&self) -> Result<T, DcpXmlError> {
+        self.text.trim().parse().map_err(|_| {
+            self.error(format!(
+                "couldn't parse '{}' (content of <{}>)",
+                self.text, self.name
+            ))
+        })
+    }
+
+    /// Computes the number of values of a table with the given dimensions
+    fn table_len(&self, dimensions: &[usize]) -> Result<usize, DcpXmlError> {
+        dimensions
+            .iter()
+            .try_fold(1usize, |len, dimension| len.checked_mul(*dimension))
+            .filter(|len| *len <= MAX_TABLE_LEN)
+            .ok_or_else(|| {
+                self.error(format!(
+                    "the dimensions of <{}> are too large (at most {MAX_TABLE_LEN} values)",
+                    self.name
+                ))
+            })
+    }
+
+    /// Collects the values the `Element` children of a table hold into a list of `len` values.
+    /// `read` returns the index of the first value an element holds and the values.
+    fn collect_table<T>(
+        &self,
+        len: usize,
+        read: impl Fn(&Element) -> Result<(usize, Vec<T>), DcpXmlError>,
+    ) -> Result<Vec<T>, DcpXmlError> {
+        let mut values: Vec<Option<T>> = (0..len).map(|_| None).collect();
+        for child in &self.children {
+            if child.name != "Element" {
+                return Err(child.error(format!(
+                    "expected <Element> in <{}> but found <{}>",
+                    self.name, child.name
+                )));
+            }
+            let (index, child_values) = read(child)?;
+            if index + child_values.len() > len {
+                return Err(child.error(format!(
+                    "the element is outside of the dimensions of <{}>",
+                    self.name
+                )));
+            }
+            for (i, value) in child_values.into_iter().enumerate() {
+                values[index + i] = Some(value);
+            }
+        }
+        values
+            .into_iter()
+            .collect::<Option<_>>()
+            .ok_or_else(|| self.error(format!("<{}> lacks some elements", self.name)))
+    }
+}
+
+fn read_element_tree(source: &str) -> Result<Element, DcpXmlError> {
+    let mut reader = EventReader::from_str(source);
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        match reader.next()? {
+            ReaderEvent::StartElement {
+                name, attributes, ..
+            } => stack.push(Element {
+                name: name.local_name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|attribute| (attribute.name.local_name, attribute.value))
+                    .collect(),
+                text: String::new(),
+                children: Vec::new(),
+                position: reader.position(),
+            }),
+            ReaderEvent::EndElement { .. } => {
+                // the reader already checks that elements are balanced
+                let element = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            ReaderEvent::Characters(text) | ReaderEvent::CData(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text)
+                }
+            }
+            ReaderEvent::EndDocument => unreachable!("the reader fails for documents without root"),
+            _ => {}
+        }
+    }
+}
+
+fn real_to_srational(value: f64) -> IfdValue {
+    IfdValue::SRational(
+        (value * RATIONAL_DENOMINATOR as f64).round() as i32,
+        RATIONAL_DENOMINATOR,
+    )
+}
+
+/// Parses DCP files in the XML format of dcpTool into an [Ifd]
+#[derive(Default)]
+pub struct DcpXmlParser {}
+impl DcpXmlParser {
+    /// Parses the `<dcpData>` of a profile into an IFD to be written with [crate::FileType::Dcp]
+    pub fn parse_from_str(&self, source: &str) -> Result<Ifd, DcpXmlError> {
+        let root = read_element_tree(source)?;
+        if root.name != "dcpData" {
+            return Err(root.error(format!("expected <dcpData> but found <{}>", root.name)));
+        }
+        let mut ifd = Ifd::new(IfdType::Ifd);
+        for element in &root.children {
+            let (_, tag, field) = FIELDS
+                .iter()
+                .find(|(name, _, _)| *name == element.name)
+                .ok_or_else(|| element.error(format!("unknown element <{}>", element.name)))?;
+            if let Some(value) = Self::parse_field(element, tag, *field, &mut ifd)? {
+                ifd.insert(*tag, value);
+            }
+        }
+        Ok(ifd)
+    }
+
+    fn parse_field(
+        element: &Element,
+        tag: &IfdFieldDescriptor,
+        field: DcpXmlField,
+        ifd: &mut Ifd,
+    ) -> Result<Option<IfdValue>, DcpXmlError> {
+        let value = match field {
+            DcpXmlField::Text => IfdValue::Ascii(element.text.clone()),
+            DcpXmlField::Integer => match tag.dtype[0] {
+                IfdValueType::Short => IfdValue::Short(element.parse_text()?),
+                _ => IfdValue::Long(element.parse_text()?),
+            },
+            DcpXmlField::Real => real_to_srational(element.parse_text()?),
+            DcpXmlField::XyzToCameraMatrix | DcpXmlField::CameraToXyzMatrix => {
+                let rows: usize = element.attribute("Rows")?;
+                let cols: usize = element.attribute("Cols")?;
+                let len = element.table_len(&[rows, cols])?;
+                // dcpTool writes empty matrices for the ones a profile doesnt have
+                if len == 0 {
+                    return Ok(None);
+                }
+                IfdValue::List(element.collect_table(len, |child| {
+                    let row: usize = child.attribute("Row")?;
+                    let col: usize = child.attribute("Col")?;
+                    if row >= rows || col >= cols {
+                        return Err(
+                            child.error(format!("element ({row}, {col}) is outside of the matrix"))
+                        );
+                    }
+                    let value = real_to_srational(child.parse_text()?);
+                    Ok((row * cols + col, vec![value]))
+                })?)
+            }
+            DcpXmlField::HueSatMap(dims_tag) => {
+                let hues: usize = element.attribute("hueDivisions")?;
+                let sats: usize = element.attribute("satDivisions")?;
+                let vals: usize = element.attribute("valDivisions")?;
+                let len = element.table_len(&[hues, sats, vals, 3])?;
+                let dims = IfdValue::List(
+                    [hues, sats, vals]
+                        .iter()
+                        .map(|divisions| IfdValue::Long(*divisions as u32))
+                        .collect(),
+                );
+                // both hue / saturation maps share their dimensions
+                if ifd.get(dims_tag).is_some_and(|other| *other != dims) {
+                    return Err(element.error(format!(
+                        "the dimensions of <{}> differ from the ones of the other table",
+                        element.name
+                    )));
+                }
+                ifd.insert(dims_tag, dims);
+                let data = element.collect_table(len, |child| {
+                    let hue: usize = child.attribute("HueDiv")?;
+                    let sat: usize = child.attribute("SatDiv")?;
+                    let val: usize = child.attribute("ValDiv")?;
+                    if hue >= hues || sat >= sats || val >= vals {
+                        return Err(child.error(format!(
+                            "division ({hue}, {sat}, {val}) is outside of the table"
+                        )));
+                    }
+                    let values = ["HueShift", "SatScale", "ValScale"]
+                        .iter()
+                        .map(|name| child.attribute(name).map(IfdValue::Float))
+                        .collect::<Result<_, _>>()?;
+                    // values are the outer, hues the middle and saturations the inner loop
+                    Ok((((val * hues + hue) * sats + sat) * 3, values))
+                })?;
+                IfdValue::List(data)
+            }
+            DcpXmlField::ToneCurve => {
+                let size: usize = element.attribute("Size")?;
+                let len = element.table_len(&[size, 2])?;
+                IfdValue::List(element.collect_table(len, |child| {
+                    let n: usize = child.attribute("N")?;
+                    if n >= size {
+                        return Err(child.error(format!("point {n} is outside of the curve")));
+                    }
+                    let h = IfdValue::Float(child.attribute("h")?);
+                    let v = IfdValue::Float(child.attribute("v")?);
+                    Ok((n * 2, vec![h, v]))
+                })?)
+            }
+        };
+        Ok(Some(value))
+    }
+}
+
+/// Dumps the IFD of a DCP file in the XML format of dcpTool
+#[derive(Default)]
+pub struct DcpXmlDumper {
+    /// leave out tags the XML format has no representation for instead of failing
+    pub skip_unsupported: bool,
+}
+impl DcpXmlDumper {
+    /// Returns the tags of the IFD that have no representation in the XML format
+    pub fn unsupported_tags(ifd: &Ifd) -> Vec<MaybeKnownIfdFieldDescriptor> {
+        ifd.entries()
+            .iter()
+            .map(|entry| entry.tag)
+            .filter(|tag| {
+                !FIELDS.iter().any(|(_, field_tag, field)| {
+                    *tag == field_tag.as_maybe()
+                        || matches!(field, DcpXmlField::HueSatMap(dims_tag) if *tag == dims_tag.as_maybe())
+                })
+            })
+            .collect()
+    }
+
+    pub fn dump_ifd(&self, ifd: &Ifd) -> Result<String, DcpXmlError> {
+        let unsupported = Self::unsupported_tags(ifd);
+        if !self.skip_unsupported && !unsupported.is_empty() {
+            return Err(DcpXmlError::UnsupportedTags(unsupported));
+        }
+
+        let mut writer = EmitterConfig::new()
+            .perform_indent(true)
+            .create_writer(Vec::new());
+        writer.write(WriterEvent::start_element("dcpData"))?;
+        for (name, tag, field) in FIELDS {
+            if let Some(value) = ifd.get(tag) {
+                Self::dump_field(&mut writer, name, tag, field, value, ifd)?;
+            }
+        }
+        writer.write(WriterEvent::end_element())?;
+        // the writer only produces valid UTF-8
+        Ok(String::from_utf8(writer.into_inner()).unwrap())
+    }
+
+    fn dump_field(
+        writer: &mut EventWriter<Vec<u8>>,
+        name: &str,
+        tag: IfdFieldDescriptor,
+        field: DcpXmlField,
+        value: &IfdValue,
+        ifd: &Ifd,
+    ) -> Result<(), DcpXmlError> {
+        let unrepresentable = |message: &str| {
+            DcpXmlError::Unrepresentable(format!("cant represent {}: {message}", tag.name))
+        };
+        let values: Vec<_> = value.as_list().collect();
+        match field {
+            DcpXmlField::Text => {
+                let text = match value {
+                    IfdValue::Ascii(text) => text.clone(),
+                    _ => {
+                        let bytes = values
+                            .iter()
+                            .map(|value| match value {
+                                IfdValue::Byte(byte) => Ok(*byte),
+                                _ => Err(unrepresentable("expected ASCII or BYTE")),
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        String::from_utf8_lossy(&bytes)
+                            .trim_end_matches('\0')
+                            .to_string()
+                    }
+                };
+                Self::write_element(writer, name, &[], Some(&text))
+            }
+            DcpXmlField::Integer => {
+                let number = value
+                    .as_u32()
+                    .ok_or_else(|| unrepresentable("expected an integer"))?;
+                Self::write_element(writer, name, &[], Some(&number.to_string()))
+            }
+            DcpXmlField::Real => {
+                let number = Self::format_number(value)
+                    .ok_or_else(|| unrepresentable("expected a number"))?;
+                Self::write_element(writer, name, &[], Some(&number))
+            }
+            DcpXmlField::XyzToCameraMatrix | DcpXmlField::CameraToXyzMatrix => {
+                if values.len() % 3 != 0 {
+                    return Err(unrepresentable("the matrix has to have 3 rows or columns"));
+                }
+                let (rows, cols) = match field {
+                    DcpXmlField::XyzToCameraMatrix => (values.len() / 3, 3),
+                    _ => (3, values.len() / 3),
+                };
+                Self::write_start(
+                    writer,
+                    name,
+                    &[("Rows", rows.to_string()), ("Cols", cols.to_string())],
+                )?;
+                for (i, value) in values.iter().enumerate() {
+                    let number = Self::format_number(value)
+                        .ok_or_else(|| unrepresentable("expected a matrix of numbers"))?;
+                    let attributes = [
+                        ("Row", (i / cols).to_string()),
+                        ("Col", (i % cols).to_string()),
+                    ];
+                    Self::write_element(writer, "Element", &attributes, Some(&number))?;
+                }
+                writer.write(WriterEvent::end_element())?;
+                Ok(())
+            }
+            DcpXmlField::HueSatMap(dims_tag) => {
+                let dims: Vec<_> = ifd
+                    .get(dims_tag)
+                    .map(|dims| dims.as_list().filter_map(|d| d.as_u32()).collect())
+                    .unwrap_or_default();
+                let [hues, sats, vals] = dims[..] else {
+                    return Err(unrepresentable(&format!("{} is missing", dims_tag.name)));
+                };
+                let (hues, sats, vals) = (hues as usize, sats as usize, vals as usize);
+                if values.len() != hues * sats * vals * 3 {
+                    return Err(unrepresentable(&format!(
+                        "the table doesnt match the dimensions of {}",
+                        dims_tag.name
+                    )));
+                }
+                Self::write_start(
+                    writer,
+                    name,
+                    &[
+                        ("hueDivisions", hues.to_string()),
+                        ("satDivisions", sats.to_string()),
+                        ("valDivisions", vals.to_string()),
+                    ],
+                )?;
+                for (i, entry) in values.chunks(3).enumerate() {
+                    let mut attributes = vec![
+                        ("HueDiv", (i / sats % hues).to_string()),
+                        ("SatDiv", (i % sats).to_string()),
+                        ("ValDiv", (i / sats / hues).to_string()),
+                    ];
+                    for (name, value) in ["HueShift", "SatScale", "ValScale"].iter().zip(entry) {
+                        let number = Self::format_number(value)
+                            .ok_or_else(|| unrepresentable("expected a table of numbers"))?;
+                        attributes.push((name, number));
+                    }
+                    Self::write_element(writer, "Element", &attributes, None)?;
+                }
+                writer.write(WriterEvent::end_element())?;
+                Ok(())
+            }
+            DcpXmlField::ToneCurve => {
+                if values.len() % 2 != 0 {
+                    return Err(unrepresentable("expected pairs of input and output values"));
+                }
+                Self::write_start(writer, name, &[("Size", (values.len() / 2).to_string())])?;
+                for (n, point) in values.chunks(2).enumerate() {
+                    let [h, v] = [point[0], point[1]].map(|value| {
+                        Self::format_number(value)
+                            .ok_or_else(|| unrepresentable("expected a curve of numbers"))
+                    });
+                    let attributes = [("N", n.to_string()), ("h", h?), ("v", v?)];
+                    Self::write_element(writer, "Element", &attributes, None)?;
+                }
+                writer.write(WriterEvent::end_element())?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Formats numbers as short as possible while still reading back to the same value
+    fn format_number(value: &IfdValue) -> Option<String> {
+        match value {
+            // going through f64 would produce a lot of digits
+            IfdValue::Float(number) => Some(number.to_string()),
+            value => value.as_f64().map(|number| number.to_string()),
+        }
+    }
+
+    fn write_start(
+        writer: &mut EventWriter<Vec<u8>>,
+        name: &str,
+        attributes: &[(&str, String)],
+    ) -> Result<(), DcpXmlError> {
+        let mut event = WriterEvent::start_element(name);
+        for (attribute, value) in attributes {
+            event = event.attr(*attribute, value);
+        }
+        writer.write(event)?;
+        Ok(())
+    }
+
+    fn write_element(
+        writer: &mut EventWriter<Vec<u8>>,
+        name: &str,
+        attributes: &[(&str, String)],
+        text: Option<&str>,
+    ) -> Result<(), DcpXmlError> {
+        Self::write_start(writer, name, attributes)?;
+        if let Some(text) = text {
+            writer.write(WriterEvent::characters(text))?;
+        }
+        writer.write(WriterEvent::end_element())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifd::IfdValue;
+    use crate::tags::ifd;
+    use crate::xml::{DcpXmlDumper, DcpXmlError, DcpXmlParser};
+    use crate::DngReader;
+    use std::fs::File;
+
+    #[test]
+    fn test_parse_dcptool_xml() {
+        let source = r#"<?xml version="1.0" encoding="UTF-8" standalone="no" ?>
+<dcpData>
+  <ProfileName>Test Profile</ProfileName>
+  <CalibrationIlluminant1>17</CalibrationIlluminant1>
+  <ColorMatrix1 Rows="3" Cols="3">
+    <Element Row="0" Col="0">1.000000</Element>
+    <Element Row="0" Col="1">-0.250000</Element>
+    <Element Row="0" Col="2">0.000000</Element>
+    <Element Row="1" Col="0">0.000000</Element>
+    <Element Row="1" Col="1">1.000000</Element>
+    <Element Row="1" Col="2">0.000000</Element>
+    <Element Row="2" Col="0">0.000000</Element>
+    <Element Row="2" Col="1">0.000000</Element>
+    <Element Row="2" Col="2">0.500000</Element>
+  </ColorMatrix1>
+  <ReductionMatrix1 Rows="0" Cols="0"/>
+  <HueSatDeltas1 hueDivisions="2" satDivisions="1" valDivisions="1">
+    <Element HueDiv="1" SatDiv="0" ValDiv="0" HueShift="-1.5" SatScale="1.25" ValScale="1"/>
+    <Element HueDiv="0" SatDiv="0" ValDiv="0" HueShift="0" SatScale="1" ValScale="1"/>
+  </HueSatDeltas1>
+  <ToneCurve Size="2">
+    <Element N="0" h="0.000000" v="0.000000"/>
+    <Element N="1" h="1.000000" v="1.000000"/>
+  </ToneCurve>
+  <UniqueCameraModelRestriction>Test Camera</UniqueCameraModelRestriction>
+</dcpData>
+"#;
+        let ifd = DcpXmlParser::default().parse_from_str(source).unwrap();
+        assert_eq!(
+            ifd.get(ifd::ProfileName),
+            Some(&IfdValue::Ascii("Test Profile".to_string()))
+        );
+        assert_eq!(
+            ifd.get(ifd::CalibrationIlluminant1),
+            Some(&IfdValue::Short(17))
+        );
+        let matrix: Vec<_> = ifd.get(ifd::ColorMatrix1).unwrap().as_list().collect();
+        assert_eq!(matrix.len(), 9);
+        assert_eq!(matrix[1], &IfdValue::SRational(-2500, 10000));
+        assert_eq!(ifd.get(ifd::ReductionMatrix1), None);
+        assert_eq!(
+            ifd.get(ifd::ProfileHueSatMapDims),
+            Some(&IfdValue::List([2, 1, 1].map(IfdValue::Long).to_vec()))
+        );
+        let deltas = [0.0, 1.0, 1.0, -1.5, 1.25, 1.0]
+            .map(IfdValue::Float)
+            .to_vec();
+        assert_eq!(
+            ifd.get(ifd::ProfileHueSatMapData1),
+            Some(&IfdValue::List(deltas))
+        );
+        let dumped = DcpXmlDumper::default().dump_ifd(&ifd).unwrap();
+        assert!(dumped.contains(r#"<Element Row="0" Col="1">-0.25</Element>"#));
+        assert!(dumped.contains(r#"<Element HueDiv="1" SatDiv="0" ValDiv="0" HueShift="-1.5" SatScale="1.25" ValScale="1" />"#));
+        assert_eq!(
+            DcpXmlParser::default().parse_from_str(&dumped).unwrap(),
+            ifd
+        );
+
+        let error = DcpXmlParser::default()
+            .parse_from_str("<dcpData>\n  <ProfileName>a</ProfileName>\n  <Foo/>\n</dcpData>")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "3:3: unknown element <Foo>");
+    }
+
+    #[test]
+    fn test_absurd_dimensions() {
+        for table in [
+            r#"<ColorMatrix1 Rows="4294967296" Cols="4294967296"/>"#,
+            r#"<HueSatDeltas1 hueDivisions="100000" satDivisions="100000" valDivisions="100000"/>"#,
+            r#"<ToneCurve Size="18446744073709551615"/>"#,
+        ] {
+            let source = format!("<dcpData>\n  {table}\n</dcpData>");
+            let error = DcpXmlParser::default().parse_from_str(&source).unwrap_err();
+            assert!(
+                matches!(&error, DcpXmlError::Invalid { message, .. } if message.contains("too large")),
+                "{error}"
+            );
+        }
+
+        let error = DcpXmlParser::default()
+            .parse_from_str(
+                "<dcpData>\n  <ToneCurve Size=\"1\">\n    \
+                 <Element N=\"9223372036854775807\" h=\"0\" v=\"0\"/>\n  </ToneCurve>\n</dcpData>",
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "3:5: point 9223372036854775807 is outside of the curve"
+        );
+    }
+
+    #[test]
+    fn test_dcp_roundtrip() {
+        let dcp =
+            DngReader::read(File::open("src/yaml/testdata/axiom_beta_simulated.dcp").unwrap())
+                .unwrap();
+        let ifd = dcp.get_ifd0();
+        let dumped = DcpXmlDumper::default().dump_ifd(ifd).unwrap();
+        let parsed = DcpXmlParser::default().parse_from_str(&dumped).unwrap();
+        for entry in ifd.entries() {
+            let original: Vec<_> = entry.value.as_list().collect();
+            let roundtripped: Vec<_> = parsed.get(entry.tag).unwrap().as_list().collect();
+            assert_eq!(original.len(), roundtripped.len(), "{}", entry.tag);
+            for (a, b) in original.iter().zip(roundtripped) {
+                match (a.as_f64(), b.as_f64()) {
+                    (Some(a), Some(b)) => assert!((a - b).abs() < 1e-4, "{}", entry.tag),
+                    _ => assert_eq!(*a, b),
+                }
+            }
+        }
+        // once the numbers are SRATIONALs with the denominator of the XML reader, it is lossless
+        let dumped_again = DcpXmlDumper::default().dump_ifd(&parsed).unwrap();
+        assert_eq!(
+            DcpXmlParser::default()
+                .parse_from_str(&dumped_again)
+                .unwrap(),
+            parsed
+        );
+
+        let mut with_unsupported = ifd.clone();
+        with_unsupported.insert(ifd::Make, "some make");
+        assert!(matches!(
+            DcpXmlDumper::default().dump_ifd(&with_unsupported),
+            Err(DcpXmlError::UnsupportedTags(tags)) if tags == [ifd::Make.as_maybe()]
+        ));
+        let dumper = DcpXmlDumper {
+            skip_unsupported: true,
+        };
+        assert_eq!(dumper.dump_ifd(&with_unsupported).unwrap(), dumped);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::ifd::{Ifd, IfdDiffEntry, IfdDiffOptions, IfdEntryRef, IfdPath, IfdValue};
+use crate::yaml::IfdYamlDumper;
+
+/// Formats the differences between IFDs (see [Ifd::diff]) like a unified diff: every line starts
+/// with `-` (old) or `+` (new) followed by the path of the entry and its value dumped as YAML.
+/// All methods return the formatted lines (each terminated by a line break).
+#[derive(Default)]
+pub struct IfdDiffPrinter {
+    pub dumper: IfdYamlDumper,
+    /// Colors removed lines red and added lines green with ANSI escape codes
+    pub color: bool,
+}
+impl IfdDiffPrinter {
+    /// The lines naming the compared files
+    pub fn header(&self, old: &str, new: &str) -> String {
+        self.line('-', &format!("-- {old}")) + &self.line('+', &format!("++ {new}"))
+    }
+
+    /// The differences between two IFD chains. IFDs that exist in only one of the chains are
+    /// reported as added / removed together with all their entries.
+    pub fn ifd_chain(&self, old: &[Ifd], new: &[Ifd], options: &IfdDiffOptions) -> String {
+        (0..old.len().max(new.len()))
+            .map(|i| self.ifd(i, old.get(i), new.get(i), options))
+            .collect()
+    }
+
+    /// The differences between the toplevel IFDs with the given index of two chains (`None` if
+    /// the chain has no such IFD)
+    pub fn ifd(
+        &self,
+        chain_index: usize,
+        old: Option<&Ifd>,
+        new: Option<&Ifd>,
+        options: &IfdDiffOptions,
+    ) -> String {
+        let path = format!("{:?}", IfdPath::chain_index(chain_index as u16));
+        let (mut output, empty_ifd) = (String::new(), Ifd::default());
+        match (old, new) {
+            (Some(_), None) => output += &self.line('-', &format!("{path}: removed IFD")),
+            (None, Some(_)) => output += &self.line('+', &format!("{path}: added IFD")),
+            _ => {}
+        }
+        let (old, new) = (old.unwrap_or(&empty_ifd), new.unwrap_or(&empty_ifd));
+        for entry in old.diff(new, options) {
+            output += &self.entry(chain_index, &entry);
+        }
+        output
+    }
+
+    /// A single difference of the toplevel IFD with the given index
+    pub fn entry(&self, chain_index: usize, entry: &IfdDiffEntry) -> String {
+        let path = self.format_path(chain_index, entry.path());
+        match entry {
+            IfdDiffEntry::Added { value, .. } => {
+                self.value('+', &path, self.format_value(entry.path(), value))
+            }
+            IfdDiffEntry::Removed { value, .. } => {
+                self.value('-', &path, self.format_value(entry.path(), value))
+            }
+            IfdDiffEntry::Changed { old, new, .. } => {
+                self.value('-', &path, self.format_value(entry.path(), old))
+                    + &self.value('+', &path, self.format_value(entry.path(), new))
+            }
+        }
+    }
+
+    /// A differing checksum (e.g. an MD5 digest) of the data behind an OFFSETS entry (`None` if
+    /// the entry is missing). The checksums are printed as hex.
+    pub fn checksum(
+        &self,
+        chain_index: usize,
+        path: &IfdPath,
+        old: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> String {
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        let path = self.format_path(chain_index, path);
+        let old = old.map(|old| self.line('-', &format!("{path}: checksum {}", hex(old))));
+        let new = new.map(|new| self.line('+', &format!("{path}: checksum {}", hex(new))));
+        old.unwrap_or_default() + &new.unwrap_or_default()
+    }
+
+    fn format_path(&self, chain_index: usize, path: &IfdPath) -> String {
+        format!(
+            "{:?}",
+            IfdPath::chain_index(chain_index as u16).chain_path(path)
+        )
+    }
+    fn format_value(&self, path: &IfdPath, value: &IfdValue) -> String {
+        match path.last_tag() {
+            Some(tag) => self.dumper.dump_ifd_value(IfdEntryRef { value, tag, path }),
+            None => format!("{value:?}"),
+        }
+    }
+    fn value(&self, sign: char, path: &str, value: String) -> String {
+        let mut output = String::new();
+        for (i, line) in format!("{path}: {value}").lines().enumerate() {
+            // continuation lines of nested IFDs are indented below the path
+            if i == 0 {
+                output += &self.line(sign, line)
+            } else if !line.trim().is_empty() {
+                output += &self.line(sign, &format!("  {line}"))
+            }
+        }
+        output
+    }
+    fn line(&self, sign: char, line: &str) -> String {
+        let color = if sign == '+' { "32" } else { "31" };
+        if self.color {
+            format!("\x1b[{color}m{sign}{line}\x1b[0m\n")
+        } else {
+            format!("{sign}{line}\n")
+        }
+    }
+}
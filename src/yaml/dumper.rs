@@ -1,38 +1,98 @@
-use crate::ifd::{Ifd, IfdEntryRef, IfdPath, IfdValue};
-use crate::tags::{IfdTypeInterpretation, IfdValueType};
+use crate::ifd::{Ifd, IfdEntryRef, IfdPath, IfdPathElement, IfdValue};
+use crate::tags::{ifd, IfdTypeInterpretation, IfdValueType};
+use crate::yaml::{base64, bitflag_name, blob_dtype, CFA_COLOR_NAMES};
 use std::sync::Arc;
 
+/// Formats a float so that it is parsed back to the exact same bits
+macro_rules! lossless_float {
+    ($value:expr, $ty:ident) => {{
+        let x: $ty = $value;
+        if x.is_nan() && x.to_bits() != $ty::NAN.to_bits() {
+            // NaNs with a payload can only be represented by their bits
+            format!("{:#X}", x.to_bits())
+        } else if x.is_nan() {
+            ".nan".to_string()
+        } else if x.is_infinite() {
+            if x > 0.0 { ".inf" } else { "-.inf" }.to_string()
+        } else {
+            format!("{x}")
+        }
+    }};
+}
+
+/// Can replace the text representation of an entry (by returning `Some`)
+pub type IfdYamlVisitor = Arc<dyn Fn(IfdEntryRef) -> Option<String>>;
+
+/// How the bytes of [IfdTypeInterpretation::Blob] tags (e.g. ICC profiles) are dumped
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IfdYamlBlobFormat {
+    /// A list of numbers like any other value
+    #[default]
+    List,
+    /// Base64 encoded with the standard `!!binary` YAML tag
+    Base64,
+    /// Hex encoded with the `!hex` tag (e.g. `!hex 00ff10`)
+    Hex,
+}
+
 /// Dumps an [Ifd] struct into a friendly human readable text-representation
 #[derive(Default)]
 pub struct IfdYamlDumper {
     pub dump_rational_as_float: bool,
-    pub visitor: Option<Arc<dyn Fn(IfdEntryRef) -> Option<String>>>,
+    /// Guarantees that the [crate::yaml::IfdYamlParser] reproduces a bit-identical [Ifd] from the
+    /// output: dtypes are annotated whenever they differ from the first dtype of the tag, enum
+    /// values without an unambiguous name are dumped as numbers and floats keep their exact bits.
+    /// `dump_rational_as_float` is ignored in this mode.
+    pub lossless: bool,
+    /// How the contents of blobs (and of offsets) are written inline
+    pub blob_format: IfdYamlBlobFormat,
+    pub visitor: Option<IfdYamlVisitor>,
 }
 impl IfdYamlDumper {
     pub fn dump_ifd(&self, ifd: &Ifd) -> String {
         self.dump_ifd_with_path(ifd, &IfdPath::default())
     }
+    /// Dumps all toplevel IFDs of a file as separate YAML documents (separated by `---`).
+    /// A chain consisting of a single IFD is dumped exactly like [IfdYamlDumper::dump_ifd] would.
+    pub fn dump_ifd_chain(&self, ifds: &[Ifd]) -> String {
+        ifds.iter()
+            .enumerate()
+            .map(|(i, ifd)| self.dump_ifd_with_path(ifd, &IfdPath::chain_index(i as u16)))
+            .collect::<Vec<_>>()
+            .join("---\n")
+    }
     pub fn dump_ifd_with_path(&self, ifd: &Ifd, path: &IfdPath) -> String {
         ifd.entries
             .iter()
             .map(|entry| {
+                let entry_path = path.chain_tag(entry.tag);
+                let entry_ref = entry.get_ref(&entry_path);
                 format!(
                     "{}: {}{}\n",
                     entry.tag,
-                    self.dump_tag_if_needed(entry.get_ref(&path.chain_tag(entry.tag))),
-                    self.dump_ifd_value(entry.get_ref(&path.chain_tag(entry.tag)))
+                    self.dump_tag_if_needed(entry_ref),
+                    self.dump_ifd_value_in_ifd(entry_ref, Some(ifd))
                 )
             })
             .collect()
     }
     pub fn dump_ifd_value(&self, entry: IfdEntryRef) -> String {
+        self.dump_ifd_value_in_ifd(entry, None)
+    }
+    /// Dumps a value that can also be interpreted using the other entries of the IFD containing it
+    /// (e.g. a CFAPattern is laid out using CFARepeatPatternDim)
+    fn dump_ifd_value_in_ifd(&self, entry: IfdEntryRef, ifd: Option<&Ifd>) -> String {
         if entry.tag.get_type_interpretation().is_some() {
-            self.dump_ifd_value_with_type_interpretation(entry)
+            self.dump_ifd_value_with_type_interpretation(entry, ifd)
         } else {
             self.dump_ifd_value_plain(entry)
         }
     }
-    fn dump_ifd_value_with_type_interpretation(&self, entry: IfdEntryRef) -> String {
+    fn dump_ifd_value_with_type_interpretation(
+        &self,
+        entry: IfdEntryRef,
+        ifd: Option<&Ifd>,
+    ) -> String {
         if let Some(s) = self.visitor.clone().and_then(|visitor| visitor(entry)) {
             return s;
         }
@@ -40,26 +100,117 @@ impl IfdYamlDumper {
         match entry.tag.get_type_interpretation().unwrap() {
             IfdTypeInterpretation::Enumerated { values } => {
                 if let Some(num) = entry.value.as_u32() {
-                    if let Some((_, v)) = values.iter().find(|(k, _)| *k == num) {
-                        v.to_string()
-                    } else {
-                        format!("UNKNOWN ({})", self.dump_ifd_value_plain(entry))
+                    let name = values.iter().find(|(k, _)| *k == num).map(|(_, v)| *v);
+                    match name {
+                        // names are only parsed back to the unsigned dtypes
+                        Some(name) if self.lossless => {
+                            if Self::is_unsigned(entry.value) {
+                                Self::dump_name_lossless(name)
+                            } else {
+                                self.dump_ifd_value_plain(entry)
+                            }
+                        }
+                        Some(name) => name.to_string(),
+                        None if self.lossless => self.dump_ifd_value_plain(entry),
+                        None => format!("UNKNOWN ({})", self.dump_ifd_value_plain(entry)),
                     }
                 } else {
-                    unreachable!()
+                    // lists of enum values are dumped element wise
+                    self.dump_ifd_value_plain(entry)
                 }
             }
+            IfdTypeInterpretation::Bitflags { values } => match entry.value.as_u32() {
+                // flags inside of lists are dumped as numbers as nested lists would be ambiguous
+                Some(num)
+                    if Self::is_unsigned(entry.value)
+                        && !matches!(
+                            entry.path.as_vec().last(),
+                            Some(IfdPathElement::ListIndex(_))
+                        ) =>
+                {
+                    let flags: Vec<String> = (0..32)
+                        .filter(|bit| num & (1 << bit) != 0)
+                        .map(|bit| match values.iter().find(|(b, _)| *b as u32 == bit) {
+                            Some((_, name)) if !self.lossless => bitflag_name(name).to_string(),
+                            Some((_, name)) if Self::is_unambiguous_bitflag(values, name) => {
+                                Self::dump_name_lossless(bitflag_name(name))
+                            }
+                            _ => format!("bit {bit}"),
+                        })
+                        .collect();
+                    format!("[{}]", flags.join(", "))
+                }
+                _ => self.dump_ifd_value_plain(entry),
+            },
+            IfdTypeInterpretation::Blob => match self.get_inline_blob_bytes(entry) {
+                Some(bytes) => self.dump_blob(&bytes),
+                None => self.dump_ifd_value_plain(entry),
+            },
+            IfdTypeInterpretation::CfaPattern => ifd
+                .and_then(|ifd| Self::dump_cfa_pattern(entry, ifd))
+                .unwrap_or_else(|| self.dump_ifd_value_plain(entry)),
             _ => self.dump_ifd_value_plain(entry),
         }
     }
+    /// Dumps a CFAPattern as rows of color names (e.g. `[[Red, Green], [Green, Blue]]`). Returns None
+    /// if the pattern doesnt match the CFARepeatPatternDim of the IFD.
+    fn dump_cfa_pattern(entry: IfdEntryRef, ifd: &Ifd) -> Option<String> {
+        let IfdValue::List(pattern) = entry.value else {
+            return None;
+        };
+        let dims: Vec<u32> = ifd
+            .get(ifd::CFARepeatPatternDim)?
+            .as_list()
+            .map(|x| x.as_u32())
+            .collect::<Option<_>>()?;
+        let [rows, cols] = dims[..] else {
+            return None;
+        };
+        if pattern.is_empty() || (rows * cols) as usize != pattern.len() {
+            return None;
+        }
+        // the grid is always parsed back with the first dtype of the tag
+        let dtype = entry.tag.get_known_value_type()?.first()?;
+        if pattern.iter().any(|x| x.get_ifd_value_type() != *dtype) {
+            return None;
+        }
+        let plane_colors: Vec<u32> = match ifd.get(ifd::CFAPlaneColor) {
+            Some(colors) => colors
+                .as_list()
+                .map(|x| x.as_u32())
+                .collect::<Option<_>>()?,
+            None => vec![0, 1, 2],
+        };
+
+        let planes: Vec<u32> = pattern.iter().map(|x| x.as_u32()).collect::<Option<_>>()?;
+        let cells: Vec<String> = planes
+            .iter()
+            .map(|&plane| match plane_colors.get(plane as usize) {
+                // a name is only unambiguous if no other plane has the same color
+                Some(color)
+                    if plane_colors.iter().position(|c| c == color) == Some(plane as usize) =>
+                {
+                    CFA_COLOR_NAMES
+                        .get(*color as usize)
+                        .map_or_else(|| plane.to_string(), |name| name.to_string())
+                }
+                _ => plane.to_string(),
+            })
+            .collect();
+        let rows: Vec<String> = cells
+            .chunks(cols as usize)
+            .map(|row| format!("[{}]", row.join(", ")))
+            .collect();
+        Some(format!("[{}]", rows.join(", ")))
+    }
     fn dump_ifd_value_plain(&self, entry: IfdEntryRef) -> String {
         match &entry.value {
             IfdValue::Byte(x) => format!("{x}"),
-            IfdValue::Ascii(x) => format!("\"{x}\""),
+            IfdValue::Ascii(x) => Self::dump_string(x),
             IfdValue::Short(x) => format!("{x}"),
             IfdValue::Long(x) => format!("{x}"),
             IfdValue::Rational(x, y) => {
-                if self.dump_rational_as_float {
+                if self.dump_rational_as_float && !self.lossless {
                     format!("{}", *x as f32 / *y as f32)
                 } else {
                     format!("{x}/{y}")
@@ -70,13 +221,15 @@ impl IfdYamlDumper {
             IfdValue::SShort(x) => format!("{x}"),
             IfdValue::SLong(x) => format!("{x}"),
             IfdValue::SRational(x, y) => {
-                if self.dump_rational_as_float {
+                if self.dump_rational_as_float && !self.lossless {
                     format!("{}", *x as f32 / *y as f32)
                 } else {
                     format!("{x}/{y}")
                 }
             }
+            IfdValue::Float(x) if self.lossless => lossless_float!(*x, f32),
             IfdValue::Float(x) => format!("{x}"),
+            IfdValue::Double(x) if self.lossless => lossless_float!(*x, f64),
             IfdValue::Double(x) => format!("{x}"),
             IfdValue::List(l) => {
                 if let IfdValue::Ifd(_) = l[0] {
@@ -116,12 +269,26 @@ impl IfdYamlDumper {
                     textwrap::indent(&self.dump_ifd_with_path(ifd, entry.path), "  ")
                 )
             }
-            IfdValue::Offsets(_) => unimplemented!(),
+            IfdValue::Offsets(_) => self.dump_blob(&entry.value.offsets_bytes().unwrap()),
         }
     }
     fn dump_tag_if_needed(&self, entry: IfdEntryRef) -> String {
+        // IFDs and offsets are written as pointers, so their dtype is implied. the dtype of blobs is
+        // implied by the tag
+        if matches!(entry.value, IfdValue::Ifd(_) | IfdValue::Offsets(_))
+            || self.get_inline_blob_bytes(entry).is_some()
+        {
+            return "".to_string();
+        }
         if let Some(types) = entry.tag.get_known_value_type() {
-            if types.contains(&entry.value.get_ifd_value_type()) {
+            let dtype = entry.value.get_ifd_value_type();
+            // the parser tries the dtypes of a tag in order and picks the first one that works
+            let is_default = if self.lossless {
+                types.first() == Some(&dtype)
+            } else {
+                types.contains(&dtype)
+            };
+            if is_default {
                 return "".to_string();
             }
         }
@@ -131,20 +298,99 @@ impl IfdYamlDumper {
         )
     }
     fn dump_ifd_value_type(v: &IfdValueType) -> &str {
-        match v {
-            IfdValueType::Byte => "BYTE",
-            IfdValueType::Ascii => "ASCII",
-            IfdValueType::Short => "SHORT",
-            IfdValueType::Long => "LONG",
-            IfdValueType::Rational => "RATIONAL",
-            IfdValueType::SByte => "SBYTE",
-            IfdValueType::Undefined => "UNDEFINED",
-            IfdValueType::SShort => "SSHORT",
-            IfdValueType::SLong => "SLONG",
-            IfdValueType::SRational => "SRATIONAL",
-            IfdValueType::Float => "FLOAT",
-            IfdValueType::Double => "DOUBLE",
+        v.name()
+    }
+    /// Dumps raw bytes as an inline blob in the configured [IfdYamlBlobFormat]. As the data of
+    /// offsets cant be given as a list of numbers, [IfdYamlBlobFormat::List] falls back to base64.
+    pub fn dump_blob(&self, data: &[u8]) -> String {
+        match self.blob_format {
+            IfdYamlBlobFormat::Hex => {
+                let hex: String = data.iter().map(|b| format!("{b:02x}")).collect();
+                format!("!hex {hex}")
+            }
+            _ => format!("!!binary {}", base64::encode(data)),
+        }
+    }
+    /// Returns the bytes of an entry of a [IfdTypeInterpretation::Blob] tag if it can be dumped as
+    /// a blob, i.e. if it has the dtype the [crate::yaml::IfdYamlParser] reads blobs of that tag as
+    pub fn get_blob_bytes(entry: IfdEntryRef) -> Option<Vec<u8>> {
+        if !matches!(
+            entry.tag.get_type_interpretation(),
+            Some(IfdTypeInterpretation::Blob)
+        ) || matches!(
+            entry.path.as_vec().last(),
+            Some(IfdPathElement::ListIndex(_))
+        ) {
+            return None;
         }
+        let dtype = blob_dtype(entry.tag);
+        entry
+            .value
+            .as_list()
+            .map(|x| match x {
+                IfdValue::Byte(x) if dtype == IfdValueType::Byte => Some(*x),
+                IfdValue::Undefined(x) if dtype == IfdValueType::Undefined => Some(*x),
+                _ => None,
+            })
+            .collect()
+    }
+    fn get_inline_blob_bytes(&self, entry: IfdEntryRef) -> Option<Vec<u8>> {
+        if self.blob_format == IfdYamlBlobFormat::List {
+            None
+        } else {
+            Self::get_blob_bytes(entry)
+        }
+    }
+    /// Quotes a string so that it is read back unchanged
+    fn dump_string(x: &str) -> String {
+        if !x.contains(['"', '\\', '\n', '\r']) {
+            format!("\"{x}\"")
+        } else if !x.contains(['\n', '\r']) {
+            // single quoted strings dont have escape sequences apart from the quote itself
+            format!("'{}'", x.replace('\'', "''"))
+        } else {
+            let escaped = x
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            if escaped.ends_with('\\') {
+                // yaml_peg would read an escaped backslash followed by the closing quote as an
+                // escaped quote. a line break after a trailing backslash is ignored though.
+                format!("\"{escaped}\n\"")
+            } else {
+                format!("\"{escaped}\"")
+            }
+        }
+    }
+    /// Dumps the name of an enum value / flag, quoting it if it would otherwise be read as
+    /// something else (e.g. a number or two list elements)
+    fn dump_name_lossless(name: &str) -> String {
+        let is_plain = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && !name.contains(|c: char| ",[]{}#:'\"\\".contains(c) || c.is_control())
+            && !["true", "false", "null"].contains(&name.to_lowercase().as_str());
+        if is_plain {
+            name.to_string()
+        } else {
+            Self::dump_string(name)
+        }
+    }
+    fn is_unambiguous_bitflag(values: &[(u8, &str)], name: &str) -> bool {
+        let short_name = bitflag_name(name);
+        values
+            .iter()
+            .filter(|(_, v)| {
+                bitflag_name(v).eq_ignore_ascii_case(short_name)
+                    || v.eq_ignore_ascii_case(short_name)
+            })
+            .count()
+            == 1
+    }
+    fn is_unsigned(value: &IfdValue) -> bool {
+        matches!(
+            value,
+            IfdValue::Byte(_) | IfdValue::Short(_) | IfdValue::Long(_)
+        )
     }
     fn indent_yaml_list_item(x: String) -> String {
         let first_line: String = x.lines().take(1).collect();
@@ -153,7 +399,7 @@ impl IfdYamlDumper {
         format!(
             "\n- {}\n{}",
             first_line,
-            textwrap::indent(rest.trim(), "  ")
+            textwrap::indent(rest.trim_matches('\n'), "  ")
         )
     }
 }
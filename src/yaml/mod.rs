@@ -1,15 +1,308 @@
+mod base64;
+mod diff_printer;
 mod dumper;
 mod parser;
 
+pub use diff_printer::IfdDiffPrinter;
+pub use dumper::IfdYamlBlobFormat;
 pub use dumper::IfdYamlDumper;
+pub use dumper::IfdYamlVisitor;
 pub use parser::IfdYamlParser;
 pub use parser::IfdYamlParserError;
 
+use crate::tags::{IfdValueType, MaybeKnownIfdFieldDescriptor};
+
+/// The names of the colors a CFAPlaneColor entry can refer to (indexed by their numeric value)
+const CFA_COLOR_NAMES: [&str; 7] = ["Red", "Green", "Blue", "Cyan", "Magenta", "Yellow", "White"];
+
+/// The dtype the bytes of a blob (e.g. `!!binary` data) are read as for the given tag
+fn blob_dtype(tag: &MaybeKnownIfdFieldDescriptor) -> IfdValueType {
+    match tag.get_known_value_type().and_then(|types| types.first()) {
+        Some(IfdValueType::Undefined) => IfdValueType::Undefined,
+        _ => IfdValueType::Byte,
+    }
+}
+
+/// The short name of a bitflag (without the parenthesized description)
+fn bitflag_name(name: &str) -> &str {
+    name.split(" (").next().unwrap()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::yaml::dumper::IfdYamlDumper;
-    use crate::yaml::parser::IfdYamlParser;
+    use crate::ifd::strategies::{arb_ifd, IfdStrategyConfig};
+    use crate::ifd::IfdDiffOptions;
+    use crate::ifd::{Ifd, IfdValue};
+    use crate::tags::{ifd, IfdType, MaybeKnownIfdFieldDescriptor};
+    use crate::yaml::diff_printer::IfdDiffPrinter;
+    use crate::yaml::dumper::{IfdYamlBlobFormat, IfdYamlDumper};
+    use crate::yaml::parser::{IfdYamlParser, IfdYamlParserError};
+    use crate::{DngReader, DngReaderOptions};
+    use proptest::prelude::*;
     use std::fs;
+    use std::sync::Arc;
+
+    fn lossless_roundtrip(ifds: &[Ifd]) -> Result<(), String> {
+        for blob_format in [
+            IfdYamlBlobFormat::List,
+            IfdYamlBlobFormat::Base64,
+            IfdYamlBlobFormat::Hex,
+        ] {
+            let dumper = IfdYamlDumper {
+                lossless: true,
+                blob_format,
+                ..Default::default()
+            };
+            let dumped = dumper.dump_ifd_chain(ifds);
+            let parsed = IfdYamlParser::default()
+                .parse_chain_from_str(&dumped)
+                .map_err(|e| format!("{e} for\n{dumped}"))?;
+            if parsed != ifds {
+                return Err(format!("{parsed:#?} != {ifds:#?} for\n{dumped}"));
+            }
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn test_dump_parse_roundtrip(
+            ifd in arb_ifd(IfdType::Ifd, IfdStrategyConfig { text_safe: true, max_depth: 2 })
+        ) {
+            let dumped = IfdYamlDumper::default().dump_ifd(&ifd);
+            let parsed = IfdYamlParser::default().parse_from_str(&dumped);
+            prop_assert!(parsed.is_ok(), "{:?} for\n{}", parsed.err(), dumped);
+            prop_assert_eq!(parsed.unwrap(), ifd, "for\n{}", dumped);
+        }
+
+        #[test]
+        fn test_lossless_roundtrip(
+            ifd in arb_ifd(IfdType::Ifd, IfdStrategyConfig { text_safe: false, max_depth: 2 })
+        ) {
+            let result = lossless_roundtrip(&[ifd]);
+            prop_assert!(result.is_ok(), "{}", result.unwrap_err());
+        }
+    }
+
+    #[test]
+    fn test_lossless_corpus() {
+        let mut paths: Vec<_> = fs::read_dir("src/yaml/testdata")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.push("src/testdata/test.dng".into());
+        for path in paths {
+            // pentax_k30.dcp contains a broken GPSInfoIFD
+            let options = DngReaderOptions {
+                lenient: true,
+                ..Default::default()
+            };
+            let dng =
+                DngReader::read_with_options(fs::File::open(&path).unwrap(), options).unwrap();
+            lossless_roundtrip(dng.ifds()).unwrap_or_else(|e| panic!("{path:?}: {e}"));
+        }
+
+        // values the strategies dont generate
+        let mut ifd = Ifd::new(IfdType::Ifd);
+        ifd.insert(
+            ifd::StripOffsets,
+            IfdValue::Offsets(Arc::new(vec![1, 2, 3])),
+        );
+        ifd.insert(ifd::StripByteCounts, 3u32);
+        ifd.insert(ifd::Artist, "a \"quoted\" \\ 'string'");
+        ifd.insert(ifd::Copyright, "multiple\nlines ending in \\");
+        ifd.insert(ifd::ImageDescription, "\r\n\"\\");
+        ifd.insert(ifd::BaselineExposure, IfdValue::SRational(-1, 3));
+        let nan_with_payload = f32::from_bits(f32::NAN.to_bits() | 1);
+        let mut exif = Ifd::new(IfdType::Exif);
+        exif.insert(
+            MaybeKnownIfdFieldDescriptor::Unknown(0xFEED),
+            IfdValue::List(vec![
+                IfdValue::Float(f32::NAN),
+                IfdValue::Float(nan_with_payload),
+                IfdValue::Float(-0.0),
+                IfdValue::Float(f32::NEG_INFINITY),
+            ]),
+        );
+        ifd.insert(ifd::ExifIFD, IfdValue::Ifd(exif));
+        lossless_roundtrip(&[ifd]).unwrap();
+    }
+
+    #[test]
+    fn test_inline_blobs() {
+        let parser = IfdYamlParser::default();
+        let ifd = parser
+            .parse_from_str("JPEGTables: !hex 00ff 10\nDNGPrivateData: !!binary Zm9v\nOpcodeList1: !hex 01\nStripOffsets: !hex 123456\n")
+            .unwrap();
+        // the bytes get the dtype of the tag
+        let undefined = [0x00, 0xff, 0x10].map(IfdValue::Undefined).to_vec();
+        assert_eq!(ifd.get(ifd::JPEGTables), Some(&IfdValue::List(undefined)));
+        let bytes = b"foo".map(IfdValue::Byte).to_vec();
+        assert_eq!(ifd.get(ifd::DNGPrivateData), Some(&IfdValue::List(bytes)));
+        assert_eq!(ifd.get(ifd::OpcodeList1), Some(&IfdValue::Undefined(1)));
+        assert_eq!(
+            ifd.get(ifd::StripOffsets),
+            Some(&IfdValue::Offsets(Arc::new(vec![0x12, 0x34, 0x56])))
+        );
+
+        let dumper = IfdYamlDumper {
+            blob_format: IfdYamlBlobFormat::Hex,
+            ..Default::default()
+        };
+        assert_eq!(
+            dumper.dump_ifd(&ifd),
+            "JPEGTables: !hex 00ff10\nDNGPrivateData: !hex 666f6f\nOpcodeList1: !hex 01\nStripOffsets: !hex 123456\nStripByteCounts: 3\n"
+        );
+        assert!(parser.parse_from_str("JPEGTables: !hex 0\n").is_err());
+        assert!(parser
+            .parse_from_str("JPEGTables: !!binary \"\"\n")
+            .is_err());
+    }
+
+    #[test]
+    fn test_error_locations() {
+        let source = "Orientation: Sideways\nCopyright: \"ok\"\nExifIFD:\n  ExposureTime: abc\nNoSuchTag: 1\n";
+        let error = IfdYamlParser::default().parse_from_str(source).unwrap_err();
+        // parsing continues after an error, so all of them are reported
+        let locations: Vec<_> = error
+            .errors()
+            .iter()
+            .map(|e| match e {
+                IfdYamlParserError::Located { line, column, .. } => (*line, *column),
+                e => panic!("{e:?} is not located"),
+            })
+            .collect();
+        assert_eq!(locations, [(1, 14), (4, 17), (5, 1)]);
+        assert!(error.to_string().contains(
+            "4:17: couldn't parse 'abc' as RATIONAL\n(tag ExposureTime expects RATIONAL with count 1)\n  ExposureTime: abc\n                ^"
+        ));
+    }
+
+    #[test]
+    fn test_extends() {
+        let dir = std::env::temp_dir().join(format!("dng_test_extends_{}", std::process::id()));
+        fs::create_dir_all(dir.join("common")).unwrap();
+        fs::write(
+            dir.join("common/base.yml"),
+            "Make: \"base\"\nModel: \"base\"\nCopyright: \"base\"\n",
+        )
+        .unwrap();
+        // paths are relative to the extending file
+        fs::write(
+            dir.join("common/camera.yml"),
+            "$extends: base.yml\nModel: \"camera\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("common/cycle.yml"), "$extends: cycle.yml\n").unwrap();
+        fs::write(
+            dir.join("common/broken.yml"),
+            "Make: \"ok\"\nOrientation: Upwards\n",
+        )
+        .unwrap();
+
+        let parser = IfdYamlParser::new(dir.clone());
+        let ifd = parser
+            .parse_from_str("Artist: \"me\"\n$extends: [common/camera.yml]\nCopyright: \"own\"\n")
+            .unwrap();
+        assert_eq!(
+            IfdYamlDumper::default().dump_ifd(&ifd),
+            "Make: \"base\"\nModel: \"camera\"\nArtist: \"me\"\nCopyright: \"own\"\n"
+        );
+
+        let error = parser
+            .parse_from_str("$extends: common/cycle.yml\n")
+            .unwrap_err();
+        assert!(error.to_string().contains("extends itself"), "{error}");
+        let error = parser
+            .parse_from_str("Artist: \"me\"\n$extends: common/broken.yml\n")
+            .unwrap_err();
+        assert!(error.to_string().starts_with("2:11: in '"), "{error}");
+        assert!(error.to_string().contains("broken.yml':\n2:14:"), "{error}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_value() {
+        let parser = IfdYamlParser::default();
+        let parse =
+            |tag, source| parser.parse_value(MaybeKnownIfdFieldDescriptor::from(tag), source);
+        assert_eq!(parse(ifd::ImageWidth, "5").unwrap(), IfdValue::Short(5));
+        assert_eq!(parse(ifd::Orientation, "3").unwrap(), IfdValue::Short(3));
+        assert_eq!(
+            parse(ifd::Orientation, "RowTopColumnLeft").unwrap(),
+            IfdValue::Short(1)
+        );
+        assert_eq!(
+            parse(ifd::AsShotNeutral, "1/3").unwrap(),
+            IfdValue::Rational(1, 3)
+        );
+        assert_eq!(
+            parse(ifd::DNGVersion, "[1, 4, 0, 0]").unwrap(),
+            IfdValue::List([1u8, 4, 0, 0].map(IfdValue::Byte).to_vec())
+        );
+        assert!(parse(ifd::Orientation, "Upwards").is_err());
+    }
+
+    #[test]
+    fn test_chain_roundtrip() {
+        let parser = IfdYamlParser::default();
+        let ifds = parser
+            .parse_chain_from_str(
+                "- Copyright: \"first\"\n- Copyright: \"second\"\n  Artist: \"someone\"\n",
+            )
+            .unwrap();
+        assert_eq!(ifds.len(), 2);
+
+        let dumped = IfdYamlDumper::default().dump_ifd_chain(&ifds);
+        assert_eq!(
+            dumped,
+            "Copyright: \"first\"\n---\nCopyright: \"second\"\nArtist: \"someone\"\n"
+        );
+        assert_eq!(parser.parse_chain_from_str(&dumped).unwrap(), ifds);
+        assert_eq!(
+            parser
+                .parse_chain_from_str("Copyright: \"single\"\n")
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_bitflags_and_cfa_pattern() {
+        let parser = IfdYamlParser::default();
+        let ifd = parser
+            .parse_from_str(
+                "NewSubfileType: [ReducedResolution, mask, bit 7]\nCFARepeatPatternDim: [2, 2]\nCFAPattern: [[Red, Green], [Green, Blue]]\n",
+            )
+            .unwrap();
+        assert_eq!(
+            ifd.get(ifd::NewSubfileType),
+            Some(&IfdValue::Long(0b10000101))
+        );
+        let pattern = [0u8, 1, 1, 2].map(IfdValue::Byte).to_vec();
+        assert_eq!(ifd.get(ifd::CFAPattern), Some(&IfdValue::List(pattern)));
+
+        let dumped = IfdYamlDumper::default().dump_ifd(&ifd);
+        assert_eq!(
+            dumped,
+            "NewSubfileType: [ReducedResolution, Mask, bit 7]\nCFARepeatPatternDim: [2, 2]\nCFAPattern: [[Red, Green], [Green, Blue]]\n"
+        );
+
+        // the names refer to the planes given by CFAPlaneColor and plain numbers are still accepted
+        let ifd = parser
+            .parse_from_str("NewSubfileType: 0\nCFAPlaneColor: [3, 5, 4]\nCFARepeatPatternDim: [1, 3]\nCFAPattern: [[Magenta, Cyan, 1]]\n")
+            .unwrap();
+        let pattern = [2u8, 0, 1].map(IfdValue::Byte).to_vec();
+        assert_eq!(ifd.get(ifd::CFAPattern), Some(&IfdValue::List(pattern)));
+        assert_eq!(
+            IfdYamlDumper::default().dump_ifd(&ifd),
+            "NewSubfileType: []\nCFAPlaneColor: [3, 5, 4]\nCFARepeatPatternDim: [1, 3]\nCFAPattern: [[Magenta, Cyan, Yellow]]\n"
+        );
+        assert!(parser
+            .parse_from_str("CFAPattern: [[Red, Green], [Blue]]\n")
+            .is_err());
+    }
 
     #[test]
     fn test_axiom_beta_sim_yaml() {
@@ -39,4 +332,36 @@ mod tests {
             .parse_from_str(&serialized)
             .unwrap();
     }
+
+    #[test]
+    fn test_diff_printer() {
+        let mut old = Ifd::new(IfdType::Ifd);
+        old.insert(ifd::Copyright, "old");
+        old.insert(ifd::Orientation, 1u16);
+        let mut new = old.clone();
+        new.insert(ifd::Copyright, "new");
+        let mut ifd1 = Ifd::new(IfdType::Ifd);
+        ifd1.insert(ifd::Artist, "someone");
+
+        let printer = IfdDiffPrinter::default();
+        let diff = printer.ifd_chain(
+            &[old.clone()],
+            &[new.clone(), ifd1.clone()],
+            &Default::default(),
+        );
+        assert_eq!(
+            diff,
+            "-IFD0.Copyright: \"old\"\n+IFD0.Copyright: \"new\"\n+IFD1: added IFD\n+IFD1.Artist: \"someone\"\n"
+        );
+        let diff = printer.ifd_chain(
+            &[new.clone(), ifd1],
+            &[new.clone()],
+            &IfdDiffOptions::default(),
+        );
+        assert_eq!(diff, "-IFD1: removed IFD\n-IFD1.Artist: \"someone\"\n");
+        assert_eq!(
+            printer.ifd_chain(&[new.clone()], &[new], &IfdDiffOptions::default()),
+            ""
+        );
+    }
 }
@@ -1,6 +1,9 @@
 use crate::ifd::IfdPath;
 use crate::ifd::{Ifd, IfdValue};
-use crate::tags::{IfdType, IfdTypeInterpretation, IfdValueType, MaybeKnownIfdFieldDescriptor};
+use crate::tags::{
+    ifd, IfdCount, IfdType, IfdTypeInterpretation, IfdValueType, MaybeKnownIfdFieldDescriptor,
+};
+use crate::yaml::{base64, bitflag_name, blob_dtype, CFA_COLOR_NAMES};
 use fraction::Ratio;
 use lazy_regex::regex_captures;
 use std::error::Error;
@@ -9,7 +12,7 @@ use std::fs::File;
 use std::io;
 use std::io::Read;
 use std::iter::once;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use yaml_peg::parser::parse;
 use yaml_peg::parser::PError;
@@ -21,7 +24,90 @@ use yaml_peg::Node;
 pub enum IfdYamlParserError {
     PError(PError),
     IoError(io::Error),
+    /// An error at a byte position of the source
     Other(u64, String),
+    /// An error resolved to its (1-based) line and column in the source together with the line
+    /// it occurred in
+    Located {
+        line: usize,
+        column: usize,
+        snippet: String,
+        message: String,
+    },
+    /// All errors found in a source, as the parser continues with the next entry after an error
+    Multiple(Vec<IfdYamlParserError>),
+}
+
+impl IfdYamlParserError {
+    /// Resolves the byte positions of [IfdYamlParserError::Other] errors to lines and columns of
+    /// the source they were produced for
+    pub fn locate(self, source: &str) -> Self {
+        match self {
+            Self::Other(pos, message) => {
+                let mut pos = (pos as usize).min(source.len());
+                while !source.is_char_boundary(pos) {
+                    pos -= 1;
+                }
+                let line_start = source[..pos].rfind('\n').map_or(0, |i| i + 1);
+                let line_end = source[pos..].find('\n').map_or(source.len(), |i| pos + i);
+                Self::Located {
+                    line: source[..pos].matches('\n').count() + 1,
+                    column: source[line_start..pos].chars().count() + 1,
+                    snippet: source[line_start..line_end].trim_end().to_string(),
+                    message,
+                }
+            }
+            Self::Multiple(mut errors) if errors.len() == 1 => errors.remove(0).locate(source),
+            Self::Multiple(errors) => {
+                Self::Multiple(errors.into_iter().map(|e| e.locate(source)).collect())
+            }
+            e => e,
+        }
+    }
+
+    /// Returns the individual errors (a single one unless this is [IfdYamlParserError::Multiple])
+    pub fn errors(&self) -> Vec<&IfdYamlParserError> {
+        match self {
+            Self::Multiple(errors) => errors.iter().flat_map(|e| e.errors()).collect(),
+            e => vec![e],
+        }
+    }
+
+    /// Adds what the tag expects to errors in its value
+    fn in_tag(self, tag: MaybeKnownIfdFieldDescriptor) -> Self {
+        match (self, tag) {
+            (Self::Other(pos, message), MaybeKnownIfdFieldDescriptor::Known(descriptor)) => {
+                let dtypes: Vec<_> = descriptor.dtype.iter().map(IfdValueType::name).collect();
+                let count = match descriptor.count {
+                    IfdCount::N => "any count".to_string(),
+                    IfdCount::ConcreteValue(count) => format!("count {count}"),
+                };
+                Self::Other(
+                    pos,
+                    format!(
+                        "{message}\n(tag {tag} expects {} with {count})",
+                        dtypes.join(" or ")
+                    ),
+                )
+            }
+            (e, _) => e,
+        }
+    }
+
+    /// Combines the errors collected while parsing into one
+    fn collect(errors: Vec<Self>) -> Result<(), Self> {
+        let errors: Vec<_> = errors
+            .into_iter()
+            .flat_map(|e| match e {
+                Self::Multiple(errors) => errors,
+                e => vec![e],
+            })
+            .collect();
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(Self::Multiple(errors)),
+        }
+    }
 }
 
 impl From<PError> for IfdYamlParserError {
@@ -49,6 +135,31 @@ impl Display for IfdYamlParserError {
                 f.write_fmt(format_args!("Other Error at {pos}: {e}"))
             }
             IfdYamlParserError::IoError(e) => f.write_fmt(format_args!("IoError '{}'", e)),
+            IfdYamlParserError::Located {
+                line,
+                column,
+                snippet,
+                message,
+            } => {
+                // the caret is placed below the start of the offending value
+                let caret_indent: String = snippet
+                    .chars()
+                    .take(column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                f.write_fmt(format_args!(
+                    "{line}:{column}: {message}\n{snippet}\n{caret_indent}^"
+                ))
+            }
+            IfdYamlParserError::Multiple(errors) => {
+                for (i, e) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("\n\n")?;
+                    }
+                    f.write_fmt(format_args!("{e}"))?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -58,7 +169,7 @@ impl Error for IfdYamlParserError {
         match self {
             IfdYamlParserError::PError(pe) => Some(pe),
             IfdYamlParserError::IoError(ioe) => Some(ioe),
-            IfdYamlParserError::Other(_, _) => None,
+            _ => None,
         }
     }
 }
@@ -69,21 +180,78 @@ macro_rules! err {
     };
 }
 
+/// The key with which an IFD can name YAML files (relative to the including file) it extends.
+/// The entries of these files are inserted first and then overwritten by the ones of the IFD.
+const EXTENDS_KEY: &str = "$extends";
+
 /// Parses an [Ifd] struct from a friendly human readable text-representation as produced by the [crate::yaml::IfdYamlDumper]
+///
+/// An IFD can extend other YAML files with a `$extends: base.yml` (or a list of files) entry. The
+/// entries of the IFD overwrite the ones of the extended files like [Ifd::insert_from_other] does.
 #[derive(Default)]
 pub struct IfdYamlParser {
     path: PathBuf,
+    /// the files that are currently extended (to detect cycles)
+    extending: Vec<PathBuf>,
 }
 impl IfdYamlParser {
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
+        Self {
+            path,
+            extending: Vec::new(),
+        }
     }
 
     pub fn parse_from_str(&self, source: &str) -> Result<Ifd, IfdYamlParserError> {
         let parsed_yaml = parse(source)?;
         self.parse_ifd(&parsed_yaml[0], IfdType::Ifd, IfdPath::default())
+            .map_err(|e| e.locate(source))
+    }
+
+    /// Parses all toplevel IFDs of a file. The IFDs can either be given as separate YAML documents
+    /// (as produced by [crate::yaml::IfdYamlDumper::dump_ifd_chain]) or as a toplevel list.
+    pub fn parse_chain_from_str(&self, source: &str) -> Result<Vec<Ifd>, IfdYamlParserError> {
+        let parsed_yaml = parse(source)?;
+        let documents = match &parsed_yaml[..] {
+            [document] => match document.as_seq() {
+                Ok(seq) => seq.to_vec(),
+                Err(_) => vec![document.clone()],
+            },
+            documents => documents.to_vec(),
+        };
+        if documents.is_empty() {
+            return Err(err!(0, "the source does not contain any IFD").locate(source));
+        }
+        let mut ifds = Vec::new();
+        let mut errors = Vec::new();
+        for (i, document) in documents.iter().enumerate() {
+            match self.parse_ifd(document, IfdType::Ifd, IfdPath::chain_index(i as u16)) {
+                Ok(ifd) => ifds.push(ifd),
+                Err(e) => errors.push(e),
+            }
+        }
+        IfdYamlParserError::collect(errors).map_err(|e| e.locate(source))?;
+        Ok(ifds)
     }
 
+    /// Parses a single value for the given tag from its text representation (e.g. `1/50`,
+    /// `[1, 2, 3]` or the name of an enum variant), using the same rules as for whole IFDs
+    pub fn parse_value(
+        &self,
+        tag: MaybeKnownIfdFieldDescriptor,
+        source: &str,
+    ) -> Result<IfdValue, IfdYamlParserError> {
+        // yaml_peg doesnt read a bare integer scalar at the end of the input without a line break
+        let source = &format!("{source}\n");
+        let parsed_yaml = parse(source)?;
+        let value = parsed_yaml
+            .first()
+            .ok_or_else(|| err!(0, "the source does not contain a value"))?;
+        self.parse_ifd_entry(value, tag, IfdPath::default(), None, None)
+            .map_err(|e| e.in_tag(tag).locate(source))
+    }
+
+    /// Parses an IFD. All errors in its entries are collected in a [IfdYamlParserError::Multiple]
     fn parse_ifd(
         &self,
         source: &Node<RcRepr>,
@@ -91,71 +259,232 @@ impl IfdYamlParser {
         path: IfdPath,
     ) -> Result<Ifd, IfdYamlParserError> {
         let mut ifd = Ifd::new(ifd_type);
+        let mut errors = Vec::new();
+        // CFA patterns given as a grid of color names can only be resolved once CFAPlaneColor is known
+        let mut cfa_grids = Vec::new();
+        let mut extended = None;
         for (key, value) in source
             .as_map()
             .map_err(|pos| err!(pos, "cant read {source:?} as map (required for ifd)"))?
             .iter()
         {
-            let tag = self.parse_ifd_tag(key, ifd_type)?;
+            if key.as_str() == Ok(EXTENDS_KEY) {
+                extended = Some(value.clone());
+                continue;
+            }
+            let result = self.parse_ifd_tag(key, ifd_type).and_then(|tag| {
+                self.parse_ifd_field(&mut ifd, &mut cfa_grids, tag, value, &path)
+                    .map_err(|e| e.in_tag(tag))
+            });
+            if let Err(e) = result {
+                errors.push(e);
+            }
+        }
+        for (tag, grid) in cfa_grids {
+            match Self::parse_cfa_pattern(&grid, tag, &ifd) {
+                Ok(pattern) => {
+                    ifd.replace_by_path(&IfdPath::default().chain_tag(tag), pattern);
+                }
+                Err(e) => errors.push(e.in_tag(tag)),
+            }
+        }
+        IfdYamlParserError::collect(errors)?;
 
-            // if we have offsets we need to emit two tags (offsets and lengths), thus we need to handle this directly
-            if let Some(IfdTypeInterpretation::Offsets { lengths }) = tag.get_type_interpretation()
-            {
-                let parse_offset_entry = |value: &Node<RcRepr>| -> Result<
-                    Option<(IfdValue, IfdValue)>,
-                    IfdYamlParserError,
-                > {
-                    let str = if let Ok(str) = value.as_str() {
-                        str
-                    } else {
-                        return Ok(None);
-                    };
-                    if let Some((_whole, file_path)) = regex_captures!("file://(.*)", str) {
-                        let file_path = self.path.join(file_path);
-                        let mut file = File::open(file_path)?;
-                        let mut buffer = Vec::new();
-                        file.read_to_end(&mut buffer)?;
-                        let len = buffer.len();
-                        let offsets_entry = IfdValue::Offsets(Arc::new(buffer));
-                        let lengths_entry = IfdValue::Long(len as u32);
-                        Ok(Some((offsets_entry, lengths_entry)))
-                    } else {
-                        Ok(None)
-                    }
+        if let Some(files) = extended {
+            let mut base = self.parse_extended(&files, ifd_type, &path)?;
+            base.insert_from_other(ifd);
+            ifd = base;
+        }
+        Ok(ifd)
+    }
+
+    /// Parses the files given by `$extends` into one IFD (later files overwrite earlier ones)
+    fn parse_extended(
+        &self,
+        files: &Node<RcRepr>,
+        ifd_type: IfdType,
+        path: &IfdPath,
+    ) -> Result<Ifd, IfdYamlParserError> {
+        let files = match files.as_seq() {
+            Ok(seq) => seq.to_vec(),
+            Err(_) => vec![files.clone()],
+        };
+        let mut base = Ifd::new(ifd_type);
+        for file in files {
+            let file_path = file
+                .as_str()
+                .map_err(|pos| err!(pos, "expected the path of a YAML file to extend"))?;
+            let file_path = self.path.join(file_path);
+            let file_path = file_path.canonicalize().unwrap_or(file_path);
+            if self.extending.contains(&file_path) {
+                return Err(err!(file.pos(), "'{}' extends itself", file_path.display()));
+            }
+            let source = String::from_utf8(Self::read_file(&file, &file_path)?)
+                .map_err(|e| err!(file.pos(), "cant read '{}': {e}", file_path.display()))?;
+            let parser = IfdYamlParser {
+                path: file_path.parent().unwrap_or(Path::new("")).to_path_buf(),
+                extending: self
+                    .extending
+                    .iter()
+                    .cloned()
+                    .chain(once(file_path.clone()))
+                    .collect(),
+            };
+            let ifd = parse(&source)
+                .map_err(IfdYamlParserError::from)
+                .and_then(|documents| match documents.first() {
+                    Some(document) => parser.parse_ifd(document, ifd_type, path.clone()),
+                    None => Err(err!(0, "the file does not contain an IFD")),
+                })
+                .map_err(|e| {
+                    let e = e.locate(&source);
+                    err!(file.pos(), "in '{}':\n{e}", file_path.display())
+                })?;
+            base.insert_from_other(ifd);
+        }
+        Ok(base)
+    }
+
+    /// Parses a single entry of an IFD and inserts it (and e.g. the lengths of offsets) into `ifd`
+    fn parse_ifd_field(
+        &self,
+        ifd: &mut Ifd,
+        cfa_grids: &mut Vec<(MaybeKnownIfdFieldDescriptor, Node<RcRepr>)>,
+        tag: MaybeKnownIfdFieldDescriptor,
+        value: &Node<RcRepr>,
+        path: &IfdPath,
+    ) -> Result<(), IfdYamlParserError> {
+        // if we have offsets we need to emit two tags (offsets and lengths), thus we need to handle this directly
+        if let Some(IfdTypeInterpretation::Offsets { lengths }) = tag.get_type_interpretation() {
+            let parse_offset_entry = |value: &Node<RcRepr>| -> Result<
+                Option<(IfdValue, IfdValue)>,
+                IfdYamlParserError,
+            > {
+                if let Some(buffer) = Self::parse_inline_blob(value)? {
+                    let len = buffer.len();
+                    let offsets_entry = IfdValue::Offsets(Arc::new(buffer));
+                    let lengths_entry = IfdValue::Long(len as u32);
+                    return Ok(Some((offsets_entry, lengths_entry)));
+                }
+                let str = if let Ok(str) = value.as_str() {
+                    str
+                } else {
+                    return Ok(None);
                 };
+                if let Some((_whole, file_path)) = regex_captures!("file://(.*)", str) {
+                    let buffer = Self::read_file(value, &self.path.join(file_path))?;
+                    let len = buffer.len();
+                    let offsets_entry = IfdValue::Offsets(Arc::new(buffer));
+                    let lengths_entry = IfdValue::Long(len as u32);
+                    Ok(Some((offsets_entry, lengths_entry)))
+                } else {
+                    Ok(None)
+                }
+            };
 
-                match value.as_seq() {
-                    Ok(seq) => {
-                        let mapped: Result<Vec<_>, IfdYamlParserError> =
-                            seq.iter().map(parse_offset_entry).collect();
-                        let mapped = mapped?;
-                        if mapped.iter().all(|x| x.is_some()) {
-                            let (offsets, lengths_values): (Vec<_>, Vec<_>) =
-                                mapped.into_iter().map(|x| x.unwrap()).unzip();
-                            ifd.insert(tag, IfdValue::List(offsets));
-                            ifd.insert(lengths.as_maybe(), IfdValue::List(lengths_values));
-                            continue;
-                        } else {
-                            return Err(err!(source.pos(), "not all buffers could be read"));
-                        }
+            match value.as_seq() {
+                Ok(seq) => {
+                    let mapped: Result<Vec<_>, IfdYamlParserError> =
+                        seq.iter().map(parse_offset_entry).collect();
+                    let mapped = mapped?;
+                    if mapped.iter().all(|x| x.is_some()) {
+                        let (offsets, lengths_values): (Vec<_>, Vec<_>) =
+                            mapped.into_iter().map(|x| x.unwrap()).unzip();
+                        ifd.insert(tag, IfdValue::List(offsets));
+                        ifd.insert(lengths.as_maybe(), IfdValue::List(lengths_values));
+                        return Ok(());
+                    } else {
+                        return Err(err!(value.pos(), "not all buffers could be read"));
                     }
-                    Err(_) => {
-                        if let Some((offsets_value, lengths_value)) = parse_offset_entry(value)? {
-                            ifd.insert(tag, offsets_value);
-                            ifd.insert(lengths.as_maybe(), lengths_value);
-                            continue;
-                        }
+                }
+                Err(_) => {
+                    if let Some((offsets_value, lengths_value)) = parse_offset_entry(value)? {
+                        ifd.insert(tag, offsets_value);
+                        ifd.insert(lengths.as_maybe(), lengths_value);
+                        return Ok(());
                     }
                 }
             }
+        }
 
-            ifd.insert(
-                tag,
-                self.parse_ifd_entry(value, tag, path.clone(), None, None)?,
-            )
+        if let Some(IfdTypeInterpretation::CfaPattern) = tag.get_type_interpretation() {
+            if let Ok(rows) = value.as_seq() {
+                if !rows.is_empty() && rows.iter().all(|row| row.as_seq().is_ok()) {
+                    // insert a placeholder to keep the order of the entries
+                    ifd.insert(tag, IfdValue::List(Vec::new()));
+                    cfa_grids.push((tag, value.clone()));
+                    return Ok(());
+                }
+            }
         }
 
-        Ok(ifd)
+        ifd.insert(
+            tag,
+            self.parse_ifd_entry(value, tag, path.clone(), None, None)?,
+        );
+        Ok(())
+    }
+
+    /// Parses a CFAPattern given as rows of color names (or plane numbers), e.g.
+    /// `[[Red, Green], [Green, Blue]]`. The names are mapped to planes using the CFAPlaneColor of
+    /// the IFD.
+    fn parse_cfa_pattern(
+        grid: &Node<RcRepr>,
+        tag: MaybeKnownIfdFieldDescriptor,
+        ifd: &Ifd,
+    ) -> Result<IfdValue, IfdYamlParserError> {
+        let plane_colors: Vec<u32> = match ifd.get(ifd::CFAPlaneColor) {
+            Some(colors) => colors.as_list().filter_map(|x| x.as_u32()).collect(),
+            None => vec![0, 1, 2],
+        };
+        let dtype = tag
+            .get_known_value_type()
+            .and_then(|types| types.first())
+            .copied()
+            .unwrap_or(IfdValueType::Byte);
+
+        let rows = grid
+            .as_seq()
+            .map_err(|pos| err!(pos, "cant read {grid:?} as list"))?;
+        let mut pattern = Vec::new();
+        for row in rows.iter() {
+            let row = row
+                .as_seq()
+                .map_err(|pos| err!(pos, "cant read {row:?} as a row of the CFA pattern"))?;
+            if row.len() != rows[0].as_seq().unwrap().len() {
+                return Err(err!(
+                    grid.pos(),
+                    "the rows of the CFA pattern differ in length"
+                ));
+            }
+            for cell in row.iter() {
+                let plane = if let Ok(plane) = cell.as_int() {
+                    plane
+                } else {
+                    let name = cell
+                        .as_str()
+                        .map_err(|pos| err!(pos, "cant read '{cell:?}' as a CFA color"))?;
+                    let color = CFA_COLOR_NAMES
+                        .iter()
+                        .position(|color| color.eq_ignore_ascii_case(name))
+                        .ok_or_else(|| {
+                            err!(cell.pos(), "'{name}' is not a CFA color.\nPossible colors are: {CFA_COLOR_NAMES:?}")
+                        })?;
+                    plane_colors
+                        .iter()
+                        .position(|c| *c == color as u32)
+                        .ok_or_else(|| {
+                            err!(cell.pos(), "'{name}' is not one of the planes in CFAPlaneColor {plane_colors:?}")
+                        })? as i64
+                };
+                let plane: u8 = plane.try_into().map_err(|e| err!(cell.pos(), "{e:?}"))?;
+                pattern.push(match dtype {
+                    IfdValueType::Undefined => IfdValue::Undefined(plane),
+                    _ => IfdValue::Byte(plane),
+                });
+            }
+        }
+        Ok(IfdValue::List(pattern))
     }
 
     fn parse_ifd_tag(
@@ -191,6 +520,12 @@ impl IfdYamlParser {
         parent_yaml_tag: Option<&str>,
         force_type: Option<IfdValueType>,
     ) -> Result<IfdValue, IfdYamlParserError> {
+        if let Some(IfdTypeInterpretation::Bitflags { values }) = tag.get_type_interpretation() {
+            if let Some(flags) = Self::parse_bitflags(value, values, tag, force_type)? {
+                return Ok(flags);
+            }
+        }
+
         Ok(if value.as_map().is_ok() {
             let ifd_type = if let Some(IfdTypeInterpretation::IfdOffset { ifd_type }) =
                 tag.get_type_interpretation()
@@ -243,14 +578,14 @@ impl IfdYamlParser {
             #[allow(clippy::never_loop)]
             loop {
                 // this is the 'well-known' loop hack
-                // we try to parse the value as a file
+                // we try to parse the value as an inline blob or a file
+                if let Some(buffer) = Self::parse_inline_blob(value)? {
+                    break Self::blob_value(value, buffer, tag)?;
+                }
                 if let Ok(str) = value.as_str() {
                     if let Some((_whole, file_path)) = regex_captures!("file://(.*)", str) {
-                        let file_path = self.path.join(file_path);
-                        let mut file = File::open(file_path)?;
-                        let mut buffer = Vec::new();
-                        file.read_to_end(&mut buffer)?;
-                        break IfdValue::List(buffer.iter().map(|b| IfdValue::Byte(*b)).collect());
+                        let buffer = Self::read_file(value, &self.path.join(file_path))?;
+                        break Self::blob_value(value, buffer, tag)?;
                     }
                 }
 
@@ -260,6 +595,111 @@ impl IfdYamlParser {
         })
     }
 
+    fn read_file(value: &Node<RcRepr>, path: &Path) -> Result<Vec<u8>, IfdYamlParserError> {
+        let mut buffer = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut buffer))
+            .map_err(|e| err!(value.pos(), "couldnt read '{}': {e}", path.display()))?;
+        Ok(buffer)
+    }
+
+    /// Reads the data of an inline blob (`!!binary` base64 or `!hex`). Returns None for other values.
+    fn parse_inline_blob(value: &Node<RcRepr>) -> Result<Option<Vec<u8>>, IfdYamlParserError> {
+        let decode = match value.tag() {
+            "tag:yaml.org,2002:binary" => base64::decode,
+            "hex" => decode_hex,
+            _ => return Ok(None),
+        };
+        // blobs consisting only of digits are read as numbers by yaml_peg, so we use the raw text
+        let str = value
+            .as_value()
+            .map_err(|pos| err!(pos, "cant read '{value:?}' as blob"))?;
+        decode(str).map(Some).map_err(|e| err!(value.pos(), "{e}"))
+    }
+
+    /// Turns the bytes of a blob into a value of the dtype blobs of the tag are read as
+    fn blob_value(
+        value: &Node<RcRepr>,
+        buffer: Vec<u8>,
+        tag: MaybeKnownIfdFieldDescriptor,
+    ) -> Result<IfdValue, IfdYamlParserError> {
+        let to_value = match blob_dtype(&tag) {
+            IfdValueType::Undefined => IfdValue::Undefined,
+            _ => IfdValue::Byte,
+        };
+        match buffer[..] {
+            [] => Err(err!(value.pos(), "the blob for tag '{tag}' is empty")),
+            [byte] => Ok(to_value(byte)),
+            _ => Ok(IfdValue::List(buffer.into_iter().map(to_value).collect())),
+        }
+    }
+
+    /// Parses bitflags given as a list of flag names (or `bit <n>` for unnamed flags). Returns None
+    /// if the value is not such a list (e.g. a plain number).
+    fn parse_bitflags(
+        value: &Node<RcRepr>,
+        values: &[(u8, &str)],
+        tag: MaybeKnownIfdFieldDescriptor,
+        force_type: Option<IfdValueType>,
+    ) -> Result<Option<IfdValue>, IfdYamlParserError> {
+        let seq = match value.as_seq() {
+            // lists of numbers are plain values (e.g. for tags with a count > 1)
+            Ok(seq) if seq.iter().all(|node| node.as_int().is_err()) => seq,
+            _ => return Ok(None),
+        };
+        let mut flags = 0u32;
+        for node in seq.iter() {
+            let name = node
+                .as_str()
+                .map_err(|pos| err!(pos, "cant read '{node:?}' as a flag name"))?;
+            let bit = match name.strip_prefix("bit ") {
+                Some(bit) => bit
+                    .trim()
+                    .parse()
+                    .map_err(|_| err!(node.pos(), "couldnt parse '{name}' as a bit number"))?,
+                None => values
+                    .iter()
+                    .find(|(_, v)| {
+                        bitflag_name(v).eq_ignore_ascii_case(name) || v.eq_ignore_ascii_case(name)
+                    })
+                    .map(|(bit, _)| *bit as u32)
+                    .ok_or_else(|| {
+                        let names: Vec<_> = values.iter().map(|(_, v)| bitflag_name(v)).collect();
+                        err!(
+                            node.pos(),
+                            "'{name}' is not a flag of tag {tag}.\nPossible flags are: {names:?}"
+                        )
+                    })?,
+            };
+            flags |= 1u32
+                .checked_shl(bit)
+                .ok_or_else(|| err!(node.pos(), "bit {bit} doesnt fit into 32 bits"))?;
+        }
+
+        let dtypes = match force_type.or_else(|| Self::parse_ifd_value_type(value.tag())) {
+            Some(ty) => vec![ty],
+            None => tag.get_known_value_type().unwrap_or(&[]).to_vec(),
+        };
+        for dtype in dtypes {
+            match dtype {
+                IfdValueType::Byte => match u8::try_from(flags) {
+                    Ok(flags) => return Ok(Some(IfdValue::Byte(flags))),
+                    Err(_) => continue,
+                },
+                IfdValueType::Short => match u16::try_from(flags) {
+                    Ok(flags) => return Ok(Some(IfdValue::Short(flags))),
+                    Err(_) => continue,
+                },
+                IfdValueType::Long => return Ok(Some(IfdValue::Long(flags))),
+                _ => {}
+            }
+        }
+        Err(err!(
+            value.pos(),
+            "the flags {flags:#X} dont fit into any dtype of tag '{tag}'"
+        ))
+    }
+
     fn parse_ifd_scalar_value(
         &self,
         value: &Node<RcRepr>,
@@ -280,13 +720,32 @@ impl IfdYamlParser {
 
         match tag.get_type_interpretation() {
             Some(IfdTypeInterpretation::Enumerated { values }) => {
+                let dtypes: Vec<_> = dtypes.collect();
                 let str = value
-                    .as_str()
+                    .as_value()
                     .map_err(|pos| err!(pos, "cant read '{value:?}' as a string"))?;
-                let matching_values: Vec<_> = values
+                // an exact match always wins, then plain numbers (e.g. values without a name) and
+                // otherwise any variant containing the string is accepted as long as it is unambiguous
+                let exact_match: Vec<_> = values
                     .iter()
-                    .filter(|(_, v)| v.to_lowercase().contains(&str.to_lowercase()))
+                    .filter(|(_, v)| v.eq_ignore_ascii_case(str))
                     .collect();
+                if exact_match.len() != 1 {
+                    let numeric = dtypes
+                        .iter()
+                        .find_map(|dtype| self.parse_ifd_primitive_value(value, *dtype).ok());
+                    if let Some(numeric) = numeric {
+                        return Ok(numeric);
+                    }
+                }
+                let matching_values: Vec<_> = if exact_match.len() == 1 {
+                    exact_match
+                } else {
+                    values
+                        .iter()
+                        .filter(|(_, v)| v.to_lowercase().contains(&str.to_lowercase()))
+                        .collect()
+                };
                 let (numeric, _) = match matching_values.len() {
                     0 => Err(err!(value.pos(), "'{str}' didnt match any enum variant for field {tag}.\nPossible variants are: {values:?}"))?,
                     1 => matching_values[0],
@@ -304,37 +763,28 @@ impl IfdYamlParser {
                 Err(err!(value.pos(), "No dtype worked"))
             }
             _ => {
-                let mut errors = String::new();
+                let mut errors = Vec::new();
                 for dtype in dtypes {
                     match self.parse_ifd_primitive_value(value, dtype) {
                         Ok(v) => return Ok(v),
-                        Err(err) => errors += &format!("\t* {err}\n"),
+                        Err(IfdYamlParserError::Other(_, message)) => errors.push(message),
+                        Err(err) => errors.push(err.to_string()),
                     }
                 }
-                Err(err!(
-                    value.pos(),
-                    "No dtype worked for tag '{tag}'. Tried: \n {errors}"
-                ))
+                match &errors[..] {
+                    [error] => Err(err!(value.pos(), "{error}")),
+                    _ => Err(err!(
+                        value.pos(),
+                        "No dtype worked for tag '{tag}'. Tried:\n  * {}",
+                        errors.join("\n  * ")
+                    )),
+                }
             }
         }
     }
 
     fn parse_ifd_value_type(v: &str) -> Option<IfdValueType> {
-        match v {
-            "BYTE" => Some(IfdValueType::Byte),
-            "ASCII" => Some(IfdValueType::Ascii),
-            "SHORT" => Some(IfdValueType::Short),
-            "LONG" => Some(IfdValueType::Long),
-            "RATIONAL" => Some(IfdValueType::Rational),
-            "SBYTE" => Some(IfdValueType::SByte),
-            "UNDEFINED" => Some(IfdValueType::Undefined),
-            "SSHORT" => Some(IfdValueType::SShort),
-            "SLONG" => Some(IfdValueType::SLong),
-            "SRATIONAL" => Some(IfdValueType::SRational),
-            "FLOAT" => Some(IfdValueType::Float),
-            "DOUBLE" => Some(IfdValueType::Double),
-            _ => None,
-        }
+        IfdValueType::from_name(v)
     }
 
     fn parse_ifd_primitive_value(
@@ -348,9 +798,12 @@ impl IfdYamlParser {
 
         macro_rules! parse_int_like {
             ($value:ident, $name:literal) => {{
-                let int = $value
-                    .as_int()
-                    .map_err(|pos| err!(pos, "couldn't parse '{str}' as '{}'", $name))?;
+                // hex values (as produced by the dumper for UNDEFINED) are not supported by yaml_peg
+                let int = match str.strip_prefix("0x") {
+                    Some(hex) => i64::from_str_radix(hex, 16).map_err(|_| value.pos()),
+                    None => $value.as_int(),
+                }
+                .map_err(|pos| err!(pos, "couldn't parse '{str}' as '{}'", $name))?;
                 int.try_into().map_err(|e| err!(value.pos(), "{e:?}"))?
             }};
         }
@@ -412,15 +865,39 @@ impl IfdYamlParser {
                 }
             }
 
-            IfdValueType::Float => IfdValue::Float(match value.as_value() {
-                Ok(v) => v
-                    .parse()
-                    .map_err(|_e| err!(value.pos(), "couldn't parse '{str}' as FLOAT"))?,
-                Err(pos) => Err(err!(pos, "couldn't parse '{str}' as FLOAT"))?,
-            }),
-            IfdValueType::Double => IfdValue::Double(value.as_float().map_err(|pos| {
-                IfdYamlParserError::Other(pos, format!("couldn't parse '{str}' as DOUBLE"))
-            })?),
+            // floats can also be given by their bits in hex (as done for NaNs with a payload)
+            IfdValueType::Float => IfdValue::Float(
+                match str.strip_prefix("0x") {
+                    Some(bits) => u32::from_str_radix(bits, 16).map(f32::from_bits).ok(),
+                    None => str.parse().ok(),
+                }
+                .ok_or_else(|| err!(value.pos(), "couldn't parse '{str}' as FLOAT"))?,
+            ),
+            IfdValueType::Double => IfdValue::Double(
+                match str.strip_prefix("0x") {
+                    Some(bits) => u64::from_str_radix(bits, 16).map(f64::from_bits).ok(),
+                    None => str.parse().ok(),
+                }
+                .ok_or_else(|| err!(value.pos(), "couldn't parse '{str}' as DOUBLE"))?,
+            ),
         })
     }
 }
+
+fn decode_hex(encoded: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = encoded.chars().filter(|c| !c.is_whitespace()).collect();
+    digits
+        .chunks(2)
+        .map(|pair| {
+            if pair.len() != 2 {
+                return Err("hex data has an odd number of digits".to_string());
+            }
+            let pair: String = pair.iter().collect();
+            // from_str_radix would also accept a sign
+            if !pair.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("'{pair}' is not a valid hex byte"));
+            }
+            Ok(u8::from_str_radix(&pair, 16).unwrap())
+        })
+        .collect()
+}